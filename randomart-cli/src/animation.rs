@@ -0,0 +1,154 @@
+//! Writers for the frames `animate` renders, one per `--format`.
+
+use anyhow::{Context, Result};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbImage, RgbaImage};
+use randomart_core::pixel_buffer::PixelBuffer;
+use std::io::Write;
+
+/// The names `--format` accepts.
+pub const FORMATS: &[&str] = &["gif", "apng", "y4m"];
+
+/// The file extension for `format`.
+pub fn extension(format: &str) -> &'static str {
+    match format {
+        "gif" => "gif",
+        "apng" => "png",
+        "y4m" => "y4m",
+        _ => unreachable!("clap only accepts FORMATS"),
+    }
+}
+
+/// Write `frames`, all the same size, to `out` as `format`, looping forever
+/// where the format can say so.
+pub fn write(format: &str, frames: Vec<PixelBuffer>, fps: u32, out: impl Write) -> Result<()> {
+    match format {
+        "gif" => write_gif(frames, fps, out),
+        "apng" => write_apng(frames, fps, out),
+        "y4m" => write_y4m(frames, fps, out),
+        _ => unreachable!("clap only accepts FORMATS"),
+    }
+}
+
+/// GIF has 256 colours per frame; the encoder picks a palette for each. Its
+/// pixels are either opaque or transparent, so only those with an alpha of 0
+/// come out transparent.
+fn write_gif(frames: Vec<PixelBuffer>, fps: u32, out: impl Write) -> Result<()> {
+    let mut encoder = GifEncoder::new_with_speed(out, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    for buf in frames {
+        let rgba = if buf.has_alpha() {
+            RgbaImage::from_raw(buf.width, buf.height, buf.data)
+        } else {
+            RgbImage::from_raw(buf.width, buf.height, buf.data)
+                .map(|image| image::DynamicImage::ImageRgb8(image).into_rgba8())
+        }
+        .context("pixel buffer dimensions do not match its data length")?;
+        encoder.encode_frame(Frame::from_parts(rgba, 0, 0, delay)).context("failed to encode GIF frame")?;
+    }
+    Ok(())
+}
+
+fn write_apng(frames: Vec<PixelBuffer>, fps: u32, out: impl Write) -> Result<()> {
+    let (width, height) = (frames[0].width, frames[0].height);
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(if frames[0].has_alpha() { png::ColorType::Rgba } else { png::ColorType::Rgb });
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(1, fps as u16)?;
+    let mut writer = encoder.write_header()?;
+    for buf in frames {
+        writer.write_image_data(&buf.data).context("failed to encode APNG frame")?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// An uncompressed YUV4MPEG2 stream, 4:4:4 in studio-range BT.601, as video
+/// tools like `ffmpeg` read from a pipe. It has no alpha, so any is dropped.
+fn write_y4m(frames: Vec<PixelBuffer>, fps: u32, mut out: impl Write) -> Result<()> {
+    let (width, height) = (frames[0].width, frames[0].height);
+    writeln!(out, "YUV4MPEG2 W{width} H{height} F{fps}:1 Ip A1:1 C444")?;
+    let pixels = (width * height) as usize;
+    let mut planes = vec![0; pixels * 3];
+    for buf in frames {
+        for (i, rgb) in buf.data.chunks_exact(buf.channels as usize).enumerate() {
+            let (r, g, b) = (rgb[0] as i32, rgb[1] as i32, rgb[2] as i32);
+            planes[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            planes[pixels + i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            planes[2 * pixels + i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+        out.write_all(b"FRAME\n")?;
+        out.write_all(&planes)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` frames of a 3x2 image, frame `i` filled with the gray `i * 255 / (count - 1)`.
+    fn frames(count: u32, alpha: bool) -> Vec<PixelBuffer> {
+        (0..count)
+            .map(|i| {
+                let mut buf = if alpha { PixelBuffer::with_alpha(3, 2) } else { PixelBuffer::new(3, 2) };
+                let v = (i * 255 / (count - 1).max(1)) as u8;
+                for (x, y) in (0..3).flat_map(|x| (0..2).map(move |y| (x, y))) {
+                    if alpha {
+                        buf.put_pixel_rgba(x, y, v, v, v, 255);
+                    } else {
+                        buf.put_pixel(x, y, v, v, v);
+                    }
+                }
+                buf
+            })
+            .collect()
+    }
+
+    #[test]
+    fn y4m_has_a_header_and_three_full_planes_per_frame() {
+        let mut out = Vec::new();
+        write_y4m(frames(2, false), 30, &mut out).unwrap();
+
+        let header = b"YUV4MPEG2 W3 H2 F30:1 Ip A1:1 C444\n";
+        assert!(out.starts_with(header));
+        let frame = b"FRAME\n".len() + 3 * 6;
+        assert_eq!(out.len(), header.len() + 2 * frame);
+        // Black and white land on the ends of studio range, with neutral chroma.
+        let (first, second) = out[header.len()..].split_at(frame);
+        assert_eq!(&first[6..], &[[16; 6], [128; 6], [128; 6]].concat()[..]);
+        assert_eq!(&second[6..], &[[235; 6], [128; 6], [128; 6]].concat()[..]);
+    }
+
+    #[test]
+    fn apng_decodes_to_every_frame() {
+        for alpha in [false, true] {
+            let mut out = Vec::new();
+            write_apng(frames(4, alpha), 30, &mut out).unwrap();
+
+            let mut reader = png::Decoder::new(std::io::Cursor::new(out)).read_info().unwrap();
+            assert_eq!(reader.info().animation_control().unwrap().num_frames, 4);
+            let mut image = vec![0; reader.output_buffer_size().unwrap()];
+            let mut grays = Vec::new();
+            for _ in 0..4 {
+                reader.next_frame(&mut image).unwrap();
+                grays.push(image[0]);
+            }
+            assert_eq!(grays, [0, 85, 170, 255]);
+            assert_eq!(image.len(), if alpha { 3 * 2 * 4 } else { 3 * 2 * 3 });
+        }
+    }
+
+    #[test]
+    fn gif_decodes_to_every_frame() {
+        use image::AnimationDecoder;
+
+        let mut out = Vec::new();
+        write_gif(frames(3, false), 30, &mut out).unwrap();
+        let decoded = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(out)).unwrap();
+        assert_eq!(decoded.into_frames().count(), 3);
+    }
+}
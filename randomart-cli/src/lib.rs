@@ -1,8 +1,21 @@
-use anyhow::{Context, Result};
+mod animation;
+
+use anyhow::{anyhow, Context, Result};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
 use clap::{Parser, Subcommand};
-use image::RgbImage;
-use randomart_core::pixel_buffer::{GenerateOutput, PixelBuffer, ReadOutput};
+use image::{RgbImage, RgbaImage};
+use randomart_core::{
+    colormap::Colormap,
+    formula::Formula,
+    grammar::{generate_tree_budget, generate_tree_coherent, generate_tree_parallel, Grammar, NodeBudget, STYLES},
+    morph::morph_frame,
+    node::Node,
+    pixel_buffer::PixelBuffer,
+    render::{frame_times, Face, Projection},
+    texture::{Filter, Texture, Textures, FILTERS},
+};
 use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::xxh3_64;
 
 #[derive(Parser)]
 #[command(about = "Generate randomart images")]
@@ -19,7 +32,18 @@ pub enum Command {
         string: String,
 
         /// Tree depth
-        depth: u32,
+        #[arg(required_unless_present = "nodes", conflicts_with = "nodes")]
+        depth: Option<u32>,
+
+        /// Grow the tree to a node count instead of a depth: `N` for about N
+        /// nodes (at most N, at least 90% of it) or `MIN..MAX` for a range
+        #[arg(long)]
+        nodes: Option<NodeBudget>,
+
+        /// Seed every tree position separately, so that raising the depth
+        /// refines the image instead of replacing it
+        #[arg(long, conflicts_with = "nodes")]
+        coherent: bool,
 
         /// Image width in pixels
         #[arg(long, default_value_t = 512)]
@@ -36,6 +60,199 @@ pub enum Command {
         /// Also write a .json file with the formula
         #[arg(long)]
         save_json: bool,
+
+        /// Grammar definition file to grow the tree from (default: the built-in grammar)
+        #[arg(long, conflicts_with = "style")]
+        grammar: Option<PathBuf>,
+
+        /// Built-in grammar preset to grow the tree from
+        #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(STYLES))]
+        style: Option<String>,
+
+        /// Override an operator's weight in the grammar, e.g. `sin=5` or `div=0`; repeatable
+        #[arg(long = "weight", value_name = "OP=WEIGHT", value_parser = parse_weight)]
+        weights: Vec<(String, f32)>,
+
+        /// Image to sample where the formula has `Texture(SLOT, ...)`, e.g.
+        /// `photo=cat.png`; repeatable
+        #[arg(long = "texture", value_name = "SLOT=PATH", value_parser = parse_texture)]
+        textures: Vec<(String, PathBuf)>,
+
+        /// How textures are read between their pixels
+        #[arg(long, default_value = "bilinear", value_parser = filter_parser())]
+        texture_filter: Filter,
+
+        /// Where the image samples the formula: `plane` for the flat image,
+        /// `sphere` for an equirectangular panorama, `cubemap` for six skybox
+        /// faces saved as STEM_px.png to STEM_nz.png, or `volume` for
+        /// `--slices` depth slices saved as STEM_00.png upwards
+        #[arg(long, default_value = "plane", value_parser = PossibleValuesParser::new(PROJECTIONS))]
+        projection: String,
+
+        /// Number of slices `--projection volume` cuts, from z = -1 to z = 1
+        #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+        slices: u32,
+
+        /// Colours for a `Mono` formula: gray, viridis, magma, inferno, plasma,
+        /// or `#rrggbb` stops separated by commas
+        #[arg(long, default_value = "gray")]
+        colormap: Colormap,
+    },
+
+    /// Render an animation from a string seed, the formula's `T` running
+    /// from -1 to 1 over the frames
+    Animate {
+        /// Input string used as seed
+        string: String,
+
+        /// Tree depth
+        #[arg(required_unless_present = "nodes", conflicts_with = "nodes")]
+        depth: Option<u32>,
+
+        /// Grow the tree to a node count instead of a depth, as for `generate`
+        #[arg(long)]
+        nodes: Option<NodeBudget>,
+
+        /// Frame width in pixels
+        #[arg(long, default_value_t = 256)]
+        width: u32,
+
+        /// Frame height in pixels
+        #[arg(long, default_value_t = 256)]
+        height: u32,
+
+        /// Number of frames
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+        frames: u32,
+
+        /// Frames per second
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=100))]
+        fps: u32,
+
+        /// Ease `T` from -1 to 1 and back instead, so the animation loops
+        /// without a seam
+        #[arg(long)]
+        periodic: bool,
+
+        /// Animation format: an animated GIF, an animated PNG, or an
+        /// uncompressed YUV4MPEG2 video stream
+        #[arg(long, default_value = "gif", value_parser = PossibleValuesParser::new(animation::FORMATS))]
+        format: String,
+
+        /// Output filename stem (default: the input string), or `-` to write
+        /// the animation to standard output
+        #[arg(long)]
+        out: Option<String>,
+
+        /// Also write a .json file with the formula
+        #[arg(long)]
+        save_json: bool,
+
+        /// Grammar definition file to grow the tree from (default: the built-in grammar)
+        #[arg(long, conflicts_with = "style")]
+        grammar: Option<PathBuf>,
+
+        /// Built-in grammar preset to grow the tree from
+        #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(STYLES))]
+        style: Option<String>,
+
+        /// Override an operator's weight in the grammar, e.g. `sin=5` or `div=0`; repeatable
+        #[arg(long = "weight", value_name = "OP=WEIGHT", value_parser = parse_weight)]
+        weights: Vec<(String, f32)>,
+
+        /// Image to sample where the formula has `Texture(SLOT, ...)`, e.g.
+        /// `photo=cat.png`; repeatable
+        #[arg(long = "texture", value_name = "SLOT=PATH", value_parser = parse_texture)]
+        textures: Vec<(String, PathBuf)>,
+
+        /// How textures are read between their pixels
+        #[arg(long, default_value = "bilinear", value_parser = filter_parser())]
+        texture_filter: Filter,
+
+        /// Colours for a `Mono` formula: gray, viridis, magma, inferno, plasma,
+        /// or `#rrggbb` stops separated by commas
+        #[arg(long, default_value = "gray")]
+        colormap: Colormap,
+    },
+
+    /// Render an animation morphing one formula into another
+    Morph {
+        /// The formula to start from: a saved .json formula file, or a string
+        /// seed to grow one from
+        from: String,
+
+        /// The formula to end on, likewise
+        to: String,
+
+        /// Tree depth for string seeds
+        #[arg(long, default_value_t = 8)]
+        depth: u32,
+
+        /// Grammar definition file to grow seeded trees from (default: the built-in grammar)
+        #[arg(long, conflicts_with = "style")]
+        grammar: Option<PathBuf>,
+
+        /// Built-in grammar preset to grow seeded trees from
+        #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(STYLES))]
+        style: Option<String>,
+
+        /// Frame width in pixels
+        #[arg(long, default_value_t = 256)]
+        width: u32,
+
+        /// Frame height in pixels
+        #[arg(long, default_value_t = 256)]
+        height: u32,
+
+        /// Number of frames
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+        frames: u32,
+
+        /// Frames per second
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=100))]
+        fps: u32,
+
+        /// Animation format: an animated GIF, an animated PNG, or an
+        /// uncompressed YUV4MPEG2 video stream
+        #[arg(long, default_value = "gif", value_parser = PossibleValuesParser::new(animation::FORMATS))]
+        format: String,
+
+        /// Output filename stem (default: FROM-TO, by file stem for .json
+        /// files), or `-` to write the animation to standard output
+        #[arg(long)]
+        out: Option<String>,
+
+        /// Image to sample where the formula has `Texture(SLOT, ...)`, e.g.
+        /// `photo=cat.png`; repeatable
+        #[arg(long = "texture", value_name = "SLOT=PATH", value_parser = parse_texture)]
+        textures: Vec<(String, PathBuf)>,
+
+        /// How textures are read between their pixels
+        #[arg(long, default_value = "bilinear", value_parser = filter_parser())]
+        texture_filter: Filter,
+
+        /// Colours for a `Mono` formula: gray, viridis, magma, inferno, plasma,
+        /// or `#rrggbb` stops separated by commas
+        #[arg(long, default_value = "gray")]
+        colormap: Colormap,
+    },
+
+    /// Predict how big trees grown to a depth will be, without generating any
+    Predict {
+        /// Tree depth
+        depth: u32,
+
+        /// Grammar definition file (default: the built-in grammar)
+        #[arg(long, conflicts_with = "style")]
+        grammar: Option<PathBuf>,
+
+        /// Built-in grammar preset
+        #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(STYLES))]
+        style: Option<String>,
+
+        /// Override an operator's weight in the grammar, e.g. `sin=5` or `div=0`; repeatable
+        #[arg(long = "weight", value_name = "OP=WEIGHT", value_parser = parse_weight)]
+        weights: Vec<(String, f32)>,
     },
 
     /// Render an image from a previously saved .json formula file
@@ -54,30 +271,169 @@ pub enum Command {
         /// Output filename stem (default: input file stem)
         #[arg(long)]
         out: Option<String>,
+
+        /// Image to sample where the formula has `Texture(SLOT, ...)`, e.g.
+        /// `photo=cat.png`; repeatable
+        #[arg(long = "texture", value_name = "SLOT=PATH", value_parser = parse_texture)]
+        textures: Vec<(String, PathBuf)>,
+
+        /// How textures are read between their pixels
+        #[arg(long, default_value = "bilinear", value_parser = filter_parser())]
+        texture_filter: Filter,
+
+        /// Where the image samples the formula: `plane` for the flat image,
+        /// `sphere` for an equirectangular panorama, `cubemap` for six skybox
+        /// faces saved as STEM_px.png to STEM_nz.png, or `volume` for
+        /// `--slices` depth slices saved as STEM_00.png upwards
+        #[arg(long, default_value = "plane", value_parser = PossibleValuesParser::new(PROJECTIONS))]
+        projection: String,
+
+        /// Number of slices `--projection volume` cuts, from z = -1 to z = 1
+        #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+        slices: u32,
+
+        /// Colours for a `Mono` formula: gray, viridis, magma, inferno, plasma,
+        /// or `#rrggbb` stops separated by commas
+        #[arg(long, default_value = "gray")]
+        colormap: Colormap,
     },
 }
 
 pub trait RandomArtBackend {
-    fn generate(string: &str, depth: u32, width: u32, height: u32) -> Result<GenerateOutput>;
-    fn read_json(json: &str, width: u32, height: u32) -> Result<ReadOutput>;
+    /// One image of `node` per entry of `projections`, a `Mono` root coloured
+    /// by `colormap`.
+    fn render(
+        node: &Node,
+        textures: &Textures,
+        colormap: &Colormap,
+        projections: &[Projection],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<PixelBuffer>>;
+
+    /// One frame of the flat image of `node` per entry of `times`.
+    fn render_frames(
+        node: &Node,
+        textures: &Textures,
+        colormap: &Colormap,
+        times: &[f32],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<PixelBuffer>>;
 }
 
+/// The names `--projection` accepts.
+const PROJECTIONS: &[&str] = &["plane", "sphere", "cubemap", "volume"];
+
 pub fn run<B: RandomArtBackend>(cli: Cli) -> Result<()> {
     match cli.command {
-        Command::Generate { string, depth, width, height, out, save_json } => {
+        Command::Generate {
+            string,
+            depth,
+            nodes,
+            coherent,
+            width,
+            height,
+            out,
+            save_json,
+            grammar,
+            style,
+            weights,
+            textures,
+            texture_filter,
+            projection,
+            slices,
+            colormap,
+        } => {
             let stem = out.unwrap_or_else(|| string.clone());
-            let output = B::generate(&string, depth, width, height)?;
+            let seed = xxh3_64(string.as_bytes());
+            let grammar = select_grammar(grammar.as_deref(), style.as_deref(), &weights, seed)?;
+            let textures = load_textures(&textures, texture_filter)?;
+
+            let tree = grow_tree(&grammar, seed, depth, nodes, coherent)?;
 
-            save_image(output.pixels, &pwd(&format!("{stem}.png")))?;
+            save_views::<B>(&tree, &textures, &colormap, &views(&projection, slices), width, height, &stem)?;
 
             if save_json {
-                let path = pwd(&format!("{stem}.json"));
-                std::fs::write(&path, &output.json)
-                    .with_context(|| format!("failed to write JSON to {}", path.display()))?;
+                save_formula(*tree, &grammar, style.as_deref(), &stem)?;
             }
         }
 
-        Command::Read { input, width, height, out } => {
+        Command::Animate {
+            string,
+            depth,
+            nodes,
+            width,
+            height,
+            frames,
+            fps,
+            periodic,
+            format,
+            out,
+            save_json,
+            grammar,
+            style,
+            weights,
+            textures,
+            texture_filter,
+            colormap,
+        } => {
+            let target = AnimationTarget::new(out, || string.clone());
+            let seed = xxh3_64(string.as_bytes());
+            let grammar = select_grammar(grammar.as_deref(), style.as_deref(), &weights, seed)?;
+            let textures = load_textures(&textures, texture_filter)?;
+            let tree = grow_tree(&grammar, seed, depth, nodes, false)?;
+
+            let frames = B::render_frames(&tree, &textures, &colormap, &frame_times(frames, periodic), width, height)?;
+            save_animation(frames, &format, fps, &target)?;
+
+            if save_json {
+                let stem = match &target {
+                    AnimationTarget::Stdout => &string,
+                    AnimationTarget::Stem(stem) => stem,
+                };
+                save_formula(*tree, &grammar, style.as_deref(), stem)?;
+            }
+        }
+
+        Command::Morph {
+            from,
+            to,
+            depth,
+            grammar,
+            style,
+            width,
+            height,
+            frames,
+            fps,
+            format,
+            out,
+            textures,
+            texture_filter,
+            colormap,
+        } => {
+            let target = AnimationTarget::new(out, || format!("{}-{}", formula_stem(&from), formula_stem(&to)));
+            let start = load_formula(&from, grammar.as_deref(), style.as_deref(), depth)?;
+            let end = load_formula(&to, grammar.as_deref(), style.as_deref(), depth)?;
+            let textures = load_textures(&textures, texture_filter)?;
+
+            // Time runs on through the morph, for formulas that use `T`.
+            let mut rendered = Vec::with_capacity(frames as usize);
+            for t in frame_times(frames, false) {
+                let frame = morph_frame(&start, &end, (t + 1.0) / 2.0, |tree| {
+                    B::render_frames(tree, &textures, &colormap, &[t], width, height).map(|mut frames| frames.remove(0))
+                })?;
+                rendered.push(frame);
+            }
+            save_animation(rendered, &format, fps, &target)?;
+        }
+
+        Command::Predict { depth, grammar, style, weights } => {
+            let grammar = select_grammar(grammar.as_deref(), style.as_deref(), &weights, 0)?;
+            predict(&grammar, depth)?;
+        }
+
+        Command::Read { input, width, height, out, textures, texture_filter, projection, slices, colormap } => {
             let stem = out.unwrap_or_else(|| {
                 Path::new(&input)
                     .file_stem()
@@ -88,19 +444,224 @@ pub fn run<B: RandomArtBackend>(cli: Cli) -> Result<()> {
 
             let json = std::fs::read_to_string(&input)
                 .with_context(|| format!("failed to read input file {input}"))?;
-            let output = B::read_json(&json, width, height)?;
+            let formula = Formula::from_json(&json)
+                .context("failed to deserialize node tree from JSON")?;
 
-            save_image(output.pixels, &pwd(&format!("{stem}.png")))?;
+            let textures = load_textures(&textures, texture_filter)?;
+            save_views::<B>(&formula.tree, &textures, &colormap, &views(&projection, slices), width, height, &stem)?;
         }
     }
     Ok(())
 }
 
+/// The tree for `seed`, grown to `depth` or to the `nodes` budget, simplified.
+fn grow_tree(grammar: &Grammar, seed: u64, depth: Option<u32>, nodes: Option<NodeBudget>, coherent: bool) -> Result<Box<Node>> {
+    let tree = match (nodes, depth) {
+        (Some(budget), _) => generate_tree_budget(grammar, seed, budget),
+        (None, Some(depth)) if coherent => generate_tree_coherent(grammar, seed, depth),
+        (None, Some(depth)) => generate_tree_parallel(grammar, seed, depth),
+        (None, None) => unreachable!("clap requires a depth or --nodes"),
+    };
+    let mut tree = tree.context("tree generation failed")?;
+    tree.simplify_root();
+    Ok(tree)
+}
+
+/// Write `tree` and the grammar it grew from to `{stem}.json`.
+fn save_formula(tree: Node, grammar: &Grammar, style: Option<&str>, stem: &str) -> Result<()> {
+    let mut formula = Formula::new(tree, grammar);
+    if let Some(style) = style {
+        formula = formula.with_style(style);
+    }
+    let json = formula.to_json().context("failed to serialize node tree")?;
+    let path = pwd(&format!("{stem}.json"));
+    std::fs::write(&path, json).with_context(|| format!("failed to write JSON to {}", path.display()))?;
+    Ok(())
+}
+
+/// Where `animate` and `morph` write: standard output only when asked for
+/// with `--out -`, since a default stem built from the input could be `-` too.
+enum AnimationTarget {
+    Stdout,
+    Stem(String),
+}
+
+impl AnimationTarget {
+    fn new(out: Option<String>, default_stem: impl FnOnce() -> String) -> Self {
+        match out {
+            Some(out) if out == "-" => AnimationTarget::Stdout,
+            out => AnimationTarget::Stem(out.unwrap_or_else(default_stem)),
+        }
+    }
+}
+
+/// Write `frames` as `format` to `{stem}.{extension}` or standard output.
+fn save_animation(frames: Vec<PixelBuffer>, format: &str, fps: u32, target: &AnimationTarget) -> Result<()> {
+    let stem = match target {
+        AnimationTarget::Stdout => return animation::write(format, frames, fps, std::io::stdout().lock()),
+        AnimationTarget::Stem(stem) => stem,
+    };
+    let path = pwd(&format!("{stem}.{}", animation::extension(format)));
+    let file = std::fs::File::create(&path).with_context(|| format!("failed to create {}", path.display()))?;
+    animation::write(format, frames, fps, std::io::BufWriter::new(file))
+        .with_context(|| format!("failed to write animation to {}", path.display()))
+}
+
+/// The tree `source` names for `morph`: the formula in a `.json` file, or one
+/// grown from a seed string to `depth`.
+fn load_formula(source: &str, grammar: Option<&Path>, style: Option<&str>, depth: u32) -> Result<Node> {
+    if source.ends_with(".json") {
+        let json = std::fs::read_to_string(source).with_context(|| format!("failed to read input file {source}"))?;
+        let formula = Formula::from_json(&json).context("failed to deserialize node tree from JSON")?;
+        return Ok(formula.tree);
+    }
+    let seed = xxh3_64(source.as_bytes());
+    let grammar = select_grammar(grammar, style, &[], seed)?;
+    Ok(*grow_tree(&grammar, seed, Some(depth), None, false)?)
+}
+
+/// `source` as it appears in a default file name: a `.json` file's stem, or
+/// the seed string itself.
+fn formula_stem(source: &str) -> &str {
+    match source.strip_suffix(".json") {
+        Some(_) => Path::new(source).file_stem().and_then(|s| s.to_str()).unwrap_or(source),
+        None => source,
+    }
+}
+
+/// Expected trees above this size are flagged as likely to exhaust memory.
+const MEMORY_WARNING_BYTES: f64 = 4.0 * 1024.0 * 1024.0 * 1024.0;
+
+fn predict(grammar: &Grammar, depth: u32) -> Result<()> {
+    println!("{:>5}  {:>14}  {:>9}", "depth", "nodes", "truncated");
+    for d in 1..=depth {
+        let stats = grammar.expected_stats(d).context("cannot predict this grammar")?;
+        println!("{d:>5}  {:>14.1}  {:>8.1}%", stats.nodes, stats.truncation_probability * 100.0);
+    }
+
+    let stats = grammar.expected_stats(depth).context("cannot predict this grammar")?;
+    println!();
+    println!("expected node counts at depth {depth}:");
+    for (name, count) in &stats.op_counts {
+        println!("  {name:<14} {count:>14.1}");
+    }
+
+    let mib = stats.tree_bytes() / (1024.0 * 1024.0);
+    println!();
+    println!("expected tree size: {mib:.1} MiB");
+    if stats.tree_bytes() > MEMORY_WARNING_BYTES {
+        eprintln!("warning: depth {depth} is likely to exhaust memory; try a lower depth or --nodes");
+    }
+    Ok(())
+}
+
+/// The grammar from a file, a style preset, or the built-in default, with
+/// `weights` overrides applied and checked before anything is generated.
+fn select_grammar(path: Option<&Path>, style: Option<&str>, weights: &[(String, f32)], seed: u64) -> Result<Grammar> {
+    let mut grammar = match (path, style) {
+        (Some(path), _) => load_grammar(path, seed)?,
+        (None, Some(style)) => Grammar::style(style, seed).ok_or_else(|| anyhow!("unknown style `{style}`"))?,
+        (None, None) => Grammar::default(seed),
+    };
+    if weights.is_empty() {
+        return Ok(grammar);
+    }
+
+    for (op, weight) in weights {
+        grammar.set_weight(op, *weight).context("invalid --weight")?;
+    }
+    if let Err(errors) = grammar.validate() {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(anyhow!("weight overrides leave an invalid grammar: {}", errors.join("; ")));
+    }
+    Ok(grammar)
+}
+
+fn parse_weight(s: &str) -> Result<(String, f32), String> {
+    let (op, weight) = s.split_once('=').ok_or_else(|| format!("expected OP=WEIGHT, found `{s}`"))?;
+    let weight = weight.trim().parse().map_err(|_| format!("`{weight}` is not a number"))?;
+    Ok((op.trim().to_string(), weight))
+}
+
+fn parse_texture(s: &str) -> Result<(String, PathBuf), String> {
+    let (slot, path) = s.split_once('=').ok_or_else(|| format!("expected SLOT=PATH, found `{s}`"))?;
+    Ok((slot.trim().to_string(), PathBuf::from(path.trim())))
+}
+
+fn filter_parser() -> impl TypedValueParser<Value = Filter> {
+    PossibleValuesParser::new(FILTERS)
+        .map(|name| name.parse().expect("FILTERS are the names Filter parses"))
+}
+
+/// Read the `--texture` images into the slots they name.
+fn load_textures(slots: &[(String, PathBuf)], filter: Filter) -> Result<Textures> {
+    let mut textures = Textures::new();
+    for (slot, path) in slots {
+        let image = image::open(path)
+            .with_context(|| format!("failed to read texture {}", path.display()))?
+            .to_rgb8();
+        if image.width() == 0 || image.height() == 0 {
+            return Err(anyhow!("texture {} has no pixels", path.display()));
+        }
+        let pixels = PixelBuffer { width: image.width(), height: image.height(), channels: 3, data: image.into_raw() };
+        textures.insert(slot, Texture::new(pixels, filter));
+    }
+    Ok(textures)
+}
+
+/// The images `--projection` lays a formula out into, each with the suffix
+/// its file name takes after the stem.
+fn views(projection: &str, slices: u32) -> Vec<(String, Projection)> {
+    match projection {
+        "plane" => vec![(String::new(), Projection::Plane(0.0))],
+        "sphere" => vec![(String::new(), Projection::Equirectangular)],
+        "cubemap" => Face::ALL.into_iter().map(|face| (format!("_{}", face.name()), Projection::CubeFace(face))).collect(),
+        "volume" => {
+            let digits = (slices - 1).to_string().len().max(2);
+            Projection::slices(slices)
+                .into_iter()
+                .enumerate()
+                .map(|(k, slice)| (format!("_{k:0digits$}"), slice))
+                .collect()
+        }
+        _ => unreachable!("clap only accepts PROJECTIONS"),
+    }
+}
+
+/// Render `tree` once for all `views` and save each image as
+/// `{stem}{suffix}.png`.
+fn save_views<B: RandomArtBackend>(
+    tree: &Node,
+    textures: &Textures,
+    colormap: &Colormap,
+    views: &[(String, Projection)],
+    width: u32,
+    height: u32,
+    stem: &str,
+) -> Result<()> {
+    let projections: Vec<Projection> = views.iter().map(|&(_, projection)| projection).collect();
+    let images = B::render(tree, textures, colormap, &projections, width, height)?;
+    for ((suffix, _), image) in views.iter().zip(images) {
+        save_image(image, &pwd(&format!("{stem}{suffix}.png")))?;
+    }
+    Ok(())
+}
+
+fn load_grammar(path: &Path, seed: u64) -> Result<Grammar> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read grammar file {}", path.display()))?;
+    Grammar::parse(&source, seed).map_err(|e| anyhow!("{}:{e}", path.display()))
+}
+
+/// Save `buf` as a PNG, with transparency if it has an alpha channel.
 fn save_image(buf: PixelBuffer, path: &Path) -> Result<()> {
-    RgbImage::from_raw(buf.width, buf.height, buf.data)
-        .context("pixel buffer dimensions do not match its data length")?
-        .save(path)
-        .with_context(|| format!("failed to save image to {}", path.display()))?;
+    let image: image::DynamicImage = if buf.has_alpha() {
+        RgbaImage::from_raw(buf.width, buf.height, buf.data).map(Into::into)
+    } else {
+        RgbImage::from_raw(buf.width, buf.height, buf.data).map(Into::into)
+    }
+    .context("pixel buffer dimensions do not match its data length")?;
+    image.save(path).with_context(|| format!("failed to save image to {}", path.display()))?;
     Ok(())
 }
 
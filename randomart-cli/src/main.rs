@@ -1,7 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
 use randomart_cli::{run, Cli, RandomArtBackend};
-use randomart_core::pixel_buffer::{GenerateOutput, ReadOutput};
+use randomart_core::{colormap::Colormap, node::Node, pixel_buffer::PixelBuffer, render::Projection, texture::Textures};
 
 // Exactly one backend feature must be enabled. Alias the selected backend crate
 // to `backend` so the rest of this file is backend-agnostic.
@@ -27,11 +27,26 @@ compile_error!("multiple backends selected: enable exactly one of `closure`, `cr
 struct Backend;
 
 impl RandomArtBackend for Backend {
-    fn generate(string: &str, depth: u32, width: u32, height: u32) -> Result<GenerateOutput> {
-        backend::generate(string, depth, width, height)
+    fn render(
+        node: &Node,
+        textures: &Textures,
+        colormap: &Colormap,
+        projections: &[Projection],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<PixelBuffer>> {
+        backend::render_projected(node, textures, colormap, projections, width, height)
     }
-    fn read_json(json: &str, width: u32, height: u32) -> Result<ReadOutput> {
-        backend::read_json(json, width, height)
+
+    fn render_frames(
+        node: &Node,
+        textures: &Textures,
+        colormap: &Colormap,
+        times: &[f32],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<PixelBuffer>> {
+        backend::render_frames(node, textures, colormap, times, width, height)
     }
 }
 
@@ -2,48 +2,107 @@ pub mod utils;
 
 use utils::compile_node;
 use randomart_core::{
-    grammar::generate_tree_parallel,
+    formula::Formula,
+    grammar::{generate_tree_parallel, Grammar},
+    colormap::Colormap,
     node::Node,
     pixel_buffer::{PixelBuffer, GenerateOutput, ReadOutput},
-    render::{render_tiled, Colour, PixelCoordinates},
+    render::{render_frames as core_render_frames, render_tiled_projected, Channels, Colour, PixelCoordinates, Projection},
+    texture::{Textures, MEAN},
 };
 use anyhow::{bail, Context, Result};
 use xxhash_rust::xxh3::xxh3_64;
 
-fn render_node(node: &Node, width: u32, height: u32) -> Result<PixelBuffer> {
-    let (r, g, b) = match node {
-        Node::Triple(r, g, b) => (r.as_ref(), g.as_ref(), b.as_ref()),
-        _ => bail!("top-level node must be a Triple"),
-    };
-    let r_fn = compile_node(r);
-    let g_fn = compile_node(g);
-    let b_fn = compile_node(b);
-    Ok(render_tiled(
-        &move |coord: PixelCoordinates| Colour {
-            r: r_fn(coord.x, coord.y),
-            g: g_fn(coord.x, coord.y),
-            b: b_fn(coord.x, coord.y),
-        },
-        width,
-        height,
-    ))
+pub fn render(node: &Node, textures: &Textures, width: u32, height: u32) -> Result<PixelBuffer> {
+    let mut images = render_projected(node, textures, &Colormap::default(), &[Projection::Plane(0.0)], width, height)?;
+    Ok(images.remove(0))
+}
+
+/// One image of `node` per entry of `projections`, compiling it once. A `Mono`
+/// root is coloured by `colormap`, and a `Quad` root renders RGBA images.
+pub fn render_projected(
+    node: &Node,
+    textures: &Textures,
+    colormap: &Colormap,
+    projections: &[Projection],
+    width: u32,
+    height: u32,
+) -> Result<Vec<PixelBuffer>> {
+    let rgb_fn = compile_root(node, textures, colormap)?;
+    Ok(projections.iter().map(|&projection| render_tiled_projected(&rgb_fn, projection, Channels::of(node), width, height)).collect())
+}
+
+/// One frame of the flat image of `node` per entry of `times`, compiling it
+/// once.
+pub fn render_frames(
+    node: &Node,
+    textures: &Textures,
+    colormap: &Colormap,
+    times: &[f32],
+    width: u32,
+    height: u32,
+) -> Result<Vec<PixelBuffer>> {
+    let rgb_fn = compile_root(node, textures, colormap)?;
+    Ok(core_render_frames(&rgb_fn, times, Channels::of(node), width, height))
+}
+
+type RootFn<'a> = Box<dyn Fn(PixelCoordinates) -> Colour + Sync + 'a>;
+
+fn compile_root<'a>(node: &Node, textures: &'a Textures, colormap: &'a Colormap) -> Result<RootFn<'a>> {
+    if !matches!(node, Node::Triple(..) | Node::Mono(_) | Node::Quad(..)) {
+        bail!("top-level node must be a Triple, Mono or Quad");
+    }
+    if let Some(slot) = textures.missing(node) {
+        bail!("no texture given for slot `{slot}`");
+    }
+    Ok(match node {
+        Node::Triple(r, g, b) => {
+            let r_fn = compile_node(r, textures, 0);
+            let g_fn = compile_node(g, textures, 1);
+            let b_fn = compile_node(b, textures, 2);
+            Box::new(move |coord: PixelCoordinates| Colour {
+                r: r_fn(coord.x, coord.y, coord.z, coord.t),
+                g: g_fn(coord.x, coord.y, coord.z, coord.t),
+                b: b_fn(coord.x, coord.y, coord.z, coord.t),
+                a: 1.0,
+            })
+        }
+        Node::Quad(r, g, b, a) => {
+            let r_fn = compile_node(r, textures, 0);
+            let g_fn = compile_node(g, textures, 1);
+            let b_fn = compile_node(b, textures, 2);
+            let a_fn = compile_node(a, textures, MEAN);
+            Box::new(move |coord: PixelCoordinates| Colour {
+                r: r_fn(coord.x, coord.y, coord.z, coord.t),
+                g: g_fn(coord.x, coord.y, coord.z, coord.t),
+                b: b_fn(coord.x, coord.y, coord.z, coord.t),
+                a: a_fn(coord.x, coord.y, coord.z, coord.t),
+            })
+        }
+        Node::Mono(v) => {
+            let v_fn = compile_node(v, textures, MEAN);
+            Box::new(move |coord: PixelCoordinates| colormap.colour(v_fn(coord.x, coord.y, coord.z, coord.t)))
+        }
+        _ => unreachable!("checked above"),
+    })
 }
 
 pub fn generate(string: &str, depth: u32, width: u32, height: u32) -> Result<GenerateOutput> {
     let seed: u64 = xxh3_64(string.as_bytes());
-    let mut node = generate_tree_parallel(seed, depth)
+    let grammar = Grammar::default(seed);
+    let mut node = generate_tree_parallel(&grammar, seed, depth)
         .context("tree generation failed")?;
-    node.simplify_triple();
+    node.simplify_root();
 
-    let json = serde_json::to_string_pretty(&*node)
+    let pixels = render(&node, &Textures::new(), width, height)?;
+    let json = Formula::new(*node, &grammar).to_json()
         .context("failed to serialize node tree")?;
-    let pixels = render_node(&node, width, height)?;
     Ok(GenerateOutput { pixels, json })
 }
 
 pub fn read_json(json: &str, width: u32, height: u32) -> Result<ReadOutput> {
-    let node: Node = serde_json::from_str(json)
+    let formula = Formula::from_json(json)
         .context("failed to deserialize node tree from JSON")?;
-    let pixels = render_node(&node, width, height)?;
+    let pixels = render(&formula.tree, &Textures::new(), width, height)?;
     Ok(ReadOutput { pixels })
 }
@@ -1,68 +1,288 @@
 use randomart_core::node::Node;
 use randomart_core::math;
+use randomart_core::noise;
+use randomart_core::sdf;
+use randomart_core::texture::Textures;
 
-pub trait ClosureNode: Fn(f32, f32) -> f32 + Send + Sync {}
-impl<T: Fn(f32, f32) -> f32 + Send + Sync> ClosureNode for T {}
+/// A compiled channel, evaluated at `(x, y, z)` and time `t`.
+pub trait ClosureNode: Fn(f32, f32, f32, f32) -> f32 + Send + Sync {}
+impl<T: Fn(f32, f32, f32, f32) -> f32 + Send + Sync> ClosureNode for T {}
 
-pub fn compile_node(node: &Node) -> Box<dyn ClosureNode> {
+/// A compiled subtree that reads the point's depth, the time and the values
+/// of its enclosing `Let`s from an [`Env`].
+trait ScopedNode: Fn(f32, f32, &mut Env) -> f32 + Send + Sync {}
+impl<T: Fn(f32, f32, &mut Env) -> f32 + Send + Sync> ScopedNode for T {}
+
+/// What a subtree reads besides `(x, y)` while evaluating: the depth and the
+/// time, which no node moves, and the stack of `Let` values, outermost first.
+struct Env {
+    z: f32,
+    t: f32,
+    lets: Vec<f32>,
+}
+
+/// What a subtree is compiled inside: the ids of the enclosing `Let`s,
+/// outermost first, so a `Var` finds its value at the position of its `Let` in
+/// the stack, and the textures and channel a `Texture` samples.
+struct Scope<'a> {
+    lets: Vec<u32>,
+    textures: &'a Textures,
+    channel: usize,
+}
+
+/// Compile `node` as channel `channel` (0 for red, 1 for green, 2 for blue)
+/// of a `Triple`, or [`MEAN`](randomart_core::texture::MEAN) under a `Mono` or
+/// as a `Quad`'s alpha, sampling `textures`, which must hold every slot it uses.
+pub fn compile_node<'a>(node: &Node, textures: &'a Textures, channel: usize) -> Box<dyn ClosureNode + 'a> {
+    let f = compile_scoped(node, &mut Scope { lets: Vec::new(), textures, channel });
+    Box::new(move |x, y, z, t| f(x, y, &mut Env { z, t, lets: Vec::new() }))
+}
+
+fn compile_scoped<'a>(node: &Node, scope: &mut Scope<'a>) -> Box<dyn ScopedNode + 'a> {
     match node {
-        Node::X => Box::new(|x, _| x),
-        Node::Y => Box::new(|_, y| y),
+        Node::X => Box::new(|x, _, _| x),
+        Node::Y => Box::new(|_, y, _| y),
+        Node::Z => Box::new(|_, _, env| env.z),
+        Node::T => Box::new(|_, _, env| env.t),
+        Node::R => Box::new(|x, y, _| math::sqrtf(x * x + y * y)),
+        Node::Theta => Box::new(|x, y, _| math::atan2f(y, x)),
         Node::Number(v) => {
             let val = *v;
-            Box::new(move |_, _| val)
+            Box::new(move |_, _, _| val)
         }
 
         Node::Add(a, b) => {
-            let fa = compile_node(a);
-            let fb = compile_node(b);
-            Box::new(move |x, y| (fa(x, y) + fb(x, y)) / 2.0)
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| (fa(x, y, env) + fb(x, y, env)) / 2.0)
         }
         Node::Mult(a, b) => {
-            let fa = compile_node(a);
-            let fb = compile_node(b);
-            Box::new(move |x, y| fa(x, y) * fb(x, y))
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| fa(x, y, env) * fb(x, y, env))
         }
         Node::Div(a, b) => {
-            let fa = compile_node(a);
-            let fb = compile_node(b);
-            Box::new(move |x, y| {
-                let denom = fb(x, y);
-                if denom.abs() > 1e-6 { fa(x, y) / denom } else { 0.0 }
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| {
+                let denom = fb(x, y, env);
+                if denom.abs() > 1e-6 { fa(x, y, env) / denom } else { 0.0 }
+            })
+        }
+        Node::Min(a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| {
+                let (a, b) = (fa(x, y, env), fb(x, y, env));
+                if a < b { a } else { b }
+            })
+        }
+        Node::Max(a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| {
+                let (a, b) = (fa(x, y, env), fb(x, y, env));
+                if a > b { a } else { b }
+            })
+        }
+        Node::Mod(a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| math::guarded_mod(fa(x, y, env), fb(x, y, env)))
+        }
+        Node::Atan2(a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| math::atan2f(fa(x, y, env), fb(x, y, env)))
+        }
+        Node::Pow(a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| math::guarded_pow(fa(x, y, env), fb(x, y, env)))
+        }
+        Node::Xor(a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| math::bit_xor(fa(x, y, env), fb(x, y, env)))
+        }
+        Node::And(a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| math::bit_and(fa(x, y, env), fb(x, y, env)))
+        }
+        Node::Step(edge, v) => {
+            let fe = compile_scoped(edge, scope);
+            let fv = compile_scoped(v, scope);
+            Box::new(move |x, y, env| if fv(x, y, env) < fe(x, y, env) { -1.0 } else { 1.0 })
+        }
+        Node::Noise(a, b, perm) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            let table = *perm.table();
+            Box::new(move |x, y, env| noise::noise(fa(x, y, env), fb(x, y, env), &table))
+        }
+        Node::Texture(slot, a, b) => {
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            let texture = scope.textures.get(slot).expect("render checks every slot has a texture");
+            let channel = scope.channel;
+            Box::new(move |x, y, env| texture.sample(channel, fa(x, y, env), fb(x, y, env)))
+        }
+        Node::Rotate(angle, body) => {
+            let fa = compile_scoped(angle, scope);
+            let fb = compile_scoped(body, scope);
+            Box::new(move |x, y, env| {
+                let (x, y) = math::rotate(fa(x, y, env), x, y);
+                fb(x, y, env)
+            })
+        }
+        Node::Scale(factor, body) => {
+            let ff = compile_scoped(factor, scope);
+            let fb = compile_scoped(body, scope);
+            Box::new(move |x, y, env| {
+                let (x, y) = math::scale(ff(x, y, env), x, y);
+                fb(x, y, env)
             })
         }
+        Node::Warp(dx, dy, body) => {
+            let fdx = compile_scoped(dx, scope);
+            let fdy = compile_scoped(dy, scope);
+            let fb = compile_scoped(body, scope);
+            Box::new(move |x, y, env| {
+                let (x, y) = (x + fdx(x, y, env), y + fdy(x, y, env));
+                fb(x, y, env)
+            })
+        }
+        Node::IfPositive(cond, a, b) => {
+            let fc = compile_scoped(cond, scope);
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            Box::new(move |x, y, env| if fc(x, y, env) > 0.0 { fa(x, y, env) } else { fb(x, y, env) })
+        }
+        Node::Smoothstep(e0, e1, v) => {
+            let f0 = compile_scoped(e0, scope);
+            let f1 = compile_scoped(e1, scope);
+            let fv = compile_scoped(v, scope);
+            Box::new(move |x, y, env| math::smoothstep(f0(x, y, env), f1(x, y, env), fv(x, y, env)))
+        }
         Node::Sqrt(inner) => {
-            let f = compile_node(inner);
-            Box::new(move |x, y| math::sqrtf(f(x, y)).max(0.0))
+            let f = compile_scoped(inner, scope);
+            Box::new(move |x, y, env| math::sqrtf(f(x, y, env)).max(0.0))
         }
         Node::Sin(inner) => {
-            let f = compile_node(inner);
-            Box::new(move |x, y| math::sinf(f(x, y)))
+            let f = compile_scoped(inner, scope);
+            Box::new(move |x, y, env| math::sinf(f(x, y, env)))
         }
         Node::Cos(inner) => {
-            let f = compile_node(inner);
-            Box::new(move |x, y| math::cosf(f(x, y)))
+            let f = compile_scoped(inner, scope);
+            Box::new(move |x, y, env| math::cosf(f(x, y, env)))
         }
         Node::Exp(inner) => {
-            let f = compile_node(inner);
-            Box::new(move |x, y| math::expf(f(x, y)))
+            let f = compile_scoped(inner, scope);
+            Box::new(move |x, y, env| math::expf(f(x, y, env)))
+        }
+        Node::Abs(inner) => {
+            let f = compile_scoped(inner, scope);
+            Box::new(move |x, y, env| f(x, y, env).abs())
+        }
+        Node::Tan(inner) => {
+            let f = compile_scoped(inner, scope);
+            Box::new(move |x, y, env| math::tanf(f(x, y, env)))
+        }
+        Node::Log(inner) => {
+            let f = compile_scoped(inner, scope);
+            Box::new(move |x, y, env| math::guarded_log(f(x, y, env)))
+        }
+        Node::Quantize(inner, levels) => {
+            let f = compile_scoped(inner, scope);
+            let levels = *levels;
+            Box::new(move |x, y, env| math::quantize(f(x, y, env), levels))
+        }
+        Node::Circle(cx, cy, r) => {
+            let (fx, fy, fr) = (compile_scoped(cx, scope), compile_scoped(cy, scope), compile_scoped(r, scope));
+            Box::new(move |x, y, env| sdf::circle(x, y, fx(x, y, env), fy(x, y, env), fr(x, y, env)))
+        }
+        Node::BoxSdf(cx, cy, w, h) => {
+            let (fx, fy) = (compile_scoped(cx, scope), compile_scoped(cy, scope));
+            let (fw, fh) = (compile_scoped(w, scope), compile_scoped(h, scope));
+            Box::new(move |x, y, env| {
+                let (cx, cy) = (fx(x, y, env), fy(x, y, env));
+                sdf::rectangle(x, y, cx, cy, fw(x, y, env), fh(x, y, env))
+            })
+        }
+        Node::Line(ax, ay, bx, by) => {
+            let (fax, fay) = (compile_scoped(ax, scope), compile_scoped(ay, scope));
+            let (fbx, fby) = (compile_scoped(bx, scope), compile_scoped(by, scope));
+            Box::new(move |x, y, env| {
+                let (ax, ay) = (fax(x, y, env), fay(x, y, env));
+                sdf::segment(x, y, ax, ay, fbx(x, y, env), fby(x, y, env))
+            })
+        }
+        Node::SmoothUnion(a, b, k) => {
+            let (fa, fb, fk) = (compile_scoped(a, scope), compile_scoped(b, scope), compile_scoped(k, scope));
+            Box::new(move |x, y, env| sdf::smooth_union(fa(x, y, env), fb(x, y, env), fk(x, y, env)))
+        }
+        Node::SmoothIntersect(a, b, k) => {
+            let (fa, fb, fk) = (compile_scoped(a, scope), compile_scoped(b, scope), compile_scoped(k, scope));
+            Box::new(move |x, y, env| sdf::smooth_intersection(fa(x, y, env), fb(x, y, env), fk(x, y, env)))
+        }
+        Node::Iterate(body, n) => {
+            let f = compile_scoped(body, scope);
+            let n = *n;
+            Box::new(move |x, y, env| {
+                let mut v = x;
+                for _ in 0..n {
+                    v = f(v, y, env);
+                }
+                v
+            })
+        }
+        Node::Escape(body, n) => {
+            let f = compile_scoped(body, scope);
+            let n = *n;
+            Box::new(move |x, y, env| {
+                let (mut v, mut steps) = (x, 0);
+                while steps < n && v.abs() <= math::ESCAPE_RADIUS {
+                    v = f(v, y, env);
+                    steps += 1;
+                }
+                math::escape_time(steps, n)
+            })
         }
         Node::MixUnbounded(a, b, c, d) => {
-            let fa = compile_node(a);
-            let fb = compile_node(b);
-            let fc = compile_node(c);
-            let fd = compile_node(d);
-            Box::new(move |x, y| {
-                let a = fa(x, y);
-                let b = fb(x, y);
-                let c = fc(x, y);
-                let d = fd(x, y);
+            let fa = compile_scoped(a, scope);
+            let fb = compile_scoped(b, scope);
+            let fc = compile_scoped(c, scope);
+            let fd = compile_scoped(d, scope);
+            Box::new(move |x, y, env| {
+                let a = fa(x, y, env);
+                let b = fb(x, y, env);
+                let c = fc(x, y, env);
+                let d = fd(x, y, env);
                 (a * c + b * d) / (a + b + 1e-6)
             })
         }
 
+        Node::Var(id) => {
+            let slot = scope.lets.iter().rposition(|bound| bound == id).expect("Var outside its Let");
+            Box::new(move |_, _, env| env.lets[slot])
+        }
+        Node::Let(id, value, body) => {
+            let fv = compile_scoped(value, scope);
+            scope.lets.push(*id);
+            let fb = compile_scoped(body, scope);
+            scope.lets.pop();
+            Box::new(move |x, y, env| {
+                let value = fv(x, y, env);
+                env.lets.push(value);
+                let result = fb(x, y, env);
+                env.lets.pop();
+                result
+            })
+        }
+
         Node::Random => panic!("Node::Random should be resolved before compilation"),
-        Node::Triple(_, _, _) => panic!("compile_node() is for scalar nodes, not Triple"),
+        Node::Triple(_, _, _) | Node::Mono(_) | Node::Quad(..) => panic!("compile_node() is for scalar nodes, not {}", node.name()),
         node => unimplemented!("compile_node: missing match arm for {:?}", node),
     }
 }
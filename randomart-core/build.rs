@@ -8,18 +8,23 @@ fn main() {
     let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
     let core_math = core_math_dir(&manifest_dir);
 
-    let sinf = core_math.join("src/binary32/sin/sinf.c");
-    let cosf = core_math.join("src/binary32/cos/cosf.c");
-    let expf = core_math.join("src/binary32/exp/expf.c");
+    let sources = [
+        "src/binary32/sin/sinf.c",
+        "src/binary32/cos/cosf.c",
+        "src/binary32/exp/expf.c",
+        "src/binary32/tan/tanf.c",
+        "src/binary32/atan2/atan2f.c",
+        "src/binary32/log/logf.c",
+        "src/binary32/pow/powf.c",
+    ]
+    .map(|source| core_math.join(source));
 
-    println!("cargo:rerun-if-changed={}", sinf.display());
-    println!("cargo:rerun-if-changed={}", cosf.display());
-    println!("cargo:rerun-if-changed={}", expf.display());
+    for source in &sources {
+        println!("cargo:rerun-if-changed={}", source.display());
+    }
 
     cc::Build::new()
-        .file(sinf)
-        .file(cosf)
-        .file(expf)
+        .files(&sources)
         .flag_if_supported("-std=c11")
         .compile("core_math");
 }
@@ -0,0 +1,141 @@
+//! Colours for the single channel of a [`Node::Mono`] root. The channel's
+//! value runs along a gradient of evenly spaced stops, from the first at `-1`
+//! to the last at `1`.
+//!
+//! [`Node::Mono`]: crate::node::Node::Mono
+
+use crate::render::Colour;
+use std::str::FromStr;
+
+/// The names [`Colormap`] parses from, besides a list of stops.
+pub const COLORMAPS: &[&str] = &["gray", "viridis", "magma", "inferno", "plasma"];
+
+/// Ten samples of each of matplotlib's perceptually uniform maps.
+const VIRIDIS: [u32; 10] =
+    [0x440154, 0x482878, 0x3e4989, 0x31688e, 0x26828e, 0x1f9e89, 0x35b779, 0x6ece58, 0xb5de2b, 0xfde725];
+const MAGMA: [u32; 10] =
+    [0x000004, 0x180f3d, 0x440f76, 0x721f81, 0x9e2f7f, 0xcd4071, 0xf1605d, 0xfd9668, 0xfeca8d, 0xfcfdbf];
+const INFERNO: [u32; 10] =
+    [0x000004, 0x1b0c41, 0x4a0c6b, 0x781c6d, 0xa52c60, 0xcf4446, 0xed6925, 0xfb9b06, 0xf7d13d, 0xfcffa4];
+const PLASMA: [u32; 10] =
+    [0x0d0887, 0x47039f, 0x7301a8, 0x9c179e, 0xbd3786, 0xd8576b, 0xed7953, 0xfa9e3b, 0xfdc926, 0xf0f921];
+
+/// A gradient through two or more RGB stops.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Colormap {
+    stops: Vec<[u8; 3]>,
+}
+
+impl Default for Colormap {
+    /// Black to white.
+    fn default() -> Self {
+        Self::new(vec![[0; 3], [255; 3]])
+    }
+}
+
+impl Colormap {
+    /// Panics with fewer than two stops.
+    pub fn new(stops: Vec<[u8; 3]>) -> Self {
+        assert!(stops.len() >= 2, "a colormap needs at least two stops");
+        Self { stops }
+    }
+
+    /// One of [`COLORMAPS`].
+    pub fn named(name: &str) -> Option<Self> {
+        let table: &[u32] = match name {
+            "gray" => return Some(Self::default()),
+            "viridis" => &VIRIDIS,
+            "magma" => &MAGMA,
+            "inferno" => &INFERNO,
+            "plasma" => &PLASMA,
+            _ => return None,
+        };
+        Some(Self::new(table.iter().map(|&rgb| [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]).collect()))
+    }
+
+    pub fn stops(&self) -> &[[u8; 3]] {
+        &self.stops
+    }
+
+    /// The colour for `v`, with values beyond `[-1, 1]` and NaN held at the
+    /// ends. Each channel is blended in bytes and read back like a texture
+    /// byte, so a stop renders to exactly its own colour.
+    pub fn colour(&self, v: f32) -> Colour {
+        let last = self.stops.len() - 1;
+        let u = ((v + 1.0) * 0.5 * last as f32).max(0.0).min(last as f32);
+        let i = (u as usize).min(last - 1);
+        let f = u - i as f32;
+        let (a, b) = (self.stops[i], self.stops[i + 1]);
+        let channel = |c: usize| {
+            let byte = a[c] as f32 + (b[c] as f32 - a[c] as f32) * f;
+            (byte + 0.5) / 127.5 - 1.0
+        };
+        Colour { r: channel(0), g: channel(1), b: channel(2), a: 1.0 }
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    /// A name from [`COLORMAPS`], or comma-separated `#rrggbb` stops.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(colormap) = Self::named(s) {
+            return Ok(colormap);
+        }
+        if !s.starts_with('#') {
+            return Err(format!(
+                "unknown colormap `{s}`, expected one of {} or `#rrggbb` stops separated by commas",
+                COLORMAPS.join(", ")
+            ));
+        }
+        let stops = s
+            .split(',')
+            .map(|stop| {
+                let hex = stop.trim().strip_prefix('#').filter(|hex| hex.len() == 6);
+                match hex.and_then(|hex| u32::from_str_radix(hex, 16).ok()) {
+                    Some(rgb) => Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]),
+                    None => Err(format!("invalid colour stop `{stop}`, expected `#rrggbb`")),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if stops.len() < 2 {
+            return Err("a colormap needs at least two stops".to_string());
+        }
+        Ok(Self::new(stops))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(colour: Colour) -> [u8; 3] {
+        [colour.r, colour.g, colour.b].map(|v| ((v + 1.0) * 127.5).clamp(0.0, 255.0) as u8)
+    }
+
+    #[test]
+    fn stops_render_to_their_colours() {
+        for name in COLORMAPS {
+            let colormap = Colormap::named(name).unwrap();
+            let last = colormap.stops().len() - 1;
+            for (i, &stop) in colormap.stops().iter().enumerate() {
+                let v = i as f32 / last as f32 * 2.0 - 1.0;
+                assert_eq!(bytes(colormap.colour(v)), stop, "{name} stop {i}");
+            }
+        }
+        let gray = Colormap::default();
+        assert_eq!(bytes(gray.colour(0.0)), [128; 3]);
+        assert_eq!(bytes(gray.colour(5.0)), [255; 3]);
+        assert_eq!(bytes(gray.colour(f32::NAN)), [0; 3]);
+    }
+
+    #[test]
+    fn parses_names_and_stops() {
+        assert_eq!("viridis".parse::<Colormap>().unwrap().stops()[0], [0x44, 0x01, 0x54]);
+        let custom: Colormap = "#ff0000, #00ff00,#0000ff".parse().unwrap();
+        assert_eq!(custom.stops(), &[[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+        assert!("cividis".parse::<Colormap>().is_err());
+        assert!("#ff0000".parse::<Colormap>().is_err());
+        assert!("#ff0000,#12345".parse::<Colormap>().is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::grammar::Grammar;
+use crate::node::{Node, MAX_ITERATIONS, MAX_NESTED_PASSES};
+use serde::{Deserialize, Serialize};
+
+/// What gets saved as JSON: the generated tree plus what it was generated from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Formula {
+    /// The grammar the tree was grown from, in the text form `Grammar::parse` reads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grammar: Option<String>,
+    /// The style preset the grammar came from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+    pub tree: Node,
+}
+
+impl Formula {
+    pub fn new(tree: Node, grammar: &Grammar) -> Self {
+        Self { grammar: Some(grammar.to_string()), style: None, tree }
+    }
+
+    /// Record that the grammar was the [`Grammar::style`] preset `style`.
+    pub fn with_style(mut self, style: &str) -> Self {
+        self.style = Some(style.to_string());
+        self
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Accepts both a saved `Formula` and a bare `Node` tree, which is what
+    /// older versions wrote. A `Var` outside every `Let` that binds it, a loop
+    /// of more than [`MAX_ITERATIONS`] passes, or nested loops making more than
+    /// [`MAX_NESTED_PASSES`] between them, is an error.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let formula: Self = if value.get("tree").is_some() {
+            serde_json::from_value(value)?
+        } else {
+            Self { grammar: None, style: None, tree: serde_json::from_value(value)? }
+        };
+        if let Some(id) = formula.tree.unbound_var() {
+            return Err(serde::de::Error::custom(format!("Var({id}) is not bound by an enclosing Let")));
+        }
+        let passes = formula.tree.most_iterations();
+        if passes > MAX_ITERATIONS {
+            return Err(serde::de::Error::custom(format!("{passes} iterations is over the limit of {MAX_ITERATIONS}")));
+        }
+        let nested = formula.tree.nested_passes();
+        if nested > MAX_NESTED_PASSES {
+            return Err(serde::de::Error::custom(format!(
+                "nested loops make {nested} passes, over the limit of {MAX_NESTED_PASSES}"
+            )));
+        }
+        Ok(formula)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_loops_past_the_limits() {
+        let nest = |depth: usize, n: u32| (0..depth).fold(Node::X, |body, _| Node::Iterate(Box::new(body), n));
+        let json = |tree: Node| serde_json::to_string(&tree).unwrap();
+
+        assert!(Formula::from_json(&json(nest(2, 256))).is_ok());
+        assert!(Formula::from_json(&json(nest(1, 257))).is_err());
+        // Each loop is within bounds, but together they make 256⁴ passes.
+        let err = Formula::from_json(&json(nest(4, 256))).unwrap_err();
+        assert!(err.to_string().contains("nested loops"), "{err}");
+    }
+}
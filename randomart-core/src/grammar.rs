@@ -1,191 +1,449 @@
+mod budget;
+mod builder;
+mod constraints;
+mod parse;
+mod predict;
+mod styles;
+mod validate;
+
+pub use budget::{generate_tree_budget, NodeBudget};
+pub use builder::{BuildError, GrammarBuilder};
+pub use constraints::Constraints;
+pub use parse::ParseError;
+pub use predict::ExpectedStats;
+pub use styles::STYLES;
+pub use validate::GrammarError;
+
 use crate::node::Node;
+use crate::noise::Permutation;
 use crate::rng::Rng_;
+use std::fmt;
+use std::ops::RangeInclusive;
 use xxhash_rust::xxh3::xxh3_64;
 
+/// The numbers of passes generation gives an `Iterate` or `Escape`.
+const GROWN_ITERATIONS: RangeInclusive<u32> = 2..=16;
+
+/// The numbers of levels generation gives a `Quantize`.
+const GROWN_LEVELS: RangeInclusive<u32> = 2..=16;
+
+/// How strongly an alternate is preferred over the other alternates of its rule,
+/// possibly varying with how much depth is left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weight {
+    Fixed(f32),
+    /// `far` while at least `span` depth remains, easing linearly to `near` as
+    /// the remaining depth runs out. A terminal alternate with a large `near`
+    /// makes trees wind down gracefully instead of being cut off.
+    Ramp { far: f32, near: f32, span: u32 },
+}
+
+impl Weight {
+    /// The weight when `depth` levels remain.
+    pub fn at(&self, depth: u32) -> f32 {
+        match *self {
+            Weight::Fixed(w) => w,
+            Weight::Ramp { far, near, span } => {
+                if depth >= span {
+                    far
+                } else {
+                    near + (far - near) * (depth as f32 / span as f32)
+                }
+            }
+        }
+    }
+
+    /// The largest weight this takes at any depth.
+    pub fn peak(&self) -> f32 {
+        match *self {
+            Weight::Fixed(w) => w,
+            Weight::Ramp { far, near, .. } => far.max(near),
+        }
+    }
+
+    /// Depth beyond which the weight stops changing.
+    fn span(&self) -> u32 {
+        match *self {
+            Weight::Fixed(_) => 0,
+            Weight::Ramp { span, .. } => span,
+        }
+    }
+}
+
+impl From<f32> for Weight {
+    fn from(weight: f32) -> Self {
+        Weight::Fixed(weight)
+    }
+}
+
 #[derive(Clone)]
 struct GrammarBranch {
     node: Box<Node>,
-    probability: f32,
+    /// Relative weight; the alternate is picked with probability `weight / total`.
+    weight: Weight,
 }
 
 #[derive(Clone)]
 struct GrammarBranches {
+    name: String,
     alternates: Vec<GrammarBranch>,
 }
 
 impl GrammarBranches {
-    fn new() -> Self {
+    fn new(name: &str) -> Self {
         Self {
+            name: name.to_string(),
             alternates: Vec::new(),
         }
     }
 
-    fn add_alternate(&mut self, node: Node, probability: f32) {
-        self.alternates.push(GrammarBranch { node: Box::new(node), probability });
+    fn add_alternate(&mut self, node: Node, weight: Weight) {
+        self.alternates.push(GrammarBranch { node: Box::new(node), weight });
+    }
+
+    fn total_weight(&self, depth: u32) -> f32 {
+        self.alternates.iter().map(|branch| branch.weight.at(depth)).sum()
     }
 }
 
+/// A set of named rules that grow random expression trees from a start rule.
 pub struct Grammar {
     rules: Vec<GrammarBranches>,
+    start: usize,
+    /// Per rule, the fewest nested expansions that reach terminals; `None`
+    /// for rules that never terminate.
+    heights: Vec<Option<u32>>,
+    constraints: Constraints,
+    tally: constraints::Tally,
+    /// How many `Let`s enclose the node being grown, which is the id the next
+    /// `Let` gets and the number of bindings a `Var` can refer to.
+    bindings: u32,
     rng: Rng_,
 }
 
 impl Grammar {
-    fn add_rule(&mut self, branch: GrammarBranches) {
-        self.rules.push(branch);
+    /// Parse a grammar from its text form:
+    ///
+    /// ```text
+    /// # comments run to the end of the line
+    /// E ::= Triple(C, C, C)
+    /// C ::= A
+    ///     | Add(C, C)
+    ///     | Sin(C) [3]
+    /// A ::= X | Y | Random
+    /// ```
+    ///
+    /// Each rule is `Name ::=` followed by alternates separated by `|`. An
+    /// alternate is a `Node` variant written as `Variant(args...)` (or bare, for
+    /// the terminals `X`, `Y`, `Z`, `T`, `R`, `Theta` and `Random`), a number literal, or
+    /// the name of another rule. The optional `[weight]` after an alternate is
+    /// relative to the other alternates of the same rule and defaults to 1;
+    /// `[far -> near @ span]` is a [`Weight::Ramp`]. The first rule is the
+    /// start rule.
+    ///
+    /// Lines starting with `@` set [`Constraints`]: `@forbid Sin Sin` keeps a
+    /// `Sin` from sitting directly inside another, `@max Exp 2` allows at most two
+    /// `Exp` nodes per channel, and `@min_xy 3` asks for at least three `X` or `Y`
+    /// leaves per channel.
+    pub fn parse(source: &str, seed: u64) -> Result<Self, ParseError> {
+        let builder = parse::parse(source)?;
+        Ok(builder.build(seed).expect("the parser only accepts grammars whose rules are all defined"))
     }
 
-    pub fn default(seed: u64) -> Self {
-        let mut grammar = Self {
-            rules: Vec::new(),
+    /// A copy of this grammar's rules driven by a fresh RNG stream.
+    pub fn with_seed(&self, seed: u64) -> Self {
+        Self {
+            rules: self.rules.clone(),
+            start: self.start,
+            heights: self.heights.clone(),
+            constraints: self.constraints.clone(),
+            tally: constraints::Tally::default(),
+            bindings: 0,
             rng: Rng_::new(seed),
-        };
+        }
+    }
+
+    pub fn default(seed: u64) -> Self {
+        let mut builder = GrammarBuilder::new();
+        let c = builder.rule("C");
+        let a = builder.rule("A");
+        let c = || Box::new(c.clone());
+
+        builder
+            // E ::= (C, C, C)
+            .alternate("E", Node::Triple(c(), c(), c()), 1.0)
+            // C ::= A | Add(C, C) | Mult(C, C) | Sin(C) | Cos(C) | Exp(C) | Sqrt(C) | Div(C, C) | MixUnbounded(C, C, C, C)
+            .alternate("C", a, 1.0)
+            .alternate("C", Node::Add(c(), c()), 1.0)
+            .alternate("C", Node::Mult(c(), c()), 1.0)
+            .alternate("C", Node::Sin(c()), 3.0)
+            .alternate("C", Node::Cos(c()), 3.0)
+            .alternate("C", Node::Exp(c()), 1.0)
+            .alternate("C", Node::Sqrt(c()), 1.0)
+            .alternate("C", Node::Div(c(), c()), 1.0)
+            .alternate("C", Node::MixUnbounded(c(), c(), c(), c()), 1.0)
+            // Hard edges, off unless given a weight: IfPositive(C, C, C) | Step(C, C) | ...
+            .alternate("C", Node::IfPositive(c(), c(), c()), 0.0)
+            .alternate("C", Node::Step(c(), c()), 0.0)
+            .alternate("C", Node::Smoothstep(c(), c(), c()), 0.0)
+            .alternate("C", Node::Min(c(), c()), 0.0)
+            .alternate("C", Node::Max(c(), c()), 0.0)
+            // Shared subexpressions, also off: Let(C, C), with Var in A referring to it
+            .alternate("C", Node::Let(0, c(), c()), 0.0)
+            // Loops, also off: Iterate(C) | Escape(C)
+            .alternate("C", Node::Iterate(c(), 0), 0.0)
+            .alternate("C", Node::Escape(c(), 0), 0.0)
+            // Shapes, also off: Circle(C, C, C) | BoxSdf(C, C, C, C) | Line(C, C, C, C) | ...
+            .alternate("C", Node::Circle(c(), c(), c()), 0.0)
+            .alternate("C", Node::BoxSdf(c(), c(), c(), c()), 0.0)
+            .alternate("C", Node::Line(c(), c(), c(), c()), 0.0)
+            .alternate("C", Node::SmoothUnion(c(), c(), c()), 0.0)
+            .alternate("C", Node::SmoothIntersect(c(), c(), c()), 0.0)
+            // Pixel grids, also off: Quantize(C) | Xor(C, C) | And(C, C)
+            .alternate("C", Node::Quantize(c(), 0), 0.0)
+            .alternate("C", Node::Xor(c(), c()), 0.0)
+            .alternate("C", Node::And(c(), c()), 0.0)
+            // A ::= x | y | random number in [-1, 1]
+            .alternate("A", Node::X, 1.0)
+            .alternate("A", Node::Y, 1.0)
+            .alternate("A", Node::Random, 1.0)
+            .alternate("A", Node::Var(0), 0.0)
+            .alternate("A", Node::Z, 0.0)
+            .alternate("A", Node::T, 0.0)
+            .start("E");
+
+        builder.build(seed).expect("the default grammar defines every rule it references")
+    }
+
+    /// Index of the rule called `name`, for use with [`Grammar::gen_rule`].
+    pub fn rule_index(&self, name: &str) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.name == name)
+    }
+
+    pub fn start_rule(&self) -> usize {
+        self.start
+    }
+
+    pub fn constraints(&self) -> &Constraints {
+        &self.constraints
+    }
+
+    /// Give every alternate that is a `op` node, or a reference to the rule
+    /// `op`, a fixed `weight`. Names are matched case-insensitively, so `sin`
+    /// adjusts `Sin(C)`. Probabilities stay relative to each rule's total, so
+    /// the rest of the rule is renormalised around the new weight.
+    pub fn set_weight(&mut self, op: &str, weight: f32) -> Result<(), WeightError> {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(WeightError::Invalid { op: op.to_string(), weight });
+        }
+
+        let names: Vec<String> = self.rules.iter().map(|rule| rule.name.clone()).collect();
+        let mut found = false;
+        for branch in self.rules.iter_mut().flat_map(|rule| rule.alternates.iter_mut()) {
+            let name = match *branch.node {
+                Node::Rule(idx) => names.get(idx).map_or("", String::as_str),
+                ref node => node.name(),
+            };
+            if name.eq_ignore_ascii_case(op) {
+                branch.weight = Weight::Fixed(weight);
+                found = true;
+            }
+        }
+        if !found {
+            return Err(WeightError::UnknownOperator(op.to_string()));
+        }
 
-        // E::= (C, C, C)
-        let mut e_branch = GrammarBranches::new();
-        e_branch.add_alternate(
-            Node::Triple(
-                Box::new(Node::Rule(1)),
-                Box::new(Node::Rule(1)),
-                Box::new(Node::Rule(1)),
-            ),
-            1.0,
-        );
-        grammar.add_rule(e_branch);
-
-        // C::= A | Add(C, C) | Mult(C, C) | Sin(C) | Cos(C) | Exp(C) | Sqrt(C) | Div(C, C) | MixUnbounded(C, C, C, C)
-        let mut c_branch = GrammarBranches::new();
-        c_branch.add_alternate(Node::Rule(2), 1.0 / 13.0);
-        c_branch.add_alternate(
-            Node::Add(Box::new(Node::Rule(1)), Box::new(Node::Rule(1))),
-            1.0 / 13.0,
-        );
-        c_branch.add_alternate(
-            Node::Mult(Box::new(Node::Rule(1)), Box::new(Node::Rule(1))),
-            1.0 / 13.0,
-        );
-        c_branch.add_alternate(Node::Sin(Box::new(Node::Rule(1))), 3.0 / 13.0);
-        c_branch.add_alternate(Node::Cos(Box::new(Node::Rule(1))), 3.0 / 13.0);
-        c_branch.add_alternate(Node::Exp(Box::new(Node::Rule(1))), 1.0 / 13.0);
-        c_branch.add_alternate(Node::Sqrt(Box::new(Node::Rule(1))), 1.0 / 13.0);
-        c_branch.add_alternate(
-            Node::Div(Box::new(Node::Rule(1)), Box::new(Node::Rule(1))),
-            1.0 / 13.0,
-        );
-        c_branch.add_alternate(
-            Node::MixUnbounded(
-                Box::new(Node::Rule(1)),
-                Box::new(Node::Rule(1)),
-                Box::new(Node::Rule(1)),
-                Box::new(Node::Rule(1)),
-            ),
-            1.0 / 13.0,
-        );
-        grammar.add_rule(c_branch);
-
-        // A ::= x | y | random number in [-1, 1]
-        let mut a_branch = GrammarBranches::new();
-        a_branch.add_alternate(Node::X, 1.0 / 3.0);
-        a_branch.add_alternate(Node::Y, 1.0 / 3.0);
-        a_branch.add_alternate(Node::Random, 1.0 / 3.0);
-        grammar.add_rule(a_branch);
-
-        grammar
+        self.heights = validate::termination_heights(&self.rules);
+        Ok(())
+    }
+
+    /// The start rule's pattern when it is a lone `Triple`, `Mono` or `Quad`,
+    /// which is what [`generate_tree_parallel`] splits into channels.
+    fn start_root(&self) -> Option<&Node> {
+        match self.rules[self.start].alternates.as_slice() {
+            [branch] if matches!(*branch.node, Node::Triple(..) | Node::Mono(_) | Node::Quad(..)) => Some(&branch.node),
+            _ => None,
+        }
     }
 
+    /// Expand `rule` with `depth` levels to spare. Once depth runs out the rule
+    /// is finished along its shortest route to terminals instead, so this only
+    /// fails for rules that `validate` would reject.
     pub fn gen_rule(&mut self, rule: usize, depth: u32) -> Option<Box<Node>> {
-        if depth <= 0 {
-            return None;
+        self.expand(rule, depth, None, None)
+    }
+
+    /// [`Grammar::gen_rule`] for a rule that sits directly inside a `parent` node.
+    /// With a `position` seed, the choice is drawn from that seed alone rather
+    /// than from the running RNG stream (see [`generate_tree_coherent`]).
+    fn expand(
+        &mut self,
+        rule: usize,
+        depth: u32,
+        parent: Option<&'static str>,
+        position: Option<u64>,
+    ) -> Option<Box<Node>> {
+        // Out-of-range and empty rules are reported by `validate`; here they just fail.
+        self.rules.get(rule)?;
+        let candidates = self.candidates(rule, depth);
+        self.begin_rule(rule, depth);
+
+        let mut permitted = Vec::new();
+        for branch in &candidates {
+            if self.in_scope(&branch.node) && self.permits(&branch.node, parent, Some(depth)) {
+                permitted.push(branch);
+            }
         }
 
-        assert!(rule < self.rules.len(), "invalid rule index");
-        let branches = self.rules[rule].clone();
-        assert!(!branches.alternates.is_empty(), "no branches available");
+        if let Some(seed) = position {
+            self.rng = Rng_::new(seed);
+        }
+        let branch = match self.choose(permitted.iter().copied(), depth) {
+            Some(branch) => branch,
+            // Nothing permitted can be picked, so the constraints give way.
+            None => match self.choose(candidates.iter(), depth) {
+                Some(branch) => branch,
+                // Every shortest route has zero weight this deep; take one anyway.
+                None if depth == 0 => permitted.first().copied().or(candidates.first())?,
+                None => return None,
+            },
+        };
 
-        let mut node = None;
+        self.record(&branch.node, Some(depth));
+        let position = position.map(|seed| derive_child_seed(seed, 0));
+        self.gen_node(&branch.node, depth.saturating_sub(1), parent, position)
+    }
+
+    /// Pick one of `alternates` with probability proportional to its weight at `depth`.
+    fn choose<'a>(
+        &mut self,
+        alternates: impl Iterator<Item = &'a GrammarBranch> + Clone,
+        depth: u32,
+    ) -> Option<&'a GrammarBranch> {
+        let total: f32 = alternates.clone().map(|branch| branch.weight.at(depth)).sum();
+        if total.is_nan() || total <= 0.0 {
+            return None;
+        }
 
+        // Rounding can leave the cumulative sum just short of `p`; draw again then.
         for _ in 0..100 {
             let p: f32 = self.rng.next_float();
 
             let mut cumulative_probability = 0.0;
-            for branch in &branches.alternates {
-                cumulative_probability += branch.probability;
+            for branch in alternates.clone() {
+                cumulative_probability += branch.weight.at(depth) / total;
                 if cumulative_probability >= p {
-                    node = self.gen_node(&branch.node, depth - 1);
-                    break;
+                    return Some(branch);
                 }
             }
-
-            if node.is_some() {
-                break;
-            }
         }
 
-        node
+        None
     }
 
-    fn gen_node(&mut self, node: &Node, depth: u32) -> Option<Box<Node>> {
+    /// Grow the rules inside `node`, which sits directly inside a `parent` node
+    /// at `position`, if positions are being tracked.
+    fn gen_node(
+        &mut self,
+        node: &Node,
+        depth: u32,
+        parent: Option<&'static str>,
+        position: Option<u64>,
+    ) -> Option<Box<Node>> {
         match node {
-            Node::X | Node::Y | Node::Number(_) => Some(Box::new(node.clone())),
+            Node::Rule(rule_index) => self.expand(*rule_index, depth.saturating_sub(1), parent, position),
 
-            Node::Sqrt(inner) => {
-                let rhs = self.gen_node(inner, depth)?;
-                Some(Box::new(Node::Sqrt(rhs)))
-            }
-            Node::Sin(inner) => {
-                let rhs = self.gen_node(inner, depth)?;
-                Some(Box::new(Node::Sin(rhs)))
-            }
-            Node::Cos(inner) => {
-                let rhs = self.gen_node(inner, depth)?;
-                Some(Box::new(Node::Cos(rhs)))
-            }
-            Node::Exp(inner) => {
-                let rhs = self.gen_node(inner, depth)?;
-                Some(Box::new(Node::Exp(rhs)))
+            Node::Random => {
+                if let Some(seed) = position {
+                    self.rng = Rng_::new(seed);
+                }
+                let val = self.rng.next_float() * 2.0 - 1.0;
+                Some(Box::new(Node::Number(val)))
             }
 
-            Node::Add(lhs, rhs) => {
-                let lhs = self.gen_node(lhs, depth)?;
-                let rhs = self.gen_node(rhs, depth)?;
-                Some(Box::new(Node::Add(lhs, rhs)))
-            }
-            Node::Mult(lhs, rhs) => {
-                let lhs = self.gen_node(lhs, depth)?;
-                let rhs = self.gen_node(rhs, depth)?;
-                Some(Box::new(Node::Mult(lhs, rhs)))
-            }
-            Node::Div(lhs, rhs) => {
-                let lhs = self.gen_node(lhs, depth)?;
-                let rhs = self.gen_node(rhs, depth)?;
-                Some(Box::new(Node::Div(lhs, rhs)))
-            }
+            Node::Var(_) => Some(Box::new(self.gen_var(position))),
 
-            Node::MixUnbounded(a, b, c, d) => {
-                let a = self.gen_node(a, depth)?;
-                let b = self.gen_node(b, depth)?;
-                let c = self.gen_node(c, depth)?;
-                let d = self.gen_node(d, depth)?;
-                Some(Box::new(Node::MixUnbounded(a, b, c, d)))
+            node => {
+                let children = node
+                    .children()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, child)| {
+                        let position = position.map(|seed| derive_child_seed(seed, i as u64 + 1));
+                        let binds = self.enter_child(node, i);
+                        let child = self.gen_node(child, depth, Some(node.name()), position);
+                        self.bindings -= binds;
+                        child
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let mut node = node.with_children(children);
+                self.finish_node(&mut node, position);
+                Some(Box::new(node))
             }
+        }
+    }
 
-            Node::Triple(first, second, third) => {
-                let first = self.gen_node(first, depth)?;
-                let second = self.gen_node(second, depth)?;
-                let third = self.gen_node(third, depth)?;
-                Some(Box::new(Node::Triple(first, second, third)))
-            }
+    /// Whether every `Var` written in `node` outside its own `Let`s has a
+    /// binding to refer to.
+    fn in_scope(&self, node: &Node) -> bool {
+        self.bindings > 0 || node.unbound_var().is_none()
+    }
 
-            Node::Rule(rule_index) => {
-                let new_depth = depth.checked_sub(1)?;
-                self.gen_rule(*rule_index, new_depth)
-            }
+    /// Start growing child `i` of `node`, which the body of a `Let` does with
+    /// one more binding in scope. Returns how many bindings were added.
+    fn enter_child(&mut self, node: &Node, i: usize) -> u32 {
+        let binds = u32::from(matches!(node, Node::Let(..)) && i == 1);
+        self.bindings += binds;
+        binds
+    }
 
-            Node::Random => {
-                let val = self.rng.next_float() * 2.0 - 1.0;
-                Some(Box::new(Node::Number(val)))
-            }
+    /// A `Var` referring to one of the enclosing `Let`s, drawn the way a
+    /// `Random` draws its number. With no `Let` around, which only happens
+    /// when a rule has nothing else to pick, it stands in for a `Random`.
+    fn gen_var(&mut self, position: Option<u64>) -> Node {
+        if let Some(seed) = position {
+            self.rng = Rng_::new(seed);
+        }
+        match self.bindings {
+            0 => Node::Number(self.rng.next_float() * 2.0 - 1.0),
+            bindings => Node::Var((self.rng.next_u64() % u64::from(bindings)) as u32),
+        }
+    }
+
+    /// Fill in what a newly grown node carries besides its children: a `Noise`
+    /// table of its own, a loop's number of passes or a `Quantize`'s number of
+    /// levels, drawn the way a `Random` draws its number, or a `Let`'s id.
+    fn finish_node(&mut self, node: &mut Node, position: Option<u64>) {
+        match node {
+            Node::Noise(_, _, perm) => *perm = Permutation::from_seed(self.draw(position)),
+            Node::Iterate(_, n) | Node::Escape(_, n) => *n = self.draw_in(GROWN_ITERATIONS, position),
+            Node::Quantize(_, levels) => *levels = self.draw_in(GROWN_LEVELS, position),
+            // Numbered by nesting, so a `Let` never shadows one around it.
+            Node::Let(id, ..) => *id = self.bindings,
+            _ => {}
+        }
+    }
+
+    /// A random draw for the node at `position`, from its own seed if positions
+    /// are being tracked.
+    fn draw(&mut self, position: Option<u64>) -> u64 {
+        if let Some(seed) = position {
+            self.rng = Rng_::new(seed);
         }
+        self.rng.next_u64()
+    }
+
+    /// A [`draw`](Self::draw) for the node at `position`, spread over `range`.
+    fn draw_in(&mut self, range: RangeInclusive<u32>, position: Option<u64>) -> u32 {
+        let span = u64::from(range.end() - range.start() + 1);
+        range.start() + (self.draw(position) % span) as u32
+    }
+}
+
+impl fmt::Display for Grammar {
+    /// Writes the rules in the text format accepted by [`Grammar::parse`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        parse::write_rules(f, &self.rules, self.start, &self.constraints)
     }
 }
 
@@ -198,18 +456,298 @@ pub fn derive_seeds(base: u64) -> (u64, u64, u64) {
     )
 }
 
-pub fn generate_tree_parallel(grand_seed: u64, depth: u32) -> Option<Box<Node>> {
+/// The seed for a `Quad`'s alpha channel, alongside the three colour channels
+/// from [`derive_seeds`].
+pub fn derive_alpha_seed(base: u64) -> u64 {
+    xxh3_64(&[base.to_le_bytes().as_slice(), b"-d"].concat())
+}
+
+/// The seed for the `index`th position below the one seeded by `base`, in the
+/// same spirit as [`derive_seeds`] for the three channels.
+pub fn derive_child_seed(base: u64, index: u64) -> u64 {
+    xxh3_64(&[base.to_le_bytes().as_slice(), b"-", &index.to_le_bytes()].concat())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WeightError {
+    /// No alternate of any rule is this operator or rule.
+    UnknownOperator(String),
+    /// The weight is negative or not finite.
+    Invalid { op: String, weight: f32 },
+}
+
+impl fmt::Display for WeightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightError::UnknownOperator(op) => write!(f, "no alternate in the grammar is `{op}`"),
+            WeightError::Invalid { op, weight } => {
+                write!(f, "weight for `{op}` must be finite and non-negative, found {weight}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WeightError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerateError {
+    /// The grammar failed [`Grammar::validate`].
+    InvalidGrammar(Vec<GrammarError>),
+    /// The start rule is not a single `Triple`, `Mono` or `Quad` alternate.
+    StartNotRoot,
+    /// The grammar's smallest tree has more nodes than the budget allows.
+    BudgetTooSmall { smallest: usize },
+    /// No seed tried grew a tree as large as the budget's minimum.
+    BudgetMissed { budget: NodeBudget, largest: usize },
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::InvalidGrammar(errors) => {
+                f.write_str("invalid grammar: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    write!(f, "{error}")?;
+                }
+                Ok(())
+            }
+            GenerateError::StartNotRoot => {
+                f.write_str("the start rule must be a single `Triple`, `Mono` or `Quad` alternate")
+            }
+            GenerateError::BudgetTooSmall { smallest } => {
+                write!(f, "the grammar's smallest tree has {smallest} nodes, more than the budget allows")
+            }
+            GenerateError::BudgetMissed { budget, largest } => {
+                write!(f, "could not grow a tree of {budget} nodes; the largest had {largest}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {}
+
+/// Grow a tree from `grammar`'s start rule, which must be a single `Triple`,
+/// `Mono` or `Quad` alternate. Each channel is generated on its own thread from its own seed.
+pub fn generate_tree_parallel(grammar: &Grammar, grand_seed: u64, depth: u32) -> Result<Box<Node>, GenerateError> {
+    generate(grammar, grand_seed, depth, false)
+}
+
+/// Like [`generate_tree_parallel`], but every position in the tree draws its
+/// choices from its own seed, derived from its parent's with
+/// [`derive_child_seed`]. A position therefore picks the same alternate at any
+/// depth that leaves it room to, so raising the depth only grows subtrees
+/// where the shallower tree had to stop, and the shallower image is a coarse
+/// version of the deeper one. Weights that ramp with depth and constraints
+/// can still make the choices differ.
+pub fn generate_tree_coherent(grammar: &Grammar, grand_seed: u64, depth: u32) -> Result<Box<Node>, GenerateError> {
+    generate(grammar, grand_seed, depth, true)
+}
+
+fn generate(grammar: &Grammar, grand_seed: u64, depth: u32, coherent: bool) -> Result<Box<Node>, GenerateError> {
+    grammar.validate().map_err(GenerateError::InvalidGrammar)?;
     let (seed_a, seed_b, seed_c) = derive_seeds(grand_seed);
 
-    let (b, c) = rayon::join(
-        || Grammar::default(seed_b).gen_rule(1, depth - 1),
-        || Grammar::default(seed_c).gen_rule(1, depth - 1),
-    );
+    let channel = |node: &Node, seed: u64, parent| {
+        let position = coherent.then_some(seed);
+        grammar.with_seed(seed).gen_node(node, depth, Some(parent), position)
+    };
 
-    let a = Grammar::default(seed_a).gen_rule(1, depth - 1);
+    let (first, second, third) = match grammar.start_root() {
+        Some(Node::Triple(first, second, third)) => (first, second, third),
+        Some(Node::Mono(first)) => {
+            let a = channel(first, seed_a, "Mono").expect("a validated grammar always terminates");
+            return Ok(Box::new(Node::Mono(a)));
+        }
+        Some(Node::Quad(first, second, third, alpha)) => {
+            let seed_d = derive_alpha_seed(grand_seed);
+            let ((a, b), (c, d)) = rayon::join(
+                || rayon::join(|| channel(first, seed_a, "Quad"), || channel(second, seed_b, "Quad")),
+                || rayon::join(|| channel(third, seed_c, "Quad"), || channel(alpha, seed_d, "Quad")),
+            );
+            return match (a, b, c, d) {
+                (Some(a), Some(b), Some(c), Some(d)) => Ok(Box::new(Node::Quad(a, b, c, d))),
+                _ => unreachable!("a validated grammar always terminates"),
+            };
+        }
+        _ => return Err(GenerateError::StartNotRoot),
+    };
+    let (b, c) = rayon::join(|| channel(second, seed_b, "Triple"), || channel(third, seed_c, "Triple"));
+    let a = channel(first, seed_a, "Triple");
 
     match (a, b, c) {
-        (Some(a), Some(b), Some(c)) => Some(Box::new(Node::Triple(a, b, c))),
-        _ => None,
+        (Some(a), Some(b), Some(c)) => Ok(Box::new(Node::Triple(a, b, c))),
+        _ => unreachable!("a validated grammar always terminates"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_overrides_renormalise() {
+        let mut grammar = Grammar::default(0);
+        grammar.set_weight("sin", 0.0).unwrap();
+        grammar.set_weight("A", 12.0).unwrap();
+        assert_eq!(grammar.validate(), Ok(()));
+        let printed = grammar.to_string();
+        assert!(printed.contains("Sin(C) [0]") && printed.contains("A [12]"), "{printed}");
+
+        assert_eq!(grammar.set_weight("tan", 1.0), Err(WeightError::UnknownOperator("tan".into())));
+        assert!(matches!(grammar.set_weight("cos", -1.0), Err(WeightError::Invalid { .. })));
+
+        for op in ["x", "y", "random"] {
+            grammar.set_weight(op, 0.0).unwrap();
+        }
+        let errors = grammar.validate().unwrap_err();
+        assert!(errors.contains(&GrammarError::NonTerminating { rule: "A".into() }), "{errors:?}");
+    }
+
+    /// Where `shallow` and `deep` differ, `shallow` must have stopped at a terminal.
+    fn refines(shallow: &Node, deep: &Node) -> bool {
+        if shallow.name() != deep.name() || shallow.children().len() != deep.children().len() {
+            return shallow.children().is_empty();
+        }
+        match (shallow, deep) {
+            (Node::Number(a), Node::Number(b)) => a == b,
+            _ => shallow.children().into_iter().zip(deep.children()).all(|(s, d)| refines(s, d)),
+        }
+    }
+
+    #[test]
+    fn coherent_trees_refine_with_depth() {
+        let grammar = Grammar::default(0);
+        for seed in 0..50 {
+            let trees: Vec<_> = (4..=12).map(|depth| generate_tree_coherent(&grammar, seed, depth).unwrap()).collect();
+            for pair in trees.windows(2) {
+                assert!(refines(&pair[0], &pair[1]), "seed {seed}");
+            }
+            assert_eq!(trees[0], generate_tree_coherent(&grammar, seed, 4).unwrap());
+        }
+    }
+
+    #[test]
+    fn noise_tables_come_from_the_seed() {
+        fn tables(node: &Node, out: &mut Vec<Permutation>) {
+            if let Node::Noise(_, _, perm) = node {
+                out.push(perm.clone());
+            }
+            node.children().into_iter().for_each(|child| tables(child, out));
+        }
+
+        let grammar = Grammar::parse("E ::= Triple(Noise(X, Y), Noise(Y, X), Noise(X, X))\n", 0).unwrap();
+        let grown = |seed| {
+            let mut out = Vec::new();
+            tables(&generate_tree_parallel(&grammar, seed, 4).unwrap(), &mut out);
+            out
+        };
+        let first = grown(1);
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|perm| *perm != Permutation::identity()));
+        assert_ne!(first[0], first[1]);
+        assert_eq!(grown(1), first);
+        assert_ne!(grown(2), first);
+    }
+
+    #[test]
+    fn lets_are_numbered_by_nesting_and_vars_stay_in_scope() {
+        /// Every `Let` at `level` has id `level`, and every `Var` refers to one around it.
+        fn well_scoped(node: &Node, level: u32) -> bool {
+            match node {
+                Node::Let(id, value, body) => *id == level && well_scoped(value, level) && well_scoped(body, level + 1),
+                Node::Var(id) => *id < level,
+                node => node.children().into_iter().all(|child| well_scoped(child, level)),
+            }
+        }
+        fn count_vars(node: &Node) -> usize {
+            usize::from(matches!(node, Node::Var(_))) + node.children().into_iter().map(count_vars).sum::<usize>()
+        }
+
+        let source = "E ::= Triple(C, C, C)\nC ::= A | Sin(C) | Add(C, C) | Let(C, C) [2]\nA ::= X | Y | Var [3]\n";
+        let grammar = Grammar::parse(source, 0).unwrap();
+        let mut vars = 0;
+        for seed in 0..30 {
+            let trees = [
+                generate_tree_parallel(&grammar, seed, 10).unwrap(),
+                generate_tree_coherent(&grammar, seed, 10).unwrap(),
+                generate_tree_budget(&grammar, seed, NodeBudget::range(20, 200)).unwrap(),
+            ];
+            for tree in trees {
+                assert!(well_scoped(&tree, 0), "seed {seed}: {tree:?}");
+                vars += count_vars(&tree);
+            }
+        }
+        assert!(vars > 0);
+    }
+
+    #[test]
+    fn loops_draw_their_passes_from_the_seed() {
+        fn passes(node: &Node, out: &mut Vec<u32>) {
+            if let Node::Iterate(_, n) | Node::Escape(_, n) = node {
+                out.push(*n);
+            }
+            node.children().into_iter().for_each(|child| passes(child, out));
+        }
+
+        let grammar = Grammar::parse("E ::= Triple(Iterate(X), Escape(Y), Iterate(Escape(X)))\n", 0).unwrap();
+        let grown = |seed| {
+            let mut out = Vec::new();
+            passes(&generate_tree_parallel(&grammar, seed, 4).unwrap(), &mut out);
+            out
+        };
+        let all: Vec<u32> = (0..20).flat_map(grown).collect();
+        assert!(all.iter().all(|n| GROWN_ITERATIONS.contains(n)), "{all:?}");
+        assert!(all.iter().any(|&n| n != all[0]));
+        assert_eq!(grown(3), grown(3));
+    }
+
+    #[test]
+    fn quantize_draws_its_levels_from_the_seed() {
+        let grammar = Grammar::parse("E ::= Triple(Quantize(X), Quantize(Y), Xor(X, Y))\n", 0).unwrap();
+        let levels: Vec<u32> = (0..20)
+            .flat_map(|seed| match *generate_tree_parallel(&grammar, seed, 4).unwrap() {
+                Node::Triple(a, b, _) => [a, b].map(|node| match *node {
+                    Node::Quantize(_, levels) => levels,
+                    other => panic!("expected Quantize, got {other:?}"),
+                }),
+                other => panic!("expected Triple, got {other:?}"),
+            })
+            .collect();
+        assert!(levels.iter().all(|n| GROWN_LEVELS.contains(n)), "{levels:?}");
+        assert!(levels.iter().any(|&n| n != levels[0]));
+    }
+
+    #[test]
+    fn shallow_trees_end_in_terminals() {
+        let grammar = Grammar::default(0);
+        for depth in 1..=4 {
+            for seed in 0..200 {
+                assert!(generate_tree_parallel(&grammar, seed, depth).is_ok(), "seed {seed}, depth {depth}");
+            }
+        }
+    }
+
+    #[test]
+    fn fallback_takes_the_shortest_route() {
+        // `C` can only finish through `B`, one expansion further than `A`'s terminals.
+        let mut builder = GrammarBuilder::new();
+        let (a, b, c) = (builder.rule("A"), builder.rule("B"), builder.rule("C"));
+        builder
+            .alternate("E", Node::Triple(Box::new(a), Box::new(b.clone()), Box::new(c.clone())), 1.0)
+            .alternate("A", Node::X, 1.0)
+            .alternate("B", Node::Sin(Box::new(c.clone())), 1.0)
+            .alternate("B", Node::Y, Weight::Ramp { far: 1.0, near: 0.0, span: 2 })
+            .alternate("C", Node::Cos(Box::new(c)), 5.0)
+            .alternate("C", Node::Sqrt(Box::new(b)), 1.0);
+        let grammar = builder.build(0).unwrap();
+        assert_eq!(grammar.validate(), Ok(()));
+
+        let tree = generate_tree_parallel(&grammar, 3, 1).unwrap();
+        let Node::Triple(_, b, c) = *tree else { panic!("expected a triple") };
+        assert_eq!(*b, Node::Y);
+        assert_eq!(*c, Node::Sqrt(Box::new(Node::Y)));
     }
 }
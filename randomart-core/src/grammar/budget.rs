@@ -0,0 +1,323 @@
+//! Growing trees to a node count instead of a depth.
+
+use super::{derive_alpha_seed, derive_seeds, GenerateError, Grammar};
+use crate::node::Node;
+use std::fmt;
+use std::str::FromStr;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// How many seeds derived from the grand seed are tried before giving up on
+/// reaching [`NodeBudget::min`].
+const ATTEMPTS: u64 = 32;
+
+/// Bounds on the size of a generated tree, counting every node including the
+/// root `Triple`. Sizes are measured before simplification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeBudget {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl NodeBudget {
+    /// Aim for `nodes`, accepting trees up to 10% smaller.
+    pub fn target(nodes: usize) -> Self {
+        Self { min: nodes - nodes / 10, max: nodes }
+    }
+
+    pub fn range(min: usize, max: usize) -> Self {
+        Self { min, max }
+    }
+}
+
+impl fmt::Display for NodeBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.min, self.max)
+    }
+}
+
+/// Parses `N` as [`NodeBudget::target`] or `MIN..MAX` as an inclusive range.
+impl FromStr for NodeBudget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |s: &str| s.trim().parse::<usize>().map_err(|_| format!("`{s}` is not a node count"));
+        let budget = match s.split_once("..") {
+            Some((min, max)) => Self::range(number(min)?, number(max)?),
+            None => Self::target(number(s)?),
+        };
+        if budget.min > budget.max {
+            return Err(format!("minimum {} is above maximum {}", budget.min, budget.max));
+        }
+        Ok(budget)
+    }
+}
+
+/// Nodes the smallest tree each rule can finish with, or `None` if it never
+/// finishes. Only alternates with a non-zero weight count.
+fn min_rule_costs(grammar: &Grammar) -> Vec<Option<usize>> {
+    let mut costs = vec![None; grammar.rules.len()];
+    loop {
+        let mut changed = false;
+        for (idx, rule) in grammar.rules.iter().enumerate() {
+            let cheapest = rule
+                .alternates
+                .iter()
+                .filter(|branch| branch.weight.peak() > 0.0)
+                .filter_map(|branch| min_cost(&branch.node, &costs))
+                .min();
+            if cheapest.is_some() && (costs[idx].is_none() || cheapest < costs[idx]) {
+                costs[idx] = cheapest;
+                changed = true;
+            }
+        }
+        if !changed {
+            return costs;
+        }
+    }
+}
+
+fn min_cost(node: &Node, costs: &[Option<usize>]) -> Option<usize> {
+    match node {
+        Node::Rule(idx) => *costs.get(*idx)?,
+        node => node.children().into_iter().try_fold(1, |sum, child| Some(sum + min_cost(child, costs)?)),
+    }
+}
+
+/// One channel's worth of budgeted generation: a grammar with its own RNG
+/// stream plus the smallest size each rule can take.
+struct Grower<'a> {
+    grammar: Grammar,
+    costs: &'a [Option<usize>],
+}
+
+impl Grower<'_> {
+    fn cost(&self, node: &Node) -> usize {
+        min_cost(node, self.costs).expect("a validated grammar always terminates")
+    }
+
+    /// Grow `node`, which sits directly inside a `parent` node, into a tree of
+    /// at most `allowance` nodes, which must be at least [`Self::cost`] of it.
+    /// Returns the tree and its size.
+    fn grow(&mut self, node: &Node, allowance: usize, parent: Option<&'static str>) -> (Box<Node>, usize) {
+        match node {
+            Node::Rule(rule) => {
+                // Ramped weights use their `far` value: budgets have no notion of depth.
+                let branches = self.grammar.rules[*rule].clone();
+                let affordable = branches
+                    .alternates
+                    .iter()
+                    .filter(|branch| branch.weight.peak() > 0.0)
+                    .filter(|branch| min_cost(&branch.node, self.costs).is_some_and(|cost| cost <= allowance))
+                    .collect::<Vec<_>>();
+                let mut permitted = Vec::new();
+                for &branch in &affordable {
+                    if self.grammar.in_scope(&branch.node) && self.grammar.permits(&branch.node, parent, None) {
+                        permitted.push(branch);
+                    }
+                }
+
+                let branch = match self.grammar.choose(permitted.iter().copied(), u32::MAX) {
+                    Some(branch) => branch,
+                    // Nothing permitted fits, so the constraints give way.
+                    None => match self.grammar.choose(affordable.iter().copied(), u32::MAX) {
+                        Some(branch) => branch,
+                        // Only zero-weight alternates fit; take one anyway.
+                        None => *affordable.first().expect("allowance covers the rule's cheapest alternate"),
+                    },
+                };
+                self.grammar.record(&branch.node, None);
+                self.grow(&branch.node, allowance, parent)
+            }
+
+            Node::Random => {
+                let val = self.grammar.rng.next_float() * 2.0 - 1.0;
+                (Box::new(Node::Number(val)), 1)
+            }
+
+            Node::Var(_) => (Box::new(self.grammar.gen_var(None)), 1),
+
+            node => {
+                let children = node.children();
+                let costs: Vec<usize> = children.iter().map(|child| self.cost(child)).collect();
+                let mut remaining = allowance - 1;
+                let mut grown = Vec::with_capacity(children.len());
+
+                // Each child gets its own minimum plus an equal share of what is
+                // left over; whatever it leaves unused carries to its siblings.
+                for (i, child) in children.iter().enumerate() {
+                    let reserved: usize = costs[i + 1..].iter().sum();
+                    let spare = remaining - reserved - costs[i];
+                    let share = costs[i] + spare / (children.len() - i);
+                    let binds = self.grammar.enter_child(node, i);
+                    let (tree, used) = self.grow(child, share, Some(node.name()));
+                    self.grammar.bindings -= binds;
+                    remaining -= used;
+                    grown.push(tree);
+                }
+
+                let mut node = node.with_children(grown);
+                self.grammar.finish_node(&mut node, None);
+                (Box::new(node), allowance - remaining)
+            }
+        }
+    }
+}
+
+/// Like [`generate_tree_parallel`](super::generate_tree_parallel), but grows
+/// the tree to a size within `budget` instead of to a depth. If a tree comes out
+/// smaller than `budget.min`, seeds derived from `grand_seed` are tried in turn,
+/// so the result is still a pure function of the seed.
+pub fn generate_tree_budget(grammar: &Grammar, grand_seed: u64, budget: NodeBudget) -> Result<Box<Node>, GenerateError> {
+    grammar.validate().map_err(GenerateError::InvalidGrammar)?;
+
+    let Some(root) = grammar.start_root() else {
+        return Err(GenerateError::StartNotRoot);
+    };
+
+    let costs = min_rule_costs(grammar);
+    let smallest = min_cost(root, &costs).expect("a validated grammar always terminates");
+    if budget.max < smallest {
+        return Err(GenerateError::BudgetTooSmall { smallest });
+    }
+
+    let mut largest = 0;
+    for attempt in 0..ATTEMPTS {
+        let seed = match attempt {
+            0 => grand_seed,
+            n => xxh3_64(&[grand_seed.to_le_bytes(), n.to_le_bytes()].concat()),
+        };
+        let (tree, size) = grow_channels(grammar, &costs, root, seed, budget.max);
+        if size >= budget.min {
+            return Ok(tree);
+        }
+        largest = largest.max(size);
+    }
+
+    Err(GenerateError::BudgetMissed { budget, largest })
+}
+
+/// Split `max` between the channels of `root` and grow them in parallel.
+fn grow_channels(grammar: &Grammar, costs: &[Option<usize>], root: &Node, seed: u64, max: usize) -> (Box<Node>, usize) {
+    let (seed_a, seed_b, seed_c) = derive_seeds(seed);
+    let grower = |seed| Grower { grammar: grammar.with_seed(seed), costs };
+    let (first, second, third) = match root {
+        Node::Triple(first, second, third) => (first, second, third),
+        Node::Mono(channel) => {
+            let (a, used) = grower(seed_a).grow(channel, max - 1, Some("Mono"));
+            return (Box::new(Node::Mono(a)), 1 + used);
+        }
+        Node::Quad(first, second, third, alpha) => {
+            let seed_d = derive_alpha_seed(seed);
+            let mins = [first, second, third, alpha].map(|child| min_cost(child, costs).unwrap());
+            let spare = (max - 1 - mins.iter().sum::<usize>()) / 4;
+            let (((a, used_a), (b, used_b)), ((c, used_c), (d, used_d))) = rayon::join(
+                || {
+                    rayon::join(
+                        || grower(seed_a).grow(first, mins[0] + spare, Some("Quad")),
+                        || grower(seed_b).grow(second, mins[1] + spare, Some("Quad")),
+                    )
+                },
+                || {
+                    rayon::join(
+                        || grower(seed_c).grow(third, mins[2] + spare, Some("Quad")),
+                        || grower(seed_d).grow(alpha, mins[3] + spare, Some("Quad")),
+                    )
+                },
+            );
+            return (Box::new(Node::Quad(a, b, c, d)), 1 + used_a + used_b + used_c + used_d);
+        }
+        _ => unreachable!("checked by the caller"),
+    };
+
+    let [cost_a, cost_b, cost_c] = [first, second, third].map(|child| min_cost(child, costs).unwrap());
+    let spare = (max - 1 - cost_a - cost_b - cost_c) / 3;
+
+    let ((b, used_b), (c, used_c)) = rayon::join(
+        || grower(seed_b).grow(second, cost_b + spare, Some("Triple")),
+        || grower(seed_c).grow(third, cost_c + spare, Some("Triple")),
+    );
+    let (a, used_a) = grower(seed_a).grow(first, cost_a + spare, Some("Triple"));
+
+    (Box::new(Node::Triple(a, b, c)), 1 + used_a + used_b + used_c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::GrammarBuilder;
+
+    fn size(node: &Node) -> usize {
+        1 + node.children().into_iter().map(size).sum::<usize>()
+    }
+
+    #[test]
+    fn trees_land_in_the_budget() {
+        let grammar = Grammar::default(0);
+        for nodes in [50, 1_000, 20_000] {
+            let budget = NodeBudget::target(nodes);
+            for seed in 0..20 {
+                let tree = generate_tree_budget(&grammar, seed, budget).unwrap();
+                let n = size(&tree);
+                assert!(budget.min <= n && n <= budget.max, "seed {seed}: {n} nodes outside {budget}");
+                assert_eq!(generate_tree_budget(&grammar, seed, budget).unwrap(), tree);
+            }
+        }
+    }
+
+    #[test]
+    fn mono_roots_spend_the_whole_budget_on_one_channel() {
+        let source = "E ::= Mono(C)\nC ::= A | Add(C, C) | Mult(C, C) | Sin(C) [3] | Cos(C) [3]\n    | Exp(C) | Sqrt(C) | Div(C, C) | MixUnbounded(C, C, C, C)\nA ::= X | Y | Random\n";
+        let grammar = Grammar::parse(source, 0).unwrap();
+        let budget = NodeBudget::target(500);
+        for seed in 0..10 {
+            let tree = generate_tree_budget(&grammar, seed, budget).unwrap();
+            assert!(matches!(*tree, Node::Mono(_)));
+            let n = size(&tree);
+            assert!(budget.min <= n && n <= budget.max, "seed {seed}: {n} nodes outside {budget}");
+        }
+    }
+
+    #[test]
+    fn quad_roots_share_the_budget_between_four_channels() {
+        let source = "E ::= Quad(C, C, C, C)\nC ::= A | Add(C, C) | Mult(C, C) | Sin(C) [3] | Cos(C) [3]\n    | Exp(C) | Sqrt(C) | Div(C, C) | MixUnbounded(C, C, C, C)\nA ::= X | Y | Random\n";
+        let grammar = Grammar::parse(source, 0).unwrap();
+        // A channel that closes off early leaves its share unspent, so a
+        // looser minimum keeps this about the split rather than the grammar.
+        let budget = NodeBudget { min: 500, max: 1_000 };
+        for seed in 0..10 {
+            let tree = generate_tree_budget(&grammar, seed, budget).unwrap();
+            assert!(matches!(*tree, Node::Quad(..)));
+            let n = size(&tree);
+            assert!(budget.min <= n && n <= budget.max, "seed {seed}: {n} nodes outside {budget}");
+        }
+    }
+
+    #[test]
+    fn reports_budgets_that_cannot_be_met() {
+        let grammar = Grammar::default(0);
+        // A triple of three terminals is the smallest tree the default grammar makes.
+        assert_eq!(
+            generate_tree_budget(&grammar, 0, NodeBudget::target(3)),
+            Err(GenerateError::BudgetTooSmall { smallest: 4 })
+        );
+
+        let mut builder = GrammarBuilder::new();
+        let c = builder.rule("C");
+        builder
+            .alternate("E", Node::Triple(Box::new(c.clone()), Box::new(c.clone()), Box::new(c.clone())), 1.0)
+            .alternate("C", Node::X, 1.0);
+        let tiny = builder.build(0).unwrap();
+        assert_eq!(
+            generate_tree_budget(&tiny, 0, NodeBudget::range(10, 20)),
+            Err(GenerateError::BudgetMissed { budget: NodeBudget::range(10, 20), largest: 4 })
+        );
+    }
+
+    #[test]
+    fn parses_targets_and_ranges() {
+        assert_eq!("1000".parse(), Ok(NodeBudget::range(900, 1000)));
+        assert_eq!("200..300".parse(), Ok(NodeBudget::range(200, 300)));
+        assert!("300..200".parse::<NodeBudget>().is_err());
+        assert!("lots".parse::<NodeBudget>().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use super::{constraints, validate, Constraints, Grammar, GrammarBranches, Weight};
+use crate::node::Node;
+use crate::rng::Rng_;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// No rule was given any alternates.
+    NoRules,
+    /// A rule is referenced but never given any alternates.
+    UndefinedRule(String),
+    /// The designated start rule does not exist.
+    UnknownStart(String),
+    /// A constraint names a node kind that does not exist.
+    UnknownNode(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::NoRules => f.write_str("grammar has no rules"),
+            BuildError::UndefinedRule(name) => write!(f, "rule `{name}` is referenced but never defined"),
+            BuildError::UnknownStart(name) => write!(f, "start rule `{name}` is not defined"),
+            BuildError::UnknownNode(name) => write!(f, "constraint names unknown node `{name}`"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Assembles a [`Grammar`] from named rules, so alternates never have to spell
+/// out raw `Node::Rule` indices.
+///
+/// ```
+/// use randomart_core::grammar::GrammarBuilder;
+/// use randomart_core::node::Node;
+///
+/// let mut builder = GrammarBuilder::new();
+/// let c = builder.rule("C");
+/// builder
+///     .alternate("E", Node::Triple(Box::new(c.clone()), Box::new(c.clone()), Box::new(c.clone())), 1.0)
+///     .alternate("C", Node::Sin(Box::new(c.clone())), 1.0)
+///     .alternate("C", Node::X, 2.0);
+/// let grammar = builder.build(42).unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct GrammarBuilder {
+    rules: Vec<GrammarBranches>,
+    start: Option<String>,
+    first_defined: Option<usize>,
+    constraints: Constraints,
+}
+
+impl GrammarBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index(&mut self, name: &str) -> usize {
+        if let Some(idx) = self.rules.iter().position(|rule| rule.name == name) {
+            return idx;
+        }
+        self.rules.push(GrammarBranches::new(name));
+        self.rules.len() - 1
+    }
+
+    /// A reference to the rule `name`, for use inside alternates. The rule may be
+    /// defined before or after it is referenced.
+    pub fn rule(&mut self, name: &str) -> Node {
+        Node::Rule(self.index(name))
+    }
+
+    /// Add `node` as an alternate of rule `rule`, chosen with probability
+    /// proportional to `weight` among that rule's alternates. A plain `f32` is a
+    /// fixed weight; see [`Weight::Ramp`] for one that changes with depth.
+    pub fn alternate(&mut self, rule: &str, node: Node, weight: impl Into<Weight>) -> &mut Self {
+        let idx = self.index(rule);
+        self.first_defined.get_or_insert(idx);
+        self.rules[idx].add_alternate(node, weight.into());
+        self
+    }
+
+    /// Make `rule` the start rule. Defaults to the first rule given an alternate.
+    pub fn start(&mut self, rule: &str) -> &mut Self {
+        self.start = Some(rule.to_string());
+        self
+    }
+
+    /// Never grow a `child` node directly inside a `parent` node.
+    pub fn forbid(&mut self, parent: &str, child: &str) -> &mut Self {
+        self.constraints.forbidden.push((parent.to_string(), child.to_string()));
+        self
+    }
+
+    /// Allow at most `max` nodes of kind `node` per channel.
+    pub fn max_count(&mut self, node: &str, max: usize) -> &mut Self {
+        self.constraints.max_counts.insert(node.to_string(), max);
+        self
+    }
+
+    /// Require at least `min` `X` or `Y` leaves per channel.
+    pub fn min_xy_leaves(&mut self, min: usize) -> &mut Self {
+        self.constraints.min_xy_leaves = min;
+        self
+    }
+
+    pub fn build(&self, seed: u64) -> Result<Grammar, BuildError> {
+        if let Some(rule) = self.rules.iter().find(|rule| rule.alternates.is_empty()) {
+            return Err(BuildError::UndefinedRule(rule.name.clone()));
+        }
+
+        if let Some(name) = self.constraints.names().find(|name| !constraints::is_node_name(name)) {
+            return Err(BuildError::UnknownNode(name.to_string()));
+        }
+
+        let start = match &self.start {
+            Some(name) => self
+                .rules
+                .iter()
+                .position(|rule| rule.name == *name)
+                .ok_or_else(|| BuildError::UnknownStart(name.clone()))?,
+            None => self.first_defined.ok_or(BuildError::NoRules)?,
+        };
+
+        Ok(Grammar {
+            rules: self.rules.clone(),
+            start,
+            heights: validate::termination_heights(&self.rules),
+            constraints: self.constraints.clone(),
+            tally: Default::default(),
+            bindings: 0,
+            rng: Rng_::new(seed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::generate_tree_parallel;
+
+    fn boxed(node: &Node) -> Box<Node> {
+        Box::new(node.clone())
+    }
+
+    #[test]
+    fn resolves_names_and_start_rule() {
+        let mut builder = GrammarBuilder::new();
+        let leaf = builder.rule("Leaf");
+        builder
+            .alternate("Leaf", Node::X, 1.0)
+            .alternate("Leaf", Node::Y, 1.0)
+            .alternate("Image", Node::Triple(boxed(&leaf), boxed(&leaf), boxed(&leaf)), 1.0)
+            .start("Image");
+        let grammar = builder.build(0).unwrap();
+
+        assert_eq!(grammar.rule_index("Leaf"), Some(0));
+        assert_eq!(grammar.start_rule(), grammar.rule_index("Image").unwrap());
+        assert!(generate_tree_parallel(&grammar, 7, 4).is_ok());
+
+        // The start rule is written first so the text form keeps it as the start.
+        let reparsed = Grammar::parse(&grammar.to_string(), 0).unwrap();
+        assert_eq!(reparsed.to_string(), grammar.to_string());
+        assert_eq!(reparsed.rules[reparsed.start_rule()].name, "Image");
+    }
+
+    #[test]
+    fn reports_undefined_rules_and_start() {
+        let mut builder = GrammarBuilder::new();
+        let missing = builder.rule("Missing");
+        builder.alternate("E", Node::Sin(Box::new(missing)), 1.0);
+        assert_eq!(builder.build(0).err(), Some(BuildError::UndefinedRule("Missing".into())));
+
+        let mut builder = GrammarBuilder::new();
+        builder.alternate("E", Node::X, 1.0).start("Nope");
+        assert_eq!(builder.build(0).err(), Some(BuildError::UnknownStart("Nope".into())));
+
+        assert_eq!(GrammarBuilder::new().build(0).err(), Some(BuildError::NoRules));
+
+        let mut builder = GrammarBuilder::new();
+        builder.alternate("E", Node::X, 1.0).forbid("Sin", "Sinh");
+        assert_eq!(builder.build(0).err(), Some(BuildError::UnknownNode("Sinh".into())));
+    }
+}
@@ -0,0 +1,245 @@
+//! Declarative limits on the shape of generated trees, enforced while picking
+//! alternates rather than by throwing finished trees away.
+
+use super::{parse, validate, Grammar, GrammarBranch};
+use crate::node::Node;
+use std::collections::{BTreeMap, HashMap};
+
+/// Limits that generation keeps each channel within. Node kinds are named as
+/// in grammar files (`Sin`, `Random`, ...).
+///
+/// These hold as long as the grammar leaves a way to satisfy them. When no
+/// alternate of a rule does, generation picks from all of them as if there
+/// were no constraints. The `X`/`Y` minimum only applies to depth-based
+/// generation, not [`generate_tree_budget`](super::generate_tree_budget).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Constraints {
+    /// `(parent, child)` pairs that may not be directly nested, e.g. `("Sin", "Sin")`.
+    pub forbidden: Vec<(String, String)>,
+    /// The most nodes of each kind a channel may contain.
+    pub max_counts: BTreeMap<String, usize>,
+    /// The fewest `X` and `Y` leaves, together, a channel must contain.
+    pub min_xy_leaves: usize,
+}
+
+impl Constraints {
+    pub fn is_empty(&self) -> bool {
+        self.forbidden.is_empty() && self.max_counts.is_empty() && self.min_xy_leaves == 0
+    }
+
+    /// Every node name mentioned, for checking they exist.
+    pub(super) fn names(&self) -> impl Iterator<Item = &str> {
+        self.forbidden
+            .iter()
+            .flat_map(|(parent, child)| [parent.as_str(), child.as_str()])
+            .chain(self.max_counts.keys().map(String::as_str))
+    }
+}
+
+/// Whether `name` is a node kind constraints can refer to.
+pub(super) fn is_node_name(name: &str) -> bool {
+    parse::builtin_arity(name).is_some() || name == "Number"
+}
+
+/// Per-channel bookkeeping for [`Constraints`] while a tree grows.
+#[derive(Clone, Default)]
+pub(super) struct Tally {
+    counts: BTreeMap<&'static str, usize>,
+    xy_leaves: usize,
+    /// Most `X`/`Y` leaves the rules still waiting to be expanded could add.
+    capacity: usize,
+    /// Memoised [`Grammar::max_xy`] by `(rule, depth)`.
+    max_xy: HashMap<(usize, u32), usize>,
+}
+
+/// `X`/`Y` leaves written directly in `node`, not counting what its rules add.
+fn direct_xy(node: &Node) -> usize {
+    match node {
+        Node::X | Node::Y => 1,
+        node => node.children().into_iter().map(direct_xy).sum(),
+    }
+}
+
+/// Add the nodes written directly in `node` to `counts`.
+fn count_nodes(node: &Node, counts: &mut BTreeMap<&'static str, usize>) {
+    if let Node::Rule(_) = node {
+        return;
+    }
+    *counts.entry(node.name()).or_default() += 1;
+    for child in node.children() {
+        count_nodes(child, counts);
+    }
+}
+
+/// Depth at which the rules inside an alternate picked at `depth` are expanded.
+fn inner_depth(depth: u32) -> u32 {
+    depth.saturating_sub(2)
+}
+
+impl Grammar {
+    /// The alternates `gen_rule(rule, depth)` may pick from: all of them,
+    /// or once depth runs out only those on the shortest route to terminals.
+    pub(super) fn candidates(&self, rule: usize, depth: u32) -> Vec<GrammarBranch> {
+        let alternates = self.rules[rule].alternates.iter();
+        if depth > 0 {
+            return alternates.cloned().collect();
+        }
+        alternates
+            .filter(|branch| branch.weight.peak() > 0.0)
+            .filter(|branch| validate::alternate_height(&branch.node, &self.heights) == self.heights[rule])
+            .cloned()
+            .collect()
+    }
+
+    /// Most `X`/`Y` leaves `gen_rule(rule, depth)` can produce.
+    fn max_xy(&mut self, rule: usize, depth: u32) -> usize {
+        if let Some(&max) = self.tally.max_xy.get(&(rule, depth)) {
+            return max;
+        }
+        let max = self
+            .candidates(rule, depth)
+            .iter()
+            .filter(|branch| depth == 0 || branch.weight.at(depth) > 0.0)
+            .map(|branch| self.alternate_max_xy(&branch.node, depth))
+            .max()
+            .unwrap_or(0);
+        self.tally.max_xy.insert((rule, depth), max);
+        max
+    }
+
+    fn alternate_max_xy(&mut self, node: &Node, depth: u32) -> usize {
+        let mut refs = Vec::new();
+        validate::rule_refs(node, &mut refs);
+        refs.into_iter().fold(direct_xy(node), |sum, r| sum.saturating_add(self.max_xy(r, inner_depth(depth))))
+    }
+
+    /// Start expanding `rule`, which stops counting towards the capacity of
+    /// pending rules now that one of its alternates is about to be picked.
+    pub(super) fn begin_rule(&mut self, rule: usize, depth: u32) {
+        if self.constraints.min_xy_leaves > 0 {
+            let max = self.max_xy(rule, depth);
+            self.tally.capacity = self.tally.capacity.saturating_sub(max);
+        }
+    }
+
+    /// Whether picking `node` for a rule expanded under `parent` keeps within the
+    /// constraints. `depth` is `None` when the `X`/`Y` minimum does not apply.
+    pub(super) fn permits(&mut self, node: &Node, parent: Option<&'static str>, depth: Option<u32>) -> bool {
+        if self.constraints.is_empty() {
+            return true;
+        }
+
+        let mut counts = self.tally.counts.clone();
+        if !self.fits(node, parent, &mut counts) {
+            return false;
+        }
+
+        match depth {
+            Some(depth) if self.constraints.min_xy_leaves > 0 => {
+                let reachable = self.tally.xy_leaves
+                    .saturating_add(self.tally.capacity)
+                    .saturating_add(self.alternate_max_xy(node, depth));
+                reachable >= self.constraints.min_xy_leaves
+            }
+            _ => true,
+        }
+    }
+
+    fn fits(&self, node: &Node, parent: Option<&'static str>, counts: &mut BTreeMap<&'static str, usize>) -> bool {
+        if let Node::Rule(_) = node {
+            return true;
+        }
+        let name = node.name();
+        if let Some(parent) = parent {
+            if self.constraints.forbidden.iter().any(|(p, c)| p == parent && c == name) {
+                return false;
+            }
+        }
+        let count = counts.entry(name).or_default();
+        *count += 1;
+        if self.constraints.max_counts.get(name).is_some_and(|&max| *count > max) {
+            return false;
+        }
+        node.children().into_iter().all(|child| self.fits(child, Some(name), counts))
+    }
+
+    /// Account for `node` having been picked at `depth`.
+    pub(super) fn record(&mut self, node: &Node, depth: Option<u32>) {
+        if self.constraints.is_empty() {
+            return;
+        }
+        count_nodes(node, &mut self.tally.counts);
+        self.tally.xy_leaves += direct_xy(node);
+        if let (Some(depth), true) = (depth, self.constraints.min_xy_leaves > 0) {
+            let mut refs = Vec::new();
+            validate::rule_refs(node, &mut refs);
+            for r in refs {
+                let max = self.max_xy(r, inner_depth(depth));
+                self.tally.capacity = self.tally.capacity.saturating_add(max);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::{generate_tree_budget, generate_tree_parallel, NodeBudget};
+
+    const SOURCE: &str = "
+        @forbid Sin Sin
+        @forbid Exp Exp
+        @max Exp 1
+        @min_xy 4
+        E ::= Triple(C, C, C)
+        C ::= A | Add(C, C) | Mult(C, C) | Sin(C) [3] | Cos(C) [3]
+            | Exp(C) | Sqrt(C) | Div(C, C) | MixUnbounded(C, C, C, C)
+        A ::= X | Y | Random
+    ";
+
+    fn has_pair(node: &Node, parent: &str, child: &str) -> bool {
+        node.children()
+            .into_iter()
+            .any(|c| (node.name() == parent && c.name() == child) || has_pair(c, parent, child))
+    }
+
+    fn count(node: &Node, name: &str) -> usize {
+        (node.name() == name) as usize + node.children().into_iter().map(|c| count(c, name)).sum::<usize>()
+    }
+
+    fn check(channel: &Node, min_xy: bool) {
+        assert!(!has_pair(channel, "Sin", "Sin"));
+        assert!(!has_pair(channel, "Exp", "Exp"));
+        assert!(count(channel, "Exp") <= 1);
+        if min_xy {
+            assert!(count(channel, "X") + count(channel, "Y") >= 4);
+        }
+    }
+
+    #[test]
+    fn trees_respect_constraints() {
+        let grammar = Grammar::parse(SOURCE, 0).unwrap();
+        for seed in 0..200 {
+            for depth in [4, 8, 14] {
+                let tree = generate_tree_parallel(&grammar, seed, depth).unwrap();
+                for channel in tree.children() {
+                    check(channel, true);
+                }
+            }
+            let tree = generate_tree_budget(&grammar, seed, NodeBudget::target(300)).unwrap();
+            for channel in tree.children() {
+                check(channel, false);
+            }
+        }
+    }
+
+    #[test]
+    fn loose_constraints_do_not_change_trees() {
+        let plain = Grammar::default(0);
+        let source = format!("@max Sqrt 100000\n{plain}");
+        let loose = Grammar::parse(&source, 0).unwrap();
+        for seed in 0..20 {
+            assert_eq!(generate_tree_parallel(&plain, seed, 12), generate_tree_parallel(&loose, seed, 12));
+        }
+    }
+}
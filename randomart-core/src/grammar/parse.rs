@@ -0,0 +1,654 @@
+//! The text grammar format read by [`Grammar::parse`](super::Grammar::parse)
+//! and written by its `Display` impl.
+
+use super::{constraints, Constraints, GrammarBranches, GrammarBuilder, Weight};
+use crate::node::Node;
+use crate::noise::Permutation;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+impl Pos {
+    fn error(self, message: impl Into<String>) -> ParseError {
+        ParseError { line: self.line, column: self.column, message: message.into() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f32),
+    Define,
+    Bar,
+    LParen,
+    RParen,
+    Comma,
+    LBracket,
+    RBracket,
+    Arrow,
+    At,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "`{name}`"),
+            Token::Number(v) => write!(f, "`{v}`"),
+            Token::Define => f.write_str("`::=`"),
+            Token::Bar => f.write_str("`|`"),
+            Token::LParen => f.write_str("`(`"),
+            Token::RParen => f.write_str("`)`"),
+            Token::Comma => f.write_str("`,`"),
+            Token::LBracket => f.write_str("`[`"),
+            Token::RBracket => f.write_str("`]`"),
+            Token::Arrow => f.write_str("`->`"),
+            Token::At => f.write_str("`@`"),
+            Token::Eof => f.write_str("end of file"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Pos)>, ParseError> {
+    let mut tokens = Vec::new();
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let pos = Pos { line: line_idx + 1, column: i + 1 };
+            let c = chars[i];
+
+            if c.is_whitespace() {
+                i += 1;
+                continue;
+            }
+            if c == '#' {
+                break;
+            }
+
+            let token = match c {
+                '|' => Token::Bar,
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                ',' => Token::Comma,
+                '[' => Token::LBracket,
+                ']' => Token::RBracket,
+                '@' => Token::At,
+                '-' if chars.get(i + 1) == Some(&'>') => {
+                    i += 1;
+                    Token::Arrow
+                }
+                ':' => {
+                    if chars.get(i + 1) != Some(&':') || chars.get(i + 2) != Some(&'=') {
+                        return Err(pos.error("expected `::=`"));
+                    }
+                    i += 2;
+                    Token::Define
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let start = i;
+                    while i + 1 < chars.len() && (chars[i + 1].is_ascii_alphanumeric() || chars[i + 1] == '_') {
+                        i += 1;
+                    }
+                    Token::Ident(chars[start..=i].iter().collect())
+                }
+                c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                    let start = i;
+                    // A sign inside a number only follows an exponent, so `4->1` splits at the arrow.
+                    while i + 1 < chars.len()
+                        && (chars[i + 1].is_ascii_alphanumeric()
+                            || chars[i + 1] == '.'
+                            || (matches!(chars[i + 1], '-' | '+') && matches!(chars[i], 'e' | 'E')))
+                    {
+                        i += 1;
+                    }
+                    let text: String = chars[start..=i].iter().collect();
+                    let value: f32 = text
+                        .parse()
+                        .map_err(|_| pos.error(format!("invalid number `{text}`")))?;
+                    Token::Number(value)
+                }
+                c => return Err(pos.error(format!("unexpected character `{c}`"))),
+            };
+
+            tokens.push((token, pos));
+            i += 1;
+        }
+    }
+
+    let eof = Pos {
+        line: source.lines().count().max(1),
+        column: source.lines().last().map_or(0, |l| l.chars().count()) + 1,
+    };
+    tokens.push((Token::Eof, eof));
+    Ok(tokens)
+}
+
+/// Number of children a built-in `Node` variant takes, or `None` if `name` is
+/// not a variant the text format can spell.
+pub(super) fn builtin_arity(name: &str) -> Option<usize> {
+    match name {
+        "X" | "Y" | "Z" | "T" | "R" | "Theta" | "Random" | "Var" => Some(0),
+        "Sqrt" | "Sin" | "Cos" | "Exp" | "Abs" | "Tan" | "Log" | "Iterate" | "Escape" | "Quantize" | "Mono" => Some(1),
+        "Add" | "Mult" | "Div" | "Min" | "Max" | "Mod" | "Atan2" | "Pow" | "Step" | "Noise"
+        | "Rotate" | "Scale" | "Let" | "Xor" | "And" | "Texture" => Some(2),
+        "Triple" | "IfPositive" | "Smoothstep" | "Warp" | "Circle" | "SmoothUnion" | "SmoothIntersect" => Some(3),
+        "MixUnbounded" | "BoxSdf" | "Line" | "Quad" => Some(4),
+        _ => None,
+    }
+}
+
+fn builtin(name: &str, mut args: Vec<Node>) -> Node {
+    let mut next = || Box::new(args.remove(0));
+    match name {
+        "X" => Node::X,
+        "Y" => Node::Y,
+        "Z" => Node::Z,
+        "T" => Node::T,
+        "R" => Node::R,
+        "Theta" => Node::Theta,
+        "Random" => Node::Random,
+        // Generation picks the binding a `Var` refers to and numbers each `Let`.
+        "Var" => Node::Var(0),
+        "Sqrt" => Node::Sqrt(next()),
+        "Sin" => Node::Sin(next()),
+        "Cos" => Node::Cos(next()),
+        "Exp" => Node::Exp(next()),
+        "Abs" => Node::Abs(next()),
+        "Tan" => Node::Tan(next()),
+        "Log" => Node::Log(next()),
+        // Generation draws the number of passes.
+        "Iterate" => Node::Iterate(next(), 0),
+        "Escape" => Node::Escape(next(), 0),
+        // And the number of levels.
+        "Quantize" => Node::Quantize(next(), 0),
+        "Add" => Node::Add(next(), next()),
+        "Mult" => Node::Mult(next(), next()),
+        "Div" => Node::Div(next(), next()),
+        "Min" => Node::Min(next(), next()),
+        "Max" => Node::Max(next(), next()),
+        "Mod" => Node::Mod(next(), next()),
+        "Atan2" => Node::Atan2(next(), next()),
+        "Pow" => Node::Pow(next(), next()),
+        "Xor" => Node::Xor(next(), next()),
+        "And" => Node::And(next(), next()),
+        "Step" => Node::Step(next(), next()),
+        "Noise" => Node::Noise(next(), next(), Permutation::identity()),
+        "Rotate" => Node::Rotate(next(), next()),
+        "Scale" => Node::Scale(next(), next()),
+        "Let" => Node::Let(0, next(), next()),
+        "Triple" => Node::Triple(next(), next(), next()),
+        "Mono" => Node::Mono(next()),
+        "Quad" => Node::Quad(next(), next(), next(), next()),
+        "IfPositive" => Node::IfPositive(next(), next(), next()),
+        "Smoothstep" => Node::Smoothstep(next(), next(), next()),
+        "Warp" => Node::Warp(next(), next(), next()),
+        "Circle" => Node::Circle(next(), next(), next()),
+        "SmoothUnion" => Node::SmoothUnion(next(), next(), next()),
+        "SmoothIntersect" => Node::SmoothIntersect(next(), next(), next()),
+        "MixUnbounded" => Node::MixUnbounded(next(), next(), next(), next()),
+        "BoxSdf" => Node::BoxSdf(next(), next(), next(), next()),
+        "Line" => Node::Line(next(), next(), next(), next()),
+        "Texture" => unreachable!("`Texture` names its slot and is parsed by `Parser::texture`"),
+        _ => unreachable!("builtin_arity and builtin disagree on `{name}`"),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, Pos)>,
+    cursor: usize,
+    builder: GrammarBuilder,
+    /// Names of the rules defined so far.
+    defined: Vec<String>,
+    /// Every rule name with where it was first referenced, for undefined-rule errors.
+    first_use: Vec<(String, Pos)>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.cursor].0
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let idx = (self.cursor + offset).min(self.tokens.len() - 1);
+        &self.tokens[idx].0
+    }
+
+    fn pos(&self) -> Pos {
+        self.tokens[self.cursor].1
+    }
+
+    fn bump(&mut self) -> (Token, Pos) {
+        let tok = self.tokens[self.cursor].clone();
+        if self.cursor + 1 < self.tokens.len() {
+            self.cursor += 1;
+        }
+        tok
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        let (tok, pos) = self.bump();
+        if tok == expected {
+            Ok(())
+        } else {
+            Err(pos.error(format!("expected {expected}, found {tok}")))
+        }
+    }
+
+    fn reference(&mut self, name: &str, pos: Pos) -> Node {
+        if !self.first_use.iter().any(|(n, _)| n == name) {
+            self.first_use.push((name.to_string(), pos));
+        }
+        self.builder.rule(name)
+    }
+
+    /// A constraint line: `@forbid Parent Child`, `@max Node N` or `@min_xy N`.
+    fn directive(&mut self) -> Result<(), ParseError> {
+        self.expect(Token::At)?;
+        let (tok, pos) = self.bump();
+        let Token::Ident(directive) = tok else {
+            return Err(pos.error(format!("expected a constraint after `@`, found {tok}")));
+        };
+        match directive.as_str() {
+            "forbid" => {
+                let parent = self.node_name()?;
+                let child = self.node_name()?;
+                self.builder.forbid(&parent, &child);
+            }
+            "max" => {
+                let node = self.node_name()?;
+                let max = self.count()?;
+                self.builder.max_count(&node, max);
+            }
+            "min_xy" => {
+                let min = self.count()?;
+                self.builder.min_xy_leaves(min);
+            }
+            _ => return Err(pos.error(format!("unknown constraint `@{directive}`"))),
+        }
+        Ok(())
+    }
+
+    fn node_name(&mut self) -> Result<String, ParseError> {
+        match self.bump() {
+            (Token::Ident(name), _) if constraints::is_node_name(&name) => Ok(name),
+            (Token::Ident(name), pos) => Err(pos.error(format!("`{name}` is not a built-in node"))),
+            (tok, pos) => Err(pos.error(format!("expected a node name, found {tok}"))),
+        }
+    }
+
+    fn count(&mut self) -> Result<usize, ParseError> {
+        match self.bump() {
+            (Token::Number(n), _) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            (tok, pos) => Err(pos.error(format!("expected a whole number, found {tok}"))),
+        }
+    }
+
+    fn rule(&mut self) -> Result<(), ParseError> {
+        let (tok, pos) = self.bump();
+        let Token::Ident(name) = tok else {
+            return Err(pos.error(format!("expected a rule name, found {tok}")));
+        };
+        if builtin_arity(&name).is_some() {
+            return Err(pos.error(format!("`{name}` is a built-in node and cannot name a rule")));
+        }
+        self.expect(Token::Define)?;
+
+        if self.defined.contains(&name) {
+            return Err(pos.error(format!("rule `{name}` is defined more than once")));
+        }
+        self.defined.push(name.clone());
+
+        let mut total_weight = 0.0;
+        loop {
+            let node = self.expr()?;
+            let weight = self.weight()?;
+            total_weight += weight.peak();
+            self.builder.alternate(&name, node, weight);
+            if *self.peek() != Token::Bar {
+                break;
+            }
+            self.bump();
+        }
+
+        if total_weight <= 0.0 {
+            return Err(pos.error(format!("the weights of rule `{name}` sum to zero")));
+        }
+        Ok(())
+    }
+
+    /// An optional `[w]` or `[far -> near @ span]` after an alternate.
+    fn weight(&mut self) -> Result<Weight, ParseError> {
+        if *self.peek() != Token::LBracket {
+            return Ok(Weight::Fixed(1.0));
+        }
+        self.bump();
+        let far = self.weight_value()?;
+        if *self.peek() != Token::Arrow {
+            self.expect(Token::RBracket)?;
+            return Ok(Weight::Fixed(far));
+        }
+        self.bump();
+        let near = self.weight_value()?;
+        self.expect(Token::At)?;
+        let (tok, pos) = self.bump();
+        let span = match tok {
+            Token::Number(s) if s >= 1.0 && s.fract() == 0.0 && s <= u32::MAX as f32 => s as u32,
+            Token::Number(s) => return Err(pos.error(format!("span must be a positive whole number, found {s}"))),
+            tok => return Err(pos.error(format!("expected a span, found {tok}"))),
+        };
+        self.expect(Token::RBracket)?;
+        Ok(Weight::Ramp { far, near, span })
+    }
+
+    fn weight_value(&mut self) -> Result<f32, ParseError> {
+        let (tok, pos) = self.bump();
+        match tok {
+            Token::Number(w) if w.is_finite() && w >= 0.0 => Ok(w),
+            Token::Number(w) => Err(pos.error(format!("weight must be finite and non-negative, found {w}"))),
+            tok => Err(pos.error(format!("expected a weight, found {tok}"))),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Node, ParseError> {
+        let (tok, pos) = self.bump();
+        match tok {
+            Token::Number(v) => Ok(Node::Number(v)),
+            Token::Ident(name) if name == "Texture" => self.texture(),
+            Token::Ident(name) => match builtin_arity(&name) {
+                Some(arity) => {
+                    let args = self.args()?;
+                    if args.len() != arity {
+                        return Err(pos.error(format!("`{name}` takes {arity} argument(s), found {}", args.len())));
+                    }
+                    Ok(builtin(&name, args))
+                }
+                None => {
+                    if *self.peek() == Token::LParen {
+                        return Err(pos.error(format!("unknown node `{name}`")));
+                    }
+                    Ok(self.reference(&name, pos))
+                }
+            },
+            tok => Err(pos.error(format!("expected an alternate, found {tok}"))),
+        }
+    }
+
+    /// The arguments of `Texture(slot, x, y)`, whose slot names an input image
+    /// rather than an alternate.
+    fn texture(&mut self) -> Result<Node, ParseError> {
+        self.expect(Token::LParen)?;
+        let (tok, pos) = self.bump();
+        let Token::Ident(slot) = tok else {
+            return Err(pos.error(format!("expected a texture slot name, found {tok}")));
+        };
+        self.expect(Token::Comma)?;
+        let x = self.expr()?;
+        self.expect(Token::Comma)?;
+        let y = self.expr()?;
+        self.expect(Token::RParen)?;
+        Ok(Node::Texture(slot, Box::new(x), Box::new(y)))
+    }
+
+    fn args(&mut self) -> Result<Vec<Node>, ParseError> {
+        let mut args = Vec::new();
+        if *self.peek() != Token::LParen {
+            return Ok(args);
+        }
+        self.bump();
+        if *self.peek() == Token::RParen {
+            self.bump();
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            let (tok, pos) = self.bump();
+            match tok {
+                Token::Comma => continue,
+                Token::RParen => return Ok(args),
+                tok => return Err(pos.error(format!("expected `,` or `)`, found {tok}"))),
+            }
+        }
+    }
+}
+
+/// Parse `source` into a builder whose start rule is the first rule defined.
+pub(super) fn parse(source: &str) -> Result<GrammarBuilder, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        cursor: 0,
+        builder: GrammarBuilder::new(),
+        defined: Vec::new(),
+        first_use: Vec::new(),
+    };
+
+    while *parser.peek() != Token::Eof {
+        if *parser.peek() == Token::At {
+            parser.directive()?;
+            continue;
+        }
+        if !matches!(parser.peek(), Token::Ident(_)) || *parser.peek_at(1) != Token::Define {
+            let (tok, pos) = parser.bump();
+            return Err(pos.error(format!("expected `Name ::=` to start a rule, found {tok}")));
+        }
+        parser.rule()?;
+    }
+    if parser.defined.is_empty() {
+        return Err(parser.pos().error("grammar has no rules"));
+    }
+
+    if let Some((name, pos)) = parser.first_use.iter().find(|(name, _)| !parser.defined.contains(name)) {
+        return Err(pos.error(format!("rule `{name}` is never defined")));
+    }
+
+    let grammar = parser.builder.build(0).expect("every referenced rule is defined");
+    if grammar.start_root().is_none() {
+        return Err(parser.tokens[0].1.error("the start rule must be a single `Triple(...)`, `Mono(...)` or `Quad(...)` alternate"));
+    }
+
+    Ok(parser.builder)
+}
+
+fn write_node(f: &mut fmt::Formatter<'_>, node: &Node, rules: &[GrammarBranches]) -> fmt::Result {
+    let (name, args): (&str, Vec<&Node>) = match node {
+        Node::X => ("X", vec![]),
+        Node::Y => ("Y", vec![]),
+        Node::Z => ("Z", vec![]),
+        Node::T => ("T", vec![]),
+        Node::R => ("R", vec![]),
+        Node::Theta => ("Theta", vec![]),
+        Node::Random => ("Random", vec![]),
+        Node::Var(_) => ("Var", vec![]),
+        Node::Number(v) => return write!(f, "{v}"),
+        Node::Rule(idx) => return f.write_str(&rules[*idx].name),
+        Node::Sqrt(a) => ("Sqrt", vec![a]),
+        Node::Sin(a) => ("Sin", vec![a]),
+        Node::Cos(a) => ("Cos", vec![a]),
+        Node::Exp(a) => ("Exp", vec![a]),
+        Node::Abs(a) => ("Abs", vec![a]),
+        Node::Tan(a) => ("Tan", vec![a]),
+        Node::Log(a) => ("Log", vec![a]),
+        Node::Iterate(a, _) => ("Iterate", vec![a]),
+        Node::Escape(a, _) => ("Escape", vec![a]),
+        Node::Quantize(a, _) => ("Quantize", vec![a]),
+        Node::Add(a, b) => ("Add", vec![a, b]),
+        Node::Mult(a, b) => ("Mult", vec![a, b]),
+        Node::Div(a, b) => ("Div", vec![a, b]),
+        Node::Min(a, b) => ("Min", vec![a, b]),
+        Node::Max(a, b) => ("Max", vec![a, b]),
+        Node::Mod(a, b) => ("Mod", vec![a, b]),
+        Node::Atan2(a, b) => ("Atan2", vec![a, b]),
+        Node::Pow(a, b) => ("Pow", vec![a, b]),
+        Node::Xor(a, b) => ("Xor", vec![a, b]),
+        Node::And(a, b) => ("And", vec![a, b]),
+        Node::Step(a, b) => ("Step", vec![a, b]),
+        Node::Noise(a, b, _) => ("Noise", vec![a, b]),
+        Node::Texture(slot, a, b) => {
+            write!(f, "Texture({slot}, ")?;
+            write_node(f, a, rules)?;
+            f.write_str(", ")?;
+            write_node(f, b, rules)?;
+            return f.write_str(")");
+        }
+        Node::Rotate(a, b) => ("Rotate", vec![a, b]),
+        Node::Scale(a, b) => ("Scale", vec![a, b]),
+        Node::Let(_, a, b) => ("Let", vec![a, b]),
+        Node::Triple(a, b, c) => ("Triple", vec![a, b, c]),
+        Node::Mono(a) => ("Mono", vec![a]),
+        Node::Quad(a, b, c, d) => ("Quad", vec![a, b, c, d]),
+        Node::IfPositive(a, b, c) => ("IfPositive", vec![a, b, c]),
+        Node::Smoothstep(a, b, c) => ("Smoothstep", vec![a, b, c]),
+        Node::Warp(a, b, c) => ("Warp", vec![a, b, c]),
+        Node::Circle(a, b, c) => ("Circle", vec![a, b, c]),
+        Node::SmoothUnion(a, b, c) => ("SmoothUnion", vec![a, b, c]),
+        Node::SmoothIntersect(a, b, c) => ("SmoothIntersect", vec![a, b, c]),
+        Node::MixUnbounded(a, b, c, d) => ("MixUnbounded", vec![a, b, c, d]),
+        Node::BoxSdf(a, b, c, d) => ("BoxSdf", vec![a, b, c, d]),
+        Node::Line(a, b, c, d) => ("Line", vec![a, b, c, d]),
+    };
+
+    f.write_str(name)?;
+    if args.is_empty() {
+        return Ok(());
+    }
+    f.write_str("(")?;
+    for (i, arg) in args.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write_node(f, arg, rules)?;
+    }
+    f.write_str(")")
+}
+
+/// Write `rules` in the same format [`parse`] reads, after any constraints and
+/// with the start rule first.
+pub(super) fn write_rules(
+    f: &mut fmt::Formatter<'_>,
+    rules: &[GrammarBranches],
+    start: usize,
+    constraints: &Constraints,
+) -> fmt::Result {
+    for (parent, child) in &constraints.forbidden {
+        writeln!(f, "@forbid {parent} {child}")?;
+    }
+    for (node, max) in &constraints.max_counts {
+        writeln!(f, "@max {node} {max}")?;
+    }
+    if constraints.min_xy_leaves > 0 {
+        writeln!(f, "@min_xy {}", constraints.min_xy_leaves)?;
+    }
+    let order = std::iter::once(start).chain((0..rules.len()).filter(|&idx| idx != start));
+    for rule in order.map(|idx| &rules[idx]) {
+        let indent = " ".repeat(rule.name.len() + 1);
+        write!(f, "{} ::= ", rule.name)?;
+        for (i, branch) in rule.alternates.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n{indent}  | ")?;
+            }
+            write_node(f, &branch.node, rules)?;
+            match branch.weight {
+                Weight::Fixed(1.0) => {}
+                Weight::Fixed(w) => write!(f, " [{w}]")?,
+                Weight::Ramp { far, near, span } => write!(f, " [{far} -> {near} @ {span}]")?,
+            }
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{generate_tree_parallel, Grammar};
+
+    const DEFAULT_SOURCE: &str = "
+        # the built-in grammar, spelled out
+        E ::= Triple(C, C, C)
+        C ::= A | Add(C, C) | Mult(C, C) | Sin(C) [3] | Cos(C) [3]
+            | Exp(C) | Sqrt(C) | Div(C, C) | MixUnbounded(C, C, C, C)
+        A ::= X | Y | Random
+    ";
+
+    #[test]
+    fn parsed_default_matches_builtin() {
+        let parsed = Grammar::parse(DEFAULT_SOURCE, 0).unwrap();
+        let builtin = Grammar::default(0);
+        for seed in [1, 2, 3] {
+            assert_eq!(
+                generate_tree_parallel(&parsed, seed, 9),
+                generate_tree_parallel(&builtin, seed, 9),
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let printed = Grammar::default(0).to_string();
+        let reparsed = Grammar::parse(&printed, 0).unwrap();
+        assert_eq!(printed, reparsed.to_string());
+    }
+
+    #[test]
+    fn parses_ramped_weights() {
+        let source = "E ::= Triple(C, C, C)\nC ::= Sin(C) [4 -> 0 @ 3]\n  | X [0.5->8@3]\n";
+        let grammar = Grammar::parse(source, 0).unwrap();
+        let printed = grammar.to_string();
+        assert!(printed.contains("Sin(C) [4 -> 0 @ 3]"), "{printed}");
+        assert!(printed.contains("X [0.5 -> 8 @ 3]"), "{printed}");
+        assert_eq!(Grammar::parse(&printed, 0).unwrap().to_string(), printed);
+
+        let err = Grammar::parse("E ::= Triple(X, X, X) [1 -> 2 @ 0.5]\n", 0).err().unwrap();
+        assert_eq!((err.line, err.column), (1, 33));
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        let err = Grammar::parse("E ::= Triple(C, C, C)\nC ::= Sin(C, C)\n", 0).err().unwrap();
+        assert_eq!((err.line, err.column), (2, 7));
+
+        let err = Grammar::parse("E ::= Triple(C, C, C)\nC ::= Sin(D)\n", 0).err().unwrap();
+        assert_eq!((err.line, err.column), (2, 11));
+        assert!(err.message.contains("`D`"), "{}", err.message);
+    }
+
+    #[test]
+    fn parses_texture_slots() {
+        let source = "E ::= Triple(C, C, C)\nC ::= Texture(photo, C, Sin(Y)) | X\n";
+        let grammar = Grammar::parse(source, 0).unwrap();
+        let printed = grammar.to_string();
+        assert!(printed.contains("Texture(photo, C, Sin(Y))"), "{printed}");
+        assert_eq!(Grammar::parse(&printed, 0).unwrap().to_string(), printed);
+
+        // The slot is a name, not an alternate.
+        let err = Grammar::parse("E ::= Triple(X, X, Texture(0.5, X, Y))\n", 0).err().unwrap();
+        assert_eq!((err.line, err.column), (1, 28));
+    }
+
+    #[test]
+    fn rejects_non_triple_start() {
+        let err = Grammar::parse("C ::= Sin(X)\n", 0).err().unwrap();
+        assert_eq!((err.line, err.column), (1, 1));
+    }
+}
@@ -0,0 +1,209 @@
+//! Predicting tree size from the grammar alone, treating generation as a
+//! branching process over the rules' weights.
+
+use super::{validate, GenerateError, Grammar};
+use crate::node::Node;
+use std::collections::BTreeMap;
+
+/// What [`generate_tree_parallel`](super::generate_tree_parallel) produces on
+/// average for a given depth, over all seeds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExpectedStats {
+    /// Expected number of nodes, before simplification.
+    pub nodes: f64,
+    /// Expected number of nodes of each variant, keyed by variant name.
+    pub op_counts: BTreeMap<&'static str, f64>,
+    /// Probability that some rule runs out of depth and has to be finished
+    /// along its shortest route instead of by its weights.
+    pub truncation_probability: f64,
+}
+
+impl ExpectedStats {
+    /// Rough heap footprint of the expected tree, one boxed `Node` per node.
+    pub fn tree_bytes(&self) -> f64 {
+        self.nodes * std::mem::size_of::<Node>() as f64
+    }
+}
+
+/// Expectations for one subtree; `complete` is the probability it was grown
+/// without truncation.
+#[derive(Clone)]
+struct Moments {
+    nodes: f64,
+    op_counts: BTreeMap<&'static str, f64>,
+    complete: f64,
+}
+
+impl Moments {
+    fn empty() -> Self {
+        Self { nodes: 0.0, op_counts: BTreeMap::new(), complete: 1.0 }
+    }
+
+    /// Account for an independent subtree alongside this one.
+    fn join(&mut self, other: &Moments) {
+        self.nodes += other.nodes;
+        for (name, count) in &other.op_counts {
+            *self.op_counts.entry(name).or_default() += count;
+        }
+        self.complete *= other.complete;
+    }
+
+    /// Account for `other` happening with probability `p`, as one outcome of a choice.
+    fn mix(&mut self, other: &Moments, p: f64) {
+        self.nodes += p * other.nodes;
+        for (name, count) in &other.op_counts {
+            *self.op_counts.entry(name).or_default() += p * count;
+        }
+        self.complete += p * other.complete;
+    }
+}
+
+/// The name of the variant `node` becomes in a generated tree.
+fn variant_name(node: &Node) -> &'static str {
+    match node {
+        Node::Random => "Number",
+        node => node.name(),
+    }
+}
+
+/// Expectations for `gen_node(node, depth)`, given `levels[d][rule]` for
+/// `gen_rule(rule, d)` at every depth below `depth` (and at 0 for rules the
+/// fallback can reach).
+fn node_moments(node: &Node, depth: u32, levels: &[Vec<Option<Moments>>]) -> Moments {
+    if let Node::Rule(rule) = node {
+        return levels[depth.saturating_sub(1) as usize][*rule].clone().expect("computed before use");
+    }
+    let mut moments = Moments::empty();
+    moments.nodes = 1.0;
+    moments.op_counts.insert(variant_name(node), 1.0);
+    for child in node.children() {
+        moments.join(&node_moments(child, depth, levels));
+    }
+    moments
+}
+
+impl Grammar {
+    /// Expected size and shape of a tree grown to `depth` from this grammar,
+    /// computed from the weights without generating anything. Mirrors
+    /// [`Grammar::gen_rule`] exactly, including the depth-zero fallback, but
+    /// leaves [`Constraints`](super::Constraints) out of account.
+    pub fn expected_stats(&self, depth: u32) -> Result<ExpectedStats, GenerateError> {
+        self.validate().map_err(GenerateError::InvalidGrammar)?;
+        let root = self.start_root().ok_or(GenerateError::StartNotRoot)?;
+
+        let n = self.rules.len();
+        let mut levels: Vec<Vec<Option<Moments>>> = vec![vec![None; n]];
+
+        // At depth zero each rule takes its shortest route, which only reaches
+        // rules of lower height, so those are filled in first.
+        let mut by_height: Vec<usize> = (0..n).collect();
+        by_height.sort_by_key(|&idx| self.heights[idx]);
+        for rule in by_height {
+            let height = self.heights[rule];
+            let shortest: Vec<_> = self.rules[rule]
+                .alternates
+                .iter()
+                .filter(|branch| branch.weight.peak() > 0.0)
+                .filter(|branch| validate::alternate_height(&branch.node, &self.heights) == height)
+                .collect();
+            let total: f32 = shortest.iter().map(|branch| branch.weight.at(0)).sum();
+
+            let mut moments = Moments { complete: 0.0, ..Moments::empty() };
+            for (i, branch) in shortest.iter().enumerate() {
+                let p = if total > 0.0 {
+                    branch.weight.at(0) / total
+                } else if i == 0 {
+                    // Every shortest route has zero weight; the first is taken.
+                    1.0
+                } else {
+                    0.0
+                };
+                moments.mix(&node_moments(&branch.node, 0, &levels), p as f64);
+            }
+            // Reaching this rule at all means the tree was cut short.
+            moments.complete = 0.0;
+            levels[0][rule] = Some(moments);
+        }
+
+        for d in 1..depth.max(1) {
+            let level = self
+                .rules
+                .iter()
+                .map(|rule| {
+                    let total = rule.total_weight(d);
+                    let mut moments = Moments { complete: 0.0, ..Moments::empty() };
+                    for branch in &rule.alternates {
+                        let p = branch.weight.at(d) / total;
+                        moments.mix(&node_moments(&branch.node, d - 1, &levels), p as f64);
+                    }
+                    Some(moments)
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        let moments = node_moments(root, depth, &levels);
+        Ok(ExpectedStats {
+            nodes: moments.nodes,
+            op_counts: moments.op_counts,
+            truncation_probability: 1.0 - moments.complete,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::{generate_tree_parallel, GrammarBuilder, Weight};
+
+    fn count(node: &Node, counts: &mut BTreeMap<&'static str, f64>) -> f64 {
+        *counts.entry(variant_name(node)).or_default() += 1.0;
+        1.0 + node.children().into_iter().map(|child| count(child, counts)).sum::<f64>()
+    }
+
+    /// Average over many seeds should approach the prediction.
+    fn assert_matches_sampling(grammar: &Grammar, depth: u32) {
+        let expected = grammar.expected_stats(depth).unwrap();
+        let samples = 2000;
+        let mut counts = BTreeMap::new();
+        let mut nodes = 0.0;
+        for seed in 0..samples {
+            nodes += count(&generate_tree_parallel(grammar, seed, depth).unwrap(), &mut counts);
+        }
+        let mean = nodes / samples as f64;
+        assert!((mean - expected.nodes).abs() < 0.05 * expected.nodes, "mean {mean}, expected {}", expected.nodes);
+        for (name, total) in counts {
+            let mean = total / samples as f64;
+            let predicted = expected.op_counts[name];
+            assert!((mean - predicted).abs() < 0.1 * predicted + 0.05, "{name}: mean {mean}, expected {predicted}");
+        }
+    }
+
+    #[test]
+    fn default_grammar_matches_sampling() {
+        let grammar = Grammar::default(0);
+        assert_matches_sampling(&grammar, 6);
+
+        // At depth 1 every rule is cut short; deep trees almost always are.
+        assert_eq!(grammar.expected_stats(1).unwrap().truncation_probability, 1.0);
+        assert!(grammar.expected_stats(20).unwrap().nodes > grammar.expected_stats(10).unwrap().nodes);
+    }
+
+    #[test]
+    fn ramped_grammar_matches_sampling() {
+        let mut builder = GrammarBuilder::new();
+        let c = builder.rule("C");
+        builder
+            .alternate("E", Node::Triple(Box::new(c.clone()), Box::new(c.clone()), Box::new(c.clone())), 1.0)
+            .alternate("C", Node::Add(Box::new(c.clone()), Box::new(c.clone())), Weight::Ramp { far: 3.0, near: 0.5, span: 6 })
+            .alternate("C", Node::Sin(Box::new(c)), 1.0)
+            .alternate("C", Node::Random, 1.0);
+        let grammar = builder.build(0).unwrap();
+        assert_matches_sampling(&grammar, 9);
+
+        // With no depth left every channel falls back to its terminal.
+        let stats = grammar.expected_stats(1).unwrap();
+        assert_eq!(stats.nodes, 4.0);
+        assert_eq!(stats.op_counts["Number"], 3.0);
+    }
+}
@@ -0,0 +1,113 @@
+//! Built-in grammar presets, each giving images a different character.
+
+use super::Grammar;
+
+/// Names accepted by [`Grammar::style`]. `classic` is [`Grammar::default`].
+pub const STYLES: &[&str] = &["classic", "smooth", "geometric", "high-contrast", "organic", "shapes", "pixel", "mono"];
+
+/// Soft gradients and waves: trig-heavy, no `Div` or `Exp` to tear or saturate.
+const SMOOTH: &str = "
+E ::= Triple(C, C, C)
+C ::= A [2] | Add(C, C) [2] | Mult(C, C) | Sin(C) [4] | Cos(C) [4]
+    | MixUnbounded(C, C, C, C) [0.5]
+A ::= X | Y | Random
+";
+
+/// Hard edges and planar shapes from arithmetic on the coordinates.
+const GEOMETRIC: &str = "
+@min_xy 2
+E ::= Triple(C, C, C)
+C ::= A [2] | Add(C, C) [3] | Mult(C, C) [3] | Div(C, C) [2] | Sqrt(C) [2] | Cos(C) [0.5]
+A ::= X [2] | Y [2] | Random
+";
+
+/// Saturated colour and sharp transitions from `Exp` and `Div`.
+const HIGH_CONTRAST: &str = "
+@forbid Exp Exp
+E ::= Triple(C, C, C)
+C ::= A | Mult(C, C) [2] | Exp(C) [3] | Div(C, C) [3] | Sin(C) [2]
+    | MixUnbounded(C, C, C, C) [2]
+A ::= X | Y | Random
+";
+
+/// Flowing, blended forms: nested trig mixed together, winding down gently.
+const ORGANIC: &str = "
+@forbid Sin Sin
+@forbid Cos Cos
+E ::= Triple(C, C, C)
+C ::= A [1 -> 4 @ 6] | Add(C, C) [2] | Mult(C, C) | Sin(C) [3] | Cos(C) [3]
+    | MixUnbounded(C, C, C, C) [2] | Sqrt(C)
+A ::= X | Y | Random
+";
+
+/// Crisp circles, boxes and strokes at random places, cut into waves and blended.
+const SHAPES: &str = "
+E ::= Triple(C, C, C)
+C ::= A [2] | Add(C, C) [2] | Mult(C, C) | Sin(C) [2] | Cos(C) [2]
+    | Step(0, S) [2] | Mult(C, Step(0, S)) [2] | Smoothstep(-0.1, 0.1, S)
+S ::= Circle(N, N, N) [2] | BoxSdf(N, N, N, N) [2] | Line(N, N, N, N)
+    | SmoothUnion(S, S, N) | SmoothIntersect(S, S, N) [0.5] | Warp(Sin(C), Cos(C), S) [0.5]
+N ::= Random
+A ::= X | Y | Random
+";
+
+/// Flat blocks and munching-square patterns from bitwise operations on the
+/// coordinates.
+const PIXEL: &str = "
+E ::= Triple(C, C, C)
+C ::= B [3] | Add(C, C) | Mult(C, C) [0.5] | Sin(C) | Quantize(C) [2]
+B ::= Xor(G, G) [2] | And(G, G) | Xor(B, G) [0.5] | And(B, G) [0.5]
+G ::= A [2] | Mult(A, N) | Quantize(A) | Sin(Mult(A, N))
+N ::= Random
+A ::= X | Y
+";
+
+/// A single channel of waves and noise, coloured by the colormap the image is
+/// rendered with.
+const MONO: &str = "
+E ::= Mono(C)
+C ::= A [2] | Add(C, C) [2] | Mult(C, C) | Sin(C) [3] | Cos(C) [3] | Noise(C, C) [0.5]
+A ::= X | Y | Random
+";
+
+impl Grammar {
+    /// The built-in preset called `name`, one of [`STYLES`].
+    pub fn style(name: &str, seed: u64) -> Option<Self> {
+        let source = match name {
+            "classic" => return Some(Self::default(seed)),
+            "smooth" => SMOOTH,
+            "geometric" => GEOMETRIC,
+            "high-contrast" => HIGH_CONTRAST,
+            "organic" => ORGANIC,
+            "shapes" => SHAPES,
+            "pixel" => PIXEL,
+            "mono" => MONO,
+            _ => return None,
+        };
+        Some(Self::parse(source, seed).expect("built-in styles are valid grammars"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::generate_tree_parallel;
+
+    #[test]
+    fn every_style_generates() {
+        for &name in STYLES {
+            let grammar = Grammar::style(name, 0).unwrap();
+            assert_eq!(grammar.validate(), Ok(()), "{name}");
+            assert!(generate_tree_parallel(&grammar, 1, 12).is_ok(), "{name}");
+        }
+        assert!(Grammar::style("baroque", 0).is_none());
+    }
+
+    #[test]
+    fn styles_differ() {
+        let printed: Vec<String> = STYLES.iter().map(|name| Grammar::style(name, 0).unwrap().to_string()).collect();
+        for (i, a) in printed.iter().enumerate() {
+            assert!(printed[i + 1..].iter().all(|b| a != b), "{}", STYLES[i]);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use super::{Grammar, GrammarBranches, Weight};
+use crate::node::Node;
+use std::fmt;
+
+/// A problem found by [`Grammar::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrammarError {
+    /// An alternate's weight is negative or not finite at some depth.
+    BadWeight { rule: String, weight: f32 },
+    /// Every alternate of the rule weighs zero at this depth, so none can be picked.
+    ZeroWeights { rule: String, depth: u32 },
+    /// The alternates' weights add up to more than an `f32` holds at this
+    /// depth, so none can be picked either.
+    OverflowingWeights { rule: String, depth: u32 },
+    /// No chain of alternates leads from the start rule to this rule.
+    Unreachable { rule: String },
+    /// Every alternate of this rule recurses into rules that never terminate.
+    NonTerminating { rule: String },
+    /// An alternate of `rule` contains a `Node::Rule` index with no rule behind it.
+    RuleOutOfRange { rule: String, index: usize },
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarError::BadWeight { rule, weight } => {
+                write!(f, "rule `{rule}` has an alternate weighing {weight}, expected a finite weight of at least 0")
+            }
+            GrammarError::ZeroWeights { rule, depth } => {
+                write!(f, "every alternate of rule `{rule}` weighs zero at depth {depth}")
+            }
+            GrammarError::OverflowingWeights { rule, depth } => {
+                write!(f, "the weights of rule `{rule}` add up to more than {} at depth {depth}", f32::MAX)
+            }
+            GrammarError::Unreachable { rule } => {
+                write!(f, "rule `{rule}` is unreachable from the start rule")
+            }
+            GrammarError::NonTerminating { rule } => {
+                write!(f, "rule `{rule}` has no alternative that terminates")
+            }
+            GrammarError::RuleOutOfRange { rule, index } => {
+                write!(f, "rule `{rule}` references rule index {index}, which does not exist")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+/// Every `Node::Rule` index mentioned anywhere in `node`.
+pub(super) fn rule_refs(node: &Node, out: &mut Vec<usize>) {
+    if let Node::Rule(idx) = node {
+        out.push(*idx);
+    }
+    for child in node.children() {
+        rule_refs(child, out);
+    }
+}
+
+/// The rule expansions an alternate needs before it is all terminals, given
+/// each rule's height: 0 for an alternate without rules, otherwise one more
+/// than its tallest rule. `None` if some rule in it never terminates.
+pub(super) fn alternate_height(node: &Node, heights: &[Option<u32>]) -> Option<u32> {
+    let mut refs = Vec::new();
+    rule_refs(node, &mut refs);
+    refs.iter()
+        .filter(|&&idx| idx < heights.len())
+        .try_fold(0, |tallest, &idx| Some(tallest.max(heights[idx]? + 1)))
+}
+
+/// For each rule, the height of its shortest alternate: how many nested rule
+/// expansions it takes at least to finish the rule, or `None` if it never can.
+/// Only alternates with a non-zero weight at some depth count.
+pub(super) fn termination_heights(rules: &[GrammarBranches]) -> Vec<Option<u32>> {
+    let mut heights: Vec<Option<u32>> = vec![None; rules.len()];
+    loop {
+        let mut changed = false;
+        for (idx, rule) in rules.iter().enumerate() {
+            let shortest = rule
+                .alternates
+                .iter()
+                .filter(|branch| branch.weight.peak() > 0.0)
+                .filter_map(|branch| alternate_height(&branch.node, &heights))
+                .min();
+            if shortest.is_some() && (heights[idx].is_none() || shortest < heights[idx]) {
+                heights[idx] = shortest;
+                changed = true;
+            }
+        }
+        if !changed {
+            return heights;
+        }
+    }
+}
+
+impl Grammar {
+    /// Check the grammar for problems that would make generation misbehave,
+    /// returning all of them rather than stopping at the first.
+    pub fn validate(&self) -> Result<(), Vec<GrammarError>> {
+        let mut errors = Vec::new();
+        let n = self.rules.len();
+
+        // Rule references per alternate, with out-of-range indices reported and dropped.
+        let refs: Vec<Vec<Vec<usize>>> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.alternates
+                    .iter()
+                    .map(|branch| {
+                        let mut out = Vec::new();
+                        rule_refs(&branch.node, &mut out);
+                        for &index in out.iter().filter(|&&idx| idx >= n) {
+                            errors.push(GrammarError::RuleOutOfRange { rule: rule.name.clone(), index });
+                        }
+                        out.retain(|&idx| idx < n);
+                        out
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for rule in &self.rules {
+            let bad = rule.alternates.iter().find_map(|branch| {
+                let (far, near) = match branch.weight {
+                    Weight::Fixed(w) => (w, w),
+                    Weight::Ramp { far, near, .. } => (far, near),
+                };
+                [far, near].into_iter().find(|w| !w.is_finite() || *w < 0.0)
+            });
+            if let Some(weight) = bad {
+                errors.push(GrammarError::BadWeight { rule: rule.name.clone(), weight });
+                continue;
+            }
+            // Ramped weights are checked at every depth where they still change.
+            let span = rule.alternates.iter().map(|branch| branch.weight.span()).max().unwrap_or(0);
+            for depth in 0..=span {
+                let total = rule.total_weight(depth);
+                let name = rule.name.clone();
+                if total <= 0.0 {
+                    errors.push(GrammarError::ZeroWeights { rule: name, depth });
+                    break;
+                } else if !total.is_finite() {
+                    errors.push(GrammarError::OverflowingWeights { rule: name, depth });
+                    break;
+                }
+            }
+        }
+
+        let mut reachable = vec![false; n];
+        let mut stack = vec![self.start];
+        while let Some(idx) = stack.pop() {
+            if std::mem::replace(&mut reachable[idx], true) {
+                continue;
+            }
+            stack.extend(refs[idx].iter().flatten().filter(|&&r| !reachable[r]));
+        }
+
+        for (idx, rule) in self.rules.iter().enumerate() {
+            if !reachable[idx] {
+                errors.push(GrammarError::Unreachable { rule: rule.name.clone() });
+            }
+            if self.heights[idx].is_none() {
+                errors.push(GrammarError::NonTerminating { rule: rule.name.clone() });
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::GrammarBuilder;
+
+    #[test]
+    fn default_grammar_is_valid() {
+        assert_eq!(Grammar::default(0).validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut builder = GrammarBuilder::new();
+        let loops = builder.rule("Loop");
+        builder
+            .alternate("E", Node::Triple(Box::new(loops.clone()), Box::new(Node::X), Box::new(Node::Rule(9))), 1.0)
+            .alternate("Loop", Node::Sin(Box::new(loops)), 1.0)
+            .alternate("Orphan", Node::Y, 0.0);
+        let errors = builder.build(0).unwrap().validate().unwrap_err();
+
+        assert!(errors.contains(&GrammarError::RuleOutOfRange { rule: "E".into(), index: 9 }));
+        assert!(errors.contains(&GrammarError::NonTerminating { rule: "Loop".into() }));
+        assert!(errors.contains(&GrammarError::NonTerminating { rule: "E".into() }));
+        assert!(errors.contains(&GrammarError::Unreachable { rule: "Orphan".into() }));
+        assert!(errors.contains(&GrammarError::ZeroWeights { rule: "Orphan".into(), depth: 0 }));
+    }
+
+    #[test]
+    fn rejects_negative_and_non_finite_weights() {
+        let mut builder = GrammarBuilder::new();
+        builder
+            .alternate("E", Node::Triple(Box::new(Node::X), Box::new(Node::Y), Box::new(Node::X)), 1.0)
+            .alternate("E", Node::Y, -0.5)
+            .alternate("C", Node::X, f32::INFINITY);
+        let errors = builder.build(0).unwrap().validate().unwrap_err();
+
+        assert!(errors.contains(&GrammarError::BadWeight { rule: "E".into(), weight: -0.5 }));
+        assert!(errors.contains(&GrammarError::BadWeight { rule: "C".into(), weight: f32::INFINITY }));
+
+        // Finite weights whose total is not.
+        let mut builder = GrammarBuilder::new();
+        builder
+            .alternate("E", Node::Triple(Box::new(Node::X), Box::new(Node::Y), Box::new(Node::X)), 3e38)
+            .alternate("E", Node::Triple(Box::new(Node::Y), Box::new(Node::X), Box::new(Node::Y)), 3e38);
+        let errors = builder.build(0).unwrap().validate().unwrap_err();
+        assert_eq!(errors, vec![GrammarError::OverflowingWeights { rule: "E".into(), depth: 0 }]);
+    }
+}
@@ -4,7 +4,13 @@ pub mod statistics;
 pub mod rng;
 pub mod pixel_buffer;
 pub mod math;
+pub mod noise;
+pub mod sdf;
+pub mod texture;
 pub mod render;
+pub mod colormap;
+pub mod morph;
+pub mod formula;
 
 /// Disable Flush-to-Zero (FTZ) and Denormals-Are-Zero (DAZ) in the MXCSR register.
 /// This ensures subnormal floats are handled correctly (IEEE 754 compliant).
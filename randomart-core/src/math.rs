@@ -2,6 +2,10 @@ extern "C" {
     fn cr_sinf(x: f32) -> f32;
     fn cr_cosf(x: f32) -> f32;
     fn cr_expf(x: f32) -> f32;
+    fn cr_tanf(x: f32) -> f32;
+    fn cr_atan2f(y: f32, x: f32) -> f32;
+    fn cr_logf(x: f32) -> f32;
+    fn cr_powf(x: f32, y: f32) -> f32;
 }
 
 #[inline]
@@ -19,7 +23,130 @@ pub fn expf(x: f32) -> f32 {
     unsafe { cr_expf(x) }
 }
 
+#[inline]
+pub fn tanf(x: f32) -> f32 {
+    unsafe { cr_tanf(x) }
+}
+
+#[inline]
+pub fn atan2f(y: f32, x: f32) -> f32 {
+    unsafe { cr_atan2f(y, x) }
+}
+
+#[inline]
+pub fn logf(x: f32) -> f32 {
+    unsafe { cr_logf(x) }
+}
+
+#[inline]
+pub fn powf(x: f32, y: f32) -> f32 {
+    unsafe { cr_powf(x, y) }
+}
+
 #[inline]
 pub fn sqrtf(x: f32) -> f32 {
     x.sqrt()
 }
+
+/// [`Node::Log`](crate::node::Node::Log): the log of `|x|`, which is held at
+/// `1e-6` or above so the result stays finite.
+#[inline]
+pub fn guarded_log(x: f32) -> f32 {
+    let magnitude = x.abs();
+    logf(if magnitude > 1e-6 { magnitude } else { 1e-6 })
+}
+
+/// [`Node::Pow`](crate::node::Node::Pow): `|base|` raised to `exponent`, or
+/// `0` once `|base|` is `1e-6` or below, like the `Div` guard. Large bases
+/// and exponents still overflow to infinity.
+#[inline]
+pub fn guarded_pow(base: f32, exponent: f32) -> f32 {
+    let magnitude = base.abs();
+    if magnitude > 1e-6 { powf(magnitude, exponent) } else { 0.0 }
+}
+
+/// [`Node::Mod`](crate::node::Node::Mod): the remainder of `a / b` with the
+/// sign of `a`, or `0` once `|b|` is `1e-6` or below, like the `Div` guard.
+#[inline]
+pub fn guarded_mod(a: f32, b: f32) -> f32 {
+    if b.abs() > 1e-6 { a % b } else { 0.0 }
+}
+
+/// The point [`Node::Rotate`](crate::node::Node::Rotate) evaluates its body
+/// at: `(x, y)` turned by `half_turns * π` radians.
+#[inline]
+pub fn rotate(half_turns: f32, x: f32, y: f32) -> (f32, f32) {
+    let angle = half_turns * std::f32::consts::PI;
+    let (c, s) = (cosf(angle), sinf(angle));
+    (x * c - y * s, x * s + y * c)
+}
+
+/// The point [`Node::Scale`](crate::node::Node::Scale) evaluates its body at:
+/// `(x, y) / factor`, or the origin once `|factor|` is `1e-6` or below, like
+/// the `Div` guard.
+#[inline]
+pub fn scale(factor: f32, x: f32, y: f32) -> (f32, f32) {
+    if factor.abs() > 1e-6 { (x / factor, y / factor) } else { (0.0, 0.0) }
+}
+
+/// [`Node::Smoothstep`](crate::node::Node::Smoothstep): `x` eased from `-1` at
+/// `e0` to `1` at `e1`, with the width guarded like `Div`. Backends that cannot
+/// call this must evaluate it in exactly these steps.
+#[inline]
+pub fn smoothstep(e0: f32, e1: f32, x: f32) -> f32 {
+    let width = e1 - e0;
+    let t = if width.abs() > 1e-6 { (x - e0) / width } else { 0.0 };
+    let t = t.clamp(0.0, 1.0);
+    2.0 * (t * t * (3.0 - 2.0 * t)) - 1.0
+}
+
+/// A [`Node::Escape`](crate::node::Node::Escape) loop keeps going while its
+/// value's magnitude is at most this, so NaN ends it too.
+pub const ESCAPE_RADIUS: f32 = 2.0;
+
+/// What [`Node::Escape`](crate::node::Node::Escape) gives for a loop that ran
+/// `steps` of at most `limit` passes: `-1` for none, `1` for all of them.
+#[inline]
+pub fn escape_time(steps: u32, limit: u32) -> f32 {
+    if limit == 0 { -1.0 } else { 2.0 * steps as f32 / limit as f32 - 1.0 }
+}
+
+/// The grid [`Node::Xor`](crate::node::Node::Xor) and
+/// [`Node::And`](crate::node::Node::And) snap their operands to, one byte wide.
+pub const BIT_LEVELS: u32 = 256;
+
+/// Which of `levels` equal steps across `[-1, 1]` holds `v`, from `0` up;
+/// values outside the range (and NaN) land on the nearest end. Backends that
+/// cannot call this must floor, convert with saturation and clamp in this order.
+#[inline]
+pub fn to_level(v: f32, levels: u32) -> i32 {
+    let scaled = ((v + 1.0) * 0.5 * levels as f32).floor();
+    (scaled as i32).max(0).min((levels - 1) as i32)
+}
+
+/// The value of step `level` of `levels`, spaced so the first is `-1` and the
+/// last is `1`.
+#[inline]
+pub fn from_level(level: i32, levels: u32) -> f32 {
+    2.0 * level as f32 / (levels - 1) as f32 - 1.0
+}
+
+/// [`Node::Quantize`](crate::node::Node::Quantize): `v` snapped to one of
+/// `levels` values, or `-1` for fewer than two.
+#[inline]
+pub fn quantize(v: f32, levels: u32) -> f32 {
+    if levels < 2 { -1.0 } else { from_level(to_level(v, levels), levels) }
+}
+
+/// [`Node::Xor`](crate::node::Node::Xor): the bits of the two operands'
+/// [`BIT_LEVELS`] steps, exclusive-ored.
+#[inline]
+pub fn bit_xor(a: f32, b: f32) -> f32 {
+    from_level(to_level(a, BIT_LEVELS) ^ to_level(b, BIT_LEVELS), BIT_LEVELS)
+}
+
+/// [`Node::And`](crate::node::Node::And): [`bit_xor`] with the bits anded.
+#[inline]
+pub fn bit_and(a: f32, b: f32) -> f32 {
+    from_level(to_level(a, BIT_LEVELS) & to_level(b, BIT_LEVELS), BIT_LEVELS)
+}
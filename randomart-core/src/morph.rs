@@ -0,0 +1,126 @@
+//! In-between formulas for morphing one tree into another.
+//!
+//! Where the two trees have the same shape, their `Number`s are interpolated,
+//! so shared structure moves smoothly instead of cross-fading. Where they
+//! differ, the two subtrees are both evaluated and their values blended.
+//! Roots of different kinds have no formula in between, so their rendered
+//! images are cross-faded instead, each drawn in its own colours.
+
+use crate::node::Node;
+use crate::pixel_buffer::PixelBuffer;
+
+/// The formula a fraction `s` of the way from `from` to `to`: `from` itself at
+/// `s = 0` and `to` at `s = 1`. `Var`s keep referring to the right `Let`s,
+/// since `Let`s only match when their ids do. `None` if one is a `Mono`,
+/// `Triple` or `Quad` root and the other a different one.
+pub fn morph(from: &Node, to: &Node, s: f32) -> Option<Node> {
+    if channels(from) != channels(to) {
+        return None;
+    }
+    Some(if s <= 0.0 {
+        from.clone()
+    } else if s >= 1.0 {
+        to.clone()
+    } else {
+        between(from, to, s)
+    })
+}
+
+/// The frame a fraction `s` of the way from `from` to `to`, with `render`
+/// drawing a formula: the [`morph`]ed formula where there is one, otherwise a
+/// [`PixelBuffer::blend`] of the two images.
+pub fn morph_frame<E>(
+    from: &Node,
+    to: &Node,
+    s: f32,
+    mut render: impl FnMut(&Node) -> Result<PixelBuffer, E>,
+) -> Result<PixelBuffer, E> {
+    match morph(from, to, s) {
+        Some(tree) => render(&tree),
+        None => Ok(render(from)?.blend(&render(to)?, s)),
+    }
+}
+
+fn between(from: &Node, to: &Node, s: f32) -> Node {
+    match (from, to) {
+        (Node::Number(a), Node::Number(b)) => Node::Number(a + (b - a) * s),
+        _ if shape(from) == shape(to) => {
+            let children = from
+                .children()
+                .into_iter()
+                .zip(to.children())
+                .map(|(a, b)| Box::new(between(a, b, s)))
+                .collect();
+            from.with_children(children)
+        }
+        // `Add` averages, so each side carries twice its share.
+        _ => Node::Add(
+            Box::new(Node::Mult(Box::new(from.clone()), Box::new(Node::Number(2.0 * (1.0 - s))))),
+            Box::new(Node::Mult(Box::new(to.clone()), Box::new(Node::Number(2.0 * s)))),
+        ),
+    }
+}
+
+/// How many channels a root has, or `None` for any other node.
+fn channels(node: &Node) -> Option<usize> {
+    match node {
+        Node::Mono(_) => Some(1),
+        Node::Triple(..) => Some(3),
+        Node::Quad(..) => Some(4),
+        _ => None,
+    }
+}
+
+/// `node` with every child replaced by the same placeholder, so two nodes
+/// compare equal when they differ at most in their children.
+fn shape(node: &Node) -> Node {
+    node.with_children(node.children().iter().map(|_| Box::new(Node::Number(0.0))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(v: f32) -> Box<Node> {
+        Box::new(Node::Number(v))
+    }
+
+    #[test]
+    fn shared_shapes_interpolate_their_numbers() {
+        let from = Node::Sin(Box::new(Node::Mult(Box::new(Node::X), num(2.0))));
+        let to = Node::Sin(Box::new(Node::Mult(Box::new(Node::X), num(4.0))));
+        assert_eq!(morph(&from, &to, 0.25).unwrap(), Node::Sin(Box::new(Node::Mult(Box::new(Node::X), num(2.5)))));
+        assert_eq!(morph(&from, &to, 0.0), Some(from.clone()));
+        assert_eq!(morph(&from, &to, 1.0), Some(to.clone()));
+    }
+
+    #[test]
+    fn differing_subtrees_blend() {
+        let from = Node::Sin(Box::new(Node::X));
+        let to = Node::Sin(Box::new(Node::Y));
+        let blend = Node::Add(
+            Box::new(Node::Mult(Box::new(Node::X), num(1.5))),
+            Box::new(Node::Mult(Box::new(Node::Y), num(0.5))),
+        );
+        assert_eq!(morph(&from, &to, 0.25).unwrap(), Node::Sin(Box::new(blend)));
+
+        // Parameters that are not children, like a loop count or a `Let` id,
+        // have to match too.
+        let from = Node::Iterate(Box::new(Node::X), 3);
+        let to = Node::Iterate(Box::new(Node::X), 4);
+        assert!(matches!(morph(&from, &to, 0.5), Some(Node::Add(..))));
+        let from = Node::Let(0, num(1.0), Box::new(Node::Var(0)));
+        let to = Node::Let(1, num(1.0), Box::new(Node::Var(1)));
+        assert!(matches!(morph(&from, &to, 0.5), Some(Node::Add(..))));
+    }
+
+    #[test]
+    fn differing_roots_have_no_formula_in_between() {
+        let mono = Node::Mono(Box::new(Node::X));
+        let triple = Node::Triple(Box::new(Node::X), num(0.0), num(1.0));
+        let quad = Node::Quad(Box::new(Node::X), num(0.0), num(1.0), num(-1.0));
+        assert_eq!(morph(&mono, &triple, 0.0), None);
+        assert_eq!(morph(&triple, &quad, 0.5), None);
+        assert!(morph(&mono, &Node::Mono(Box::new(Node::Y)), 0.5).is_some());
+    }
+}
@@ -1,25 +1,256 @@
+use crate::noise::Permutation;
+
+/// The most passes an [`Node::Iterate`] or [`Node::Escape`] may make. Formulas
+/// asking for more are rejected when read, since every pixel pays for each pass.
+pub const MAX_ITERATIONS: u32 = 256;
+
+/// The most loop passes a formula may make per pixel, counting each pass of a
+/// loop nested inside another once for every pass of the outer one.
+pub const MAX_NESTED_PASSES: u64 = 1 << 16;
+
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum Node {
     X,
     Y,
+    /// Depth of the point, off the image plane: `0` in a flat image, and the
+    /// third coordinate when rendering a sphere or a volume. See
+    /// [`Projection`](crate::render::Projection).
+    Z,
+    /// Time through an animation, in `[-1, 1]`, and `0` in a still image. See
+    /// [`frame_times`](crate::render::frame_times).
+    T,
+    /// Distance of the pixel from the origin.
+    R,
+    /// Angle of the pixel around the origin in radians, in `[-π, π]`.
+    Theta,
     Random,
     Rule(usize),
     Number(f32),
+    /// The value bound to this id by the innermost enclosing [`Node::Let`].
+    Var(u32),
     Sqrt(Box<Node>),
     Sin(Box<Node>),
     Cos(Box<Node>),
     Exp(Box<Node>),
+    Abs(Box<Node>),
+    Tan(Box<Node>),
+    /// Guarded as [`math::guarded_log`](crate::math::guarded_log).
+    Log(Box<Node>),
+    /// The child applied to the point's x coordinate the given number of
+    /// times: each pass evaluates it at `(previous result, y)`, starting from `x`.
+    Iterate(Box<Node>, u32),
+    /// [`Node::Iterate`] that stops once the value's magnitude passes
+    /// [`math::ESCAPE_RADIUS`](crate::math::ESCAPE_RADIUS), giving the number of
+    /// passes made as [`math::escape_time`](crate::math::escape_time).
+    Escape(Box<Node>, u32),
+    /// The child snapped to the given number of evenly spaced values across
+    /// `[-1, 1]`, as [`math::quantize`](crate::math::quantize).
+    Quantize(Box<Node>, u32),
     Add(Box<Node>, Box<Node>),
     Mult(Box<Node>, Box<Node>),
     Div(Box<Node>, Box<Node>),
+    Min(Box<Node>, Box<Node>),
+    Max(Box<Node>, Box<Node>),
+    /// Guarded as [`math::guarded_mod`](crate::math::guarded_mod).
+    Mod(Box<Node>, Box<Node>),
+    /// The angle of the point `(second, first)`, as C's `atan2(y, x)`.
+    Atan2(Box<Node>, Box<Node>),
+    /// Guarded as [`math::guarded_pow`](crate::math::guarded_pow).
+    Pow(Box<Node>, Box<Node>),
+    /// The children as bytes across `[-1, 1]`, exclusive-ored, as
+    /// [`math::bit_xor`](crate::math::bit_xor).
+    Xor(Box<Node>, Box<Node>),
+    /// [`Node::Xor`] with the bits anded.
+    And(Box<Node>, Box<Node>),
+    /// `-1` below the edge (the first child), `1` at or above it.
+    Step(Box<Node>, Box<Node>),
+    /// Gradient noise sampled at the two children, patterned by its own table.
+    Noise(Box<Node>, Box<Node>, Permutation),
+    /// The input image in the named slot sampled at the two children, in the
+    /// channel of the `Triple` component it is in, or the mean of the three
+    /// under a `Mono` and in a `Quad`'s alpha; see [`crate::texture`].
+    Texture(String, Box<Node>, Box<Node>),
+    /// The second child evaluated at the point turned about the origin by the
+    /// first, in half-turns.
+    Rotate(Box<Node>, Box<Node>),
+    /// The second child evaluated at the point divided by the first, so it
+    /// appears scaled by it; the divisor is guarded like `Div`.
+    Scale(Box<Node>, Box<Node>),
     Triple(Box<Node>, Box<Node>, Box<Node>),
+    /// A root with a single channel, coloured by the
+    /// [`Colormap`](crate::colormap::Colormap) chosen when rendering.
+    Mono(Box<Node>),
+    /// A root like `Triple` with a fourth channel for the alpha, `-1` being
+    /// transparent and `1` opaque.
+    Quad(Box<Node>, Box<Node>, Box<Node>, Box<Node>),
+    /// The second child where the first is positive, the third elsewhere.
+    IfPositive(Box<Node>, Box<Node>, Box<Node>),
+    /// A smooth [`Node::Step`] from `-1` at the first edge to `1` at the second.
+    Smoothstep(Box<Node>, Box<Node>, Box<Node>),
+    /// The third child evaluated at the point offset by the first two.
+    Warp(Box<Node>, Box<Node>, Box<Node>),
+    /// Signed distance from the point to the circle around the first two
+    /// children with the third as radius, as [`sdf::circle`](crate::sdf::circle).
+    Circle(Box<Node>, Box<Node>, Box<Node>),
+    /// The first two children as signed distances, joined into one shape with
+    /// a seam rounded over the third, as [`sdf::smooth_union`](crate::sdf::smooth_union).
+    SmoothUnion(Box<Node>, Box<Node>, Box<Node>),
+    /// [`Node::SmoothUnion`] for the overlap of the two shapes.
+    SmoothIntersect(Box<Node>, Box<Node>, Box<Node>),
+    /// The body (second child) with `Var(id)` standing for the value of the
+    /// first, which is evaluated once, at the point the `Let` is evaluated at.
+    Let(u32, Box<Node>, Box<Node>),
     MixUnbounded(Box<Node>, Box<Node>, Box<Node>, Box<Node>),
+    /// Signed distance to the box around the first two children with the last
+    /// two as half-width and half-height, as [`sdf::rectangle`](crate::sdf::rectangle).
+    BoxSdf(Box<Node>, Box<Node>, Box<Node>, Box<Node>),
+    /// Distance to the segment between the points given by the first two and
+    /// last two children, as [`sdf::segment`](crate::sdf::segment).
+    Line(Box<Node>, Box<Node>, Box<Node>, Box<Node>),
 }
 
 impl Node {
+    /// The direct children of this node, left to right.
+    pub fn children(&self) -> Vec<&Node> {
+        use Node::*;
+        match self {
+            X | Y | Z | T | R | Theta | Random | Rule(_) | Number(_) | Var(_) => vec![],
+            Sqrt(a) | Sin(a) | Cos(a) | Exp(a) | Abs(a) | Tan(a) | Log(a) | Iterate(a, _) | Escape(a, _)
+            | Quantize(a, _) | Mono(a) => vec![a],
+            Add(a, b) | Mult(a, b) | Div(a, b) | Min(a, b) | Max(a, b) | Mod(a, b) | Atan2(a, b) | Pow(a, b)
+            | Xor(a, b) | And(a, b) | Step(a, b) | Noise(a, b, _) | Texture(_, a, b) | Rotate(a, b) | Scale(a, b) | Let(_, a, b) => {
+                vec![a, b]
+            }
+            Triple(a, b, c) | IfPositive(a, b, c) | Smoothstep(a, b, c) | Warp(a, b, c) | Circle(a, b, c)
+            | SmoothUnion(a, b, c) | SmoothIntersect(a, b, c) => vec![a, b, c],
+            MixUnbounded(a, b, c, d) | BoxSdf(a, b, c, d) | Line(a, b, c, d) | Quad(a, b, c, d) => vec![a, b, c, d],
+        }
+    }
+
+    /// [`Node::children`], mutably.
+    pub fn children_mut(&mut self) -> Vec<&mut Box<Node>> {
+        use Node::*;
+        match self {
+            X | Y | Z | T | R | Theta | Random | Rule(_) | Number(_) | Var(_) => vec![],
+            Sqrt(a) | Sin(a) | Cos(a) | Exp(a) | Abs(a) | Tan(a) | Log(a) | Iterate(a, _) | Escape(a, _)
+            | Quantize(a, _) | Mono(a) => vec![a],
+            Add(a, b) | Mult(a, b) | Div(a, b) | Min(a, b) | Max(a, b) | Mod(a, b) | Atan2(a, b) | Pow(a, b)
+            | Xor(a, b) | And(a, b) | Step(a, b) | Noise(a, b, _) | Texture(_, a, b) | Rotate(a, b) | Scale(a, b) | Let(_, a, b) => {
+                vec![a, b]
+            }
+            Triple(a, b, c) | IfPositive(a, b, c) | Smoothstep(a, b, c) | Warp(a, b, c) | Circle(a, b, c)
+            | SmoothUnion(a, b, c) | SmoothIntersect(a, b, c) => vec![a, b, c],
+            MixUnbounded(a, b, c, d) | BoxSdf(a, b, c, d) | Line(a, b, c, d) | Quad(a, b, c, d) => vec![a, b, c, d],
+        }
+    }
+
+    /// The variant's name, as written in grammar files.
+    pub fn name(&self) -> &'static str {
+        use Node::*;
+        match self {
+            X => "X",
+            Y => "Y",
+            Z => "Z",
+            T => "T",
+            R => "R",
+            Theta => "Theta",
+            Random => "Random",
+            Rule(_) => "Rule",
+            Number(_) => "Number",
+            Var(_) => "Var",
+            Sqrt(_) => "Sqrt",
+            Sin(_) => "Sin",
+            Cos(_) => "Cos",
+            Exp(_) => "Exp",
+            Abs(_) => "Abs",
+            Tan(_) => "Tan",
+            Log(_) => "Log",
+            Iterate(..) => "Iterate",
+            Escape(..) => "Escape",
+            Quantize(..) => "Quantize",
+            Add(..) => "Add",
+            Mult(..) => "Mult",
+            Div(..) => "Div",
+            Min(..) => "Min",
+            Max(..) => "Max",
+            Mod(..) => "Mod",
+            Atan2(..) => "Atan2",
+            Pow(..) => "Pow",
+            Xor(..) => "Xor",
+            And(..) => "And",
+            Step(..) => "Step",
+            Noise(..) => "Noise",
+            Texture(..) => "Texture",
+            Rotate(..) => "Rotate",
+            Scale(..) => "Scale",
+            Triple(..) => "Triple",
+            Mono(_) => "Mono",
+            Quad(..) => "Quad",
+            IfPositive(..) => "IfPositive",
+            Smoothstep(..) => "Smoothstep",
+            Warp(..) => "Warp",
+            Circle(..) => "Circle",
+            SmoothUnion(..) => "SmoothUnion",
+            SmoothIntersect(..) => "SmoothIntersect",
+            Let(..) => "Let",
+            MixUnbounded(..) => "MixUnbounded",
+            BoxSdf(..) => "BoxSdf",
+            Line(..) => "Line",
+        }
+    }
+
+    /// A node of the same kind as `self` with `children` in place of its own,
+    /// which must match [`Node::children`] in number.
+    pub fn with_children(&self, children: Vec<Box<Node>>) -> Node {
+        use Node::*;
+        let mut it = children.into_iter();
+        let mut next = || it.next().expect("too few children for node");
+        match self {
+            X | Y | Z | T | R | Theta | Random | Rule(_) | Number(_) | Var(_) => self.clone(),
+            Sqrt(_) => Sqrt(next()),
+            Sin(_) => Sin(next()),
+            Cos(_) => Cos(next()),
+            Exp(_) => Exp(next()),
+            Abs(_) => Abs(next()),
+            Tan(_) => Tan(next()),
+            Log(_) => Log(next()),
+            Iterate(_, n) => Iterate(next(), *n),
+            Escape(_, n) => Escape(next(), *n),
+            Quantize(_, levels) => Quantize(next(), *levels),
+            Add(..) => Add(next(), next()),
+            Mult(..) => Mult(next(), next()),
+            Div(..) => Div(next(), next()),
+            Min(..) => Min(next(), next()),
+            Max(..) => Max(next(), next()),
+            Mod(..) => Mod(next(), next()),
+            Atan2(..) => Atan2(next(), next()),
+            Pow(..) => Pow(next(), next()),
+            Xor(..) => Xor(next(), next()),
+            And(..) => And(next(), next()),
+            Step(..) => Step(next(), next()),
+            Noise(_, _, perm) => Noise(next(), next(), perm.clone()),
+            Texture(slot, ..) => Texture(slot.clone(), next(), next()),
+            Rotate(..) => Rotate(next(), next()),
+            Scale(..) => Scale(next(), next()),
+            Triple(..) => Triple(next(), next(), next()),
+            Mono(_) => Mono(next()),
+            Quad(..) => Quad(next(), next(), next(), next()),
+            IfPositive(..) => IfPositive(next(), next(), next()),
+            Smoothstep(..) => Smoothstep(next(), next(), next()),
+            Warp(..) => Warp(next(), next(), next()),
+            Circle(..) => Circle(next(), next(), next()),
+            SmoothUnion(..) => SmoothUnion(next(), next(), next()),
+            SmoothIntersect(..) => SmoothIntersect(next(), next(), next()),
+            Let(id, ..) => Let(*id, next(), next()),
+            MixUnbounded(..) => MixUnbounded(next(), next(), next(), next()),
+            BoxSdf(..) => BoxSdf(next(), next(), next(), next()),
+            Line(..) => Line(next(), next(), next(), next()),
+        }
+    }
+
     pub fn simplify(&mut self) {
         use Node::*;
-        use crate::math;
+        use crate::{math, sdf};
         match self {
             Sin(inner) => {
                 inner.simplify();
@@ -37,6 +268,39 @@ impl Node {
                 inner.simplify();
                 if let Number(val) = **inner { *self = Number(math::sqrtf(val).max(0.0)); }
             }
+            Abs(inner) => {
+                inner.simplify();
+                if let Number(val) = **inner { *self = Number(val.abs()); }
+            }
+            Tan(inner) => {
+                inner.simplify();
+                if let Number(val) = **inner { *self = Number(math::tanf(val)); }
+            }
+            Log(inner) => {
+                inner.simplify();
+                if let Number(val) = **inner { *self = Number(math::guarded_log(val)); }
+            }
+            Iterate(body, n) => {
+                body.simplify();
+                // No passes leave x as it was; any pass of a constant gives the constant.
+                if *n == 0 {
+                    *self = X;
+                } else if let Number(val) = **body {
+                    *self = Number(val);
+                }
+            }
+            Escape(body, n) => {
+                body.simplify();
+                if *n == 0 { *self = Number(math::escape_time(0, 0)); }
+            }
+            Quantize(inner, levels) => {
+                inner.simplify();
+                if *levels < 2 {
+                    *self = Number(math::quantize(0.0, *levels));
+                } else if let Number(val) = **inner {
+                    *self = Number(math::quantize(val, *levels));
+                }
+            }
             Add(lhs, rhs) => {
                 lhs.simplify();
                 rhs.simplify();
@@ -58,25 +322,216 @@ impl Node {
                     *self = Number(if r.abs() > 1e-6 { l / r } else { 0.0 });
                 }
             }
+            Min(lhs, rhs) => {
+                lhs.simplify();
+                rhs.simplify();
+                if let (Number(l), Number(r)) = (&**lhs, &**rhs) {
+                    *self = Number(if l < r { *l } else { *r });
+                }
+            }
+            Max(lhs, rhs) => {
+                lhs.simplify();
+                rhs.simplify();
+                if let (Number(l), Number(r)) = (&**lhs, &**rhs) {
+                    *self = Number(if l > r { *l } else { *r });
+                }
+            }
+            Mod(lhs, rhs) => {
+                lhs.simplify();
+                rhs.simplify();
+                if let (Number(l), Number(r)) = (&**lhs, &**rhs) {
+                    *self = Number(math::guarded_mod(*l, *r));
+                }
+            }
+            Atan2(lhs, rhs) => {
+                lhs.simplify();
+                rhs.simplify();
+                if let (Number(l), Number(r)) = (&**lhs, &**rhs) {
+                    *self = Number(math::atan2f(*l, *r));
+                }
+            }
+            Pow(lhs, rhs) => {
+                lhs.simplify();
+                rhs.simplify();
+                if let (Number(l), Number(r)) = (&**lhs, &**rhs) {
+                    *self = Number(math::guarded_pow(*l, *r));
+                }
+            }
+            Xor(lhs, rhs) => {
+                lhs.simplify();
+                rhs.simplify();
+                if let (Number(l), Number(r)) = (&**lhs, &**rhs) {
+                    *self = Number(math::bit_xor(*l, *r));
+                }
+            }
+            And(lhs, rhs) => {
+                lhs.simplify();
+                rhs.simplify();
+                if let (Number(l), Number(r)) = (&**lhs, &**rhs) {
+                    *self = Number(math::bit_and(*l, *r));
+                }
+            }
+            Step(edge, x) => {
+                edge.simplify();
+                x.simplify();
+                if let (Number(edge), Number(x)) = (&**edge, &**x) {
+                    *self = Number(if x < edge { -1.0 } else { 1.0 });
+                }
+            }
+            Noise(x, y, perm) => {
+                x.simplify();
+                y.simplify();
+                if let (Number(x), Number(y)) = (&**x, &**y) {
+                    *self = Number(crate::noise::noise(*x, *y, perm.table()));
+                }
+            }
+            // The image is only known when rendering.
+            Texture(_, x, y) => {
+                x.simplify();
+                y.simplify();
+            }
+            Rotate(transform, body) | Scale(transform, body) => {
+                transform.simplify();
+                body.simplify();
+                // A constant looks the same wherever it is evaluated.
+                if let Number(val) = **body { *self = Number(val); }
+            }
+            Warp(dx, dy, body) => {
+                dx.simplify();
+                dy.simplify();
+                body.simplify();
+                if let Number(val) = **body { *self = Number(val); }
+            }
+            Let(id, value, body) => {
+                value.simplify();
+                if let Number(val) = **value {
+                    body.substitute(*id, val);
+                }
+                body.simplify();
+                if let Number(val) = **body { *self = Number(val); }
+            }
+            IfPositive(cond, then, otherwise) => {
+                cond.simplify();
+                then.simplify();
+                otherwise.simplify();
+                // A constant condition picks its branch whether or not that folds.
+                if let Number(c) = **cond {
+                    let taken = if c > 0.0 { then } else { otherwise };
+                    let taken = std::mem::replace(&mut **taken, X);
+                    *self = taken;
+                }
+            }
+            Smoothstep(e0, e1, x) => {
+                e0.simplify(); e1.simplify(); x.simplify();
+                if let (Number(e0), Number(e1), Number(x)) = (&**e0, &**e1, &**x) {
+                    *self = Number(math::smoothstep(*e0, *e1, *x));
+                }
+            }
+            SmoothUnion(a, b, k) => {
+                a.simplify(); b.simplify(); k.simplify();
+                if let (Number(a), Number(b), Number(k)) = (&**a, &**b, &**k) {
+                    *self = Number(sdf::smooth_union(*a, *b, *k));
+                }
+            }
+            SmoothIntersect(a, b, k) => {
+                a.simplify(); b.simplify(); k.simplify();
+                if let (Number(a), Number(b), Number(k)) = (&**a, &**b, &**k) {
+                    *self = Number(sdf::smooth_intersection(*a, *b, *k));
+                }
+            }
+            // Distances to shapes vary with the point, so only their parameters fold.
+            Circle(..) | BoxSdf(..) | Line(..) => {
+                for child in self.children_mut() {
+                    child.simplify();
+                }
+            }
             MixUnbounded(a, b, c, d) => {
                 a.simplify(); b.simplify(); c.simplify(); d.simplify();
                 if let (Number(a), Number(b), Number(c), Number(d)) = (&**a, &**b, &**c, &**d) {
                     *self = Number((a * c + b * d) / (a + b + 1e-6));
                 }
             }
-            Number(_) | X | Y => {}
+            Number(_) | X | Y | Z | T | R | Theta | Var(_) => {}
             node => panic!("encountered {:?} which is not evaluatable. examine your grammar.", node),
         }
     }
 
-    pub fn simplify_triple(&mut self) {
-        if let Node::Triple(first, second, third) = self {
-            rayon::join(|| first.simplify(), || second.simplify());
-            third.simplify();
-        } else {
-            panic!("expected Node::Triple, encountered {:?}", self);
+    /// Replace the `Var(id)`s that refer to the `Let` just outside `self` with `val`.
+    fn substitute(&mut self, id: u32, val: f32) {
+        match self {
+            Node::Var(var) if *var == id => *self = Node::Number(val),
+            // An inner `Let` of the same id shadows the outer one in its body.
+            Node::Let(inner, value, _) if *inner == id => value.substitute(id, val),
+            node => {
+                for child in node.children_mut() {
+                    child.substitute(id, val);
+                }
+            }
+        }
+    }
+
+    /// The id of a `Var` that no enclosing `Let` binds, if there is one.
+    pub fn unbound_var(&self) -> Option<u32> {
+        fn helper(node: &Node, scope: &mut Vec<u32>) -> Option<u32> {
+            match node {
+                Node::Var(id) => (!scope.contains(id)).then_some(*id),
+                Node::Let(id, value, body) => helper(value, scope).or_else(|| {
+                    scope.push(*id);
+                    let unbound = helper(body, scope);
+                    scope.pop();
+                    unbound
+                }),
+                node => node.children().into_iter().find_map(|child| helper(child, scope)),
+            }
+        }
+        helper(self, &mut Vec::new())
+    }
+
+    /// The most passes any [`Node::Iterate`] or [`Node::Escape`] in the tree makes.
+    pub fn most_iterations(&self) -> u32 {
+        let own = match self {
+            Node::Iterate(_, n) | Node::Escape(_, n) => *n,
+            _ => 0,
+        };
+        self.children().into_iter().map(Node::most_iterations).fold(own, u32::max)
+    }
+
+    /// The most loop passes one evaluation of the tree makes: the product of
+    /// the passes of the [`Node::Iterate`]s and [`Node::Escape`]s along any one
+    /// path from the root, or 1 without loops.
+    pub fn nested_passes(&self) -> u64 {
+        let inner = self.children().into_iter().map(Node::nested_passes).max().unwrap_or(1);
+        match self {
+            Node::Iterate(_, n) | Node::Escape(_, n) => inner.saturating_mul(u64::from(*n)),
+            _ => inner,
+        }
+    }
+
+    /// Simplify each channel of a `Triple`, `Mono` or `Quad` root.
+    pub fn simplify_root(&mut self) {
+        match self {
+            Node::Triple(first, second, third) => {
+                rayon::join(|| first.simplify(), || second.simplify());
+                third.simplify();
+            }
+            Node::Mono(channel) => channel.simplify(),
+            Node::Quad(first, second, third, alpha) => {
+                rayon::join(|| first.simplify(), || second.simplify());
+                rayon::join(|| third.simplify(), || alpha.simplify());
+            }
+            _ => panic!("expected Node::Triple, Node::Mono or Node::Quad, encountered {:?}", self),
         }
     }
+
+    #[deprecated(note = "renamed to `simplify_root`, which also takes `Mono` and `Quad` roots")]
+    pub fn simplify_triple(&mut self) {
+        self.simplify_root();
+    }
+
+    /// Whether this root renders with an alpha channel, which only a `Quad` has.
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, Node::Quad(..))
+    }
 }
 
 #[cfg(test)]
@@ -138,4 +593,112 @@ mod tests {
         // Can't fold because X is not a constant; stays an Add.
         assert!(matches!(n, Add(_, _)));
     }
+
+    #[test]
+    fn folds_thresholds() {
+        assert_eq!(simplified(Min(num(0.5), num(-0.5))), -0.5);
+        assert_eq!(simplified(Max(num(0.5), num(-0.5))), 0.5);
+        // At the edge counts as above it.
+        assert_eq!(simplified(Step(num(0.2), num(0.2))), 1.0);
+        assert_eq!(simplified(Step(num(0.2), num(0.1))), -1.0);
+        // Halfway between the edges is halfway between -1 and 1; a zero-width
+        // edge is guarded like Div and stays at -1.
+        assert_eq!(simplified(Smoothstep(num(-1.0), num(1.0), num(0.0))), 0.0);
+        assert_eq!(simplified(Smoothstep(num(-1.0), num(1.0), num(5.0))), 1.0);
+        assert_eq!(simplified(Smoothstep(num(0.3), num(0.3), num(5.0))), -1.0);
+    }
+
+    #[test]
+    fn constant_condition_picks_its_branch() {
+        let mut n = IfPositive(num(0.5), Box::new(X), Box::new(Y));
+        n.simplify();
+        assert_eq!(n, X);
+        // Zero is not positive.
+        assert_eq!(simplified(IfPositive(num(0.0), num(1.0), Box::new(Sin(num(0.0))))), 0.0);
+    }
+
+    #[test]
+    fn math_guards_match_runtime_formulas() {
+        // Log takes the magnitude and holds it at 1e-6 or above.
+        assert_eq!(simplified(Log(num(-1.0))), 0.0);
+        assert_eq!(simplified(Log(num(0.0))), simplified(Log(num(1e-6))));
+        // Pow takes the magnitude of the base and is 0 for a base near zero.
+        assert_eq!(simplified(Pow(num(-2.0), num(2.0))), 4.0);
+        assert_eq!(simplified(Pow(num(0.0), num(-1.0))), 0.0);
+        // Mod keeps the sign of the dividend and is guarded like Div.
+        assert_eq!(simplified(Mod(num(-0.75), num(0.5))), -0.25);
+        assert_eq!(simplified(Mod(num(0.75), num(0.0))), 0.0);
+        assert_eq!(simplified(Abs(num(-0.5))), 0.5);
+        assert_eq!(simplified(Atan2(num(0.0), num(-1.0))), std::f32::consts::PI);
+    }
+
+    #[test]
+    fn constant_bindings_fold_into_their_vars() {
+        // Sin(0.5) is bound once and used twice; the inner Let of the same id
+        // shadows it in its own body only.
+        let tree = Let(
+            0,
+            Box::new(Sin(num(0.5))),
+            Box::new(Add(Box::new(Var(0)), Box::new(Let(0, Box::new(Mult(Box::new(Var(0)), num(2.0))), Box::new(Var(0)))))),
+        );
+        let bound = crate::math::sinf(0.5);
+        assert_eq!(simplified(tree), (bound + bound * 2.0) / 2.0);
+
+        // A binding that varies keeps its Let; a body that doesn't use it folds anyway.
+        let mut n = Let(3, Box::new(X), Box::new(Sin(Box::new(Var(3)))));
+        n.simplify();
+        assert!(matches!(n, Let(3, _, _)));
+        assert_eq!(simplified(Let(3, Box::new(X), num(0.25))), 0.25);
+    }
+
+    #[test]
+    fn loops_fold_when_their_passes_allow() {
+        assert_eq!(simplified(Iterate(Box::new(Sin(num(0.5))), 3)), crate::math::sinf(0.5));
+        assert_eq!(simplified(Escape(Box::new(X), 0)), -1.0);
+        let mut n = Iterate(Box::new(Sin(Box::new(X))), 0);
+        n.simplify();
+        assert_eq!(n, X);
+        // Escape depends on where it starts even with a constant body.
+        let mut n = Escape(num(3.0), 4);
+        n.simplify();
+        assert_eq!(n, Escape(num(3.0), 4));
+        assert_eq!(Add(Box::new(Iterate(Box::new(Escape(Box::new(X), 9)), 2)), Box::new(Y)).most_iterations(), 9);
+        assert_eq!(Add(Box::new(Iterate(Box::new(Escape(Box::new(X), 9)), 2)), Box::new(Y)).nested_passes(), 18);
+    }
+
+    #[test]
+    fn finds_vars_outside_their_lets() {
+        assert_eq!(Let(1, Box::new(X), Box::new(Var(1))).unbound_var(), None);
+        // The value is outside its own binding.
+        assert_eq!(Let(1, Box::new(Var(1)), Box::new(X)).unbound_var(), Some(1));
+        assert_eq!(Sin(Box::new(Var(2))).unbound_var(), Some(2));
+    }
+
+    #[test]
+    fn quantized_values_fold_onto_their_grid() {
+        // Five levels split [-1, 1] into fifths valued -1, -0.5, 0, 0.5 and 1.
+        assert_eq!(simplified(Quantize(num(-0.4), 5)), -0.5);
+        assert_eq!(simplified(Quantize(num(0.0), 5)), 0.0);
+        assert_eq!(simplified(Quantize(num(0.9), 5)), 1.0);
+        // Out of range and NaN land on the ends.
+        assert_eq!(simplified(Quantize(num(5.0), 5)), 1.0);
+        assert_eq!(simplified(Quantize(num(f32::NAN), 5)), -1.0);
+        // Too few levels to span the range is flat, whatever the child.
+        assert_eq!(simplified(Quantize(Box::new(X), 1)), -1.0);
+        // -1 and 1 are bytes 0 and 255; 0 is byte 128.
+        assert_eq!(simplified(Xor(num(-1.0), num(1.0))), 1.0);
+        assert_eq!(simplified(Xor(num(0.0), num(0.0))), -1.0);
+        assert_eq!(simplified(And(num(0.0), num(1.0))), crate::math::from_level(128, 256));
+        assert_eq!(simplified(And(num(-1.0), num(1.0))), -1.0);
+    }
+
+    #[test]
+    fn transforms_of_a_constant_body_fold_away() {
+        assert_eq!(simplified(Rotate(Box::new(X), num(0.25))), 0.25);
+        assert_eq!(simplified(Warp(Box::new(X), Box::new(Y), num(-0.5))), -0.5);
+        // A body that reads the coordinates keeps the transform, folded inside.
+        let mut n = Scale(Box::new(Mult(num(4.0), num(0.5))), Box::new(X));
+        n.simplify();
+        assert_eq!(n, Scale(num(2.0), Box::new(X)));
+    }
 }
@@ -0,0 +1,132 @@
+//! 2D gradient (Perlin) noise for [`Node::Noise`](crate::node::Node::Noise),
+//! in plain f32 arithmetic so every CPU backend gets the same bits.
+
+use crate::rng::Rng_;
+use serde::{Deserialize, Serialize};
+
+/// Noise is sampled at this many cells per unit, so the `[-1, 1]` square
+/// spans several cells instead of one smooth bump.
+const FREQUENCY: f32 = 4.0;
+
+/// A shuffle of `0..=255` that fixes the pattern of one `Noise` node.
+/// Serialized as the plain list of 256 entries.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<u8>", into = "Vec<u8>")]
+pub struct Permutation(Box<[u8; 256]>);
+
+impl Permutation {
+    /// `0..=255` in order: the placeholder a `Noise` node in a grammar carries
+    /// until generation gives it a table of its own.
+    pub fn identity() -> Self {
+        Self(Box::new(std::array::from_fn(|i| i as u8)))
+    }
+
+    /// A Fisher-Yates shuffle driven by `seed`.
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = Rng_::new(seed);
+        let mut table = Self::identity().0;
+        for i in (1..table.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+        Self(table)
+    }
+
+    pub fn table(&self) -> &[u8; 256] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for Permutation {
+    type Error = String;
+
+    fn try_from(entries: Vec<u8>) -> Result<Self, Self::Error> {
+        let table: Box<[u8; 256]> = entries
+            .into_boxed_slice()
+            .try_into()
+            .map_err(|entries: Box<[u8]>| format!("a permutation has 256 entries, not {}", entries.len()))?;
+        let mut seen = [false; 256];
+        for &entry in table.iter() {
+            if std::mem::replace(&mut seen[entry as usize], true) {
+                return Err(format!("{entry} appears twice in the permutation"));
+            }
+        }
+        Ok(Self(table))
+    }
+}
+
+impl From<Permutation> for Vec<u8> {
+    fn from(permutation: Permutation) -> Self {
+        permutation.0.to_vec()
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// Dot product of `(x, y)` with one of eight gradients picked by `hash`.
+fn grad(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// Perlin noise at `(x, y)`, roughly in `[-1, 1]`. Non-finite coordinates give NaN.
+pub fn noise(x: f32, y: f32, perm: &[u8; 256]) -> f32 {
+    let (x, y) = (x * FREQUENCY, y * FREQUENCY);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    // Cells repeat every 256; `as` saturates, so far-off coordinates stay in range.
+    let (xi, yi) = ((x0 as i32 & 255) as usize, (y0 as i32 & 255) as usize);
+
+    let p = |i: usize| perm[i & 255] as usize;
+    let hash = |ix: usize, iy: usize| perm[(p(ix) + iy) & 255];
+
+    let n00 = grad(hash(xi, yi), fx, fy);
+    let n10 = grad(hash(xi + 1, yi), fx - 1.0, fy);
+    let n01 = grad(hash(xi, yi + 1), fx, fy - 1.0);
+    let n11 = grad(hash(xi + 1, yi + 1), fx - 1.0, fy - 1.0);
+
+    let (u, v) = (fade(fx), fade(fy));
+    lerp(v, lerp(u, n00, n10), lerp(u, n01, n11))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_are_seeded_permutations() {
+        let table = Permutation::from_seed(7);
+        assert_eq!(table, Permutation::from_seed(7));
+        assert_ne!(table, Permutation::from_seed(8));
+        assert_eq!(Permutation::try_from(Vec::from(table.clone())), Ok(table));
+        assert!(Permutation::try_from(vec![0; 256]).is_err());
+        assert!(Permutation::try_from(vec![0, 1]).is_err());
+    }
+
+    #[test]
+    fn noise_is_zero_on_lattice_points_and_bounded() {
+        let perm = Permutation::from_seed(1);
+        assert_eq!(noise(0.25, -0.5, perm.table()), 0.0);
+        for i in 0..100 {
+            for j in 0..100 {
+                let v = noise(i as f32 / 50.0 - 1.0, j as f32 / 37.0 - 1.0, perm.table());
+                assert!((-1.0..=1.0).contains(&v), "{v}");
+            }
+        }
+        assert!(noise(f32::INFINITY, 0.0, perm.table()).is_nan());
+    }
+}
@@ -7,29 +7,72 @@ pub struct ReadOutput {
     pub pixels: PixelBuffer,
 }
 
-/// A flat RGB image buffer. Each pixel is 3 consecutive bytes: R, G, B.
+/// A flat RGB or RGBA image buffer. Each pixel is `channels` consecutive
+/// bytes: R, G, B and, with an alpha channel, A.
 #[derive(PartialEq, Eq, Debug)]
 pub struct PixelBuffer {
     pub width: u32,
     pub height: u32,
-    /// Row-major RGB bytes, length == width * height * 3.
+    /// 3 for RGB, 4 for RGBA.
+    pub channels: u32,
+    /// Row-major bytes, length == width * height * channels.
     pub data: Vec<u8>,
 }
 
 impl PixelBuffer {
     pub fn new(width: u32, height: u32) -> Self {
+        Self::with_channels(width, height, 3)
+    }
+
+    /// An RGBA buffer, fully transparent until written.
+    pub fn with_alpha(width: u32, height: u32) -> Self {
+        Self::with_channels(width, height, 4)
+    }
+
+    fn with_channels(width: u32, height: u32, channels: u32) -> Self {
         Self {
             width,
             height,
-            data: vec![0u8; width as usize * height as usize * 3],
+            channels,
+            data: vec![0u8; width as usize * height as usize * channels as usize],
         }
     }
 
+    pub fn has_alpha(&self) -> bool {
+        self.channels == 4
+    }
+
+    /// Set the colour of a pixel, leaving its alpha, if any, as it was.
     #[inline]
     pub fn put_pixel(&mut self, x: u32, y: u32, r: u8, g: u8, b: u8) {
-        let idx = (y as usize * self.width as usize + x as usize) * 3;
+        let idx = (y as usize * self.width as usize + x as usize) * self.channels as usize;
         self.data[idx]     = r;
         self.data[idx + 1] = g;
         self.data[idx + 2] = b;
     }
+
+    /// Set the colour and alpha of a pixel in an RGBA buffer.
+    #[inline]
+    pub fn put_pixel_rgba(&mut self, x: u32, y: u32, r: u8, g: u8, b: u8, a: u8) {
+        debug_assert!(self.has_alpha(), "put_pixel_rgba on an RGB buffer");
+        self.put_pixel(x, y, r, g, b);
+        self.data[(y as usize * self.width as usize + x as usize) * 4 + 3] = a;
+    }
+
+    /// A cross-fade a fraction `s` of the way from this image to `other`, of
+    /// the same size. It has an alpha channel if either does, taking a missing
+    /// one as opaque.
+    pub fn blend(&self, other: &PixelBuffer, s: f32) -> PixelBuffer {
+        assert_eq!((self.width, self.height), (other.width, other.height), "blending images of different sizes");
+        let mut out = Self::with_channels(self.width, self.height, self.channels.max(other.channels));
+        let byte = |buf: &PixelBuffer, i: usize, c: usize| {
+            if c < buf.channels as usize { f32::from(buf.data[i * buf.channels as usize + c]) } else { 255.0 }
+        };
+        let channels = out.channels as usize;
+        for (j, v) in out.data.iter_mut().enumerate() {
+            let (a, b) = (byte(self, j / channels, j % channels), byte(other, j / channels, j % channels));
+            *v = (a + (b - a) * s).round() as u8;
+        }
+        out
+    }
 }
@@ -1,24 +1,200 @@
 use crate::disable_ftz;
+use crate::math::{cosf, sinf, sqrtf};
+use crate::node::Node;
 use crate::pixel_buffer::PixelBuffer;
 use rayon::prelude::*;
+use std::f32::consts::{PI, TAU};
 
 pub struct PixelCoordinates {
     pub x: f32,
     pub y: f32,
+    pub z: f32,
+    pub t: f32,
+}
+
+/// Where the pixels of an image sample a formula. `y` runs down the image in
+/// every projection, so the point straight ahead, `(0, 0, 1)`, sits upright
+/// in the middle of the sphere and of the [`Face::PositiveZ`] face.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// The `[-1, 1]` square at depth `z`, edge pixels on its edges.
+    /// `Plane(0.0)` is the ordinary flat image.
+    Plane(f32),
+    /// The unit sphere unrolled by longitude across and latitude down, for a
+    /// seamless 2:1 panorama.
+    Equirectangular,
+    /// One face of a cubemap of the unit sphere.
+    CubeFace(Face),
+}
+
+/// The faces of a cubemap, named and laid out the way OpenGL reads them.
+/// OpenGL's `y` points up, so its [`Face::PositiveY`] looks towards the
+/// formula's `-y`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Face {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl Face {
+    /// All six, in OpenGL's order.
+    pub const ALL: [Face; 6] =
+        [Face::PositiveX, Face::NegativeX, Face::PositiveY, Face::NegativeY, Face::PositiveZ, Face::NegativeZ];
+
+    /// The usual short name for the face in skybox file names, e.g. `px`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Face::PositiveX => "px",
+            Face::NegativeX => "nx",
+            Face::PositiveY => "py",
+            Face::NegativeY => "ny",
+            Face::PositiveZ => "pz",
+            Face::NegativeZ => "nz",
+        }
+    }
+
+    /// The point on the cube for `(s, t)` across and down the face, each in
+    /// `[-1, 1]`, in the formula's coordinates.
+    fn point(self, s: f32, t: f32) -> (f32, f32, f32) {
+        match self {
+            Face::PositiveX => (1.0, t, -s),
+            Face::NegativeX => (-1.0, t, s),
+            Face::PositiveY => (s, -1.0, t),
+            Face::NegativeY => (s, 1.0, -t),
+            Face::PositiveZ => (s, t, 1.0),
+            Face::NegativeZ => (-s, t, -1.0),
+        }
+    }
+}
+
+impl Projection {
+    /// `count` planes evenly spaced from `z = -1` to `z = 1`, the slices of
+    /// the volume. A single slice is `Plane(0.0)`.
+    pub fn slices(count: u32) -> Vec<Projection> {
+        match count {
+            1 => vec![Projection::Plane(0.0)],
+            _ => (0..count).map(|k| Projection::Plane((k as f32 / (count - 1) as f32) * 2.0 - 1.0)).collect(),
+        }
+    }
+
+    /// The six [`Projection::CubeFace`]s, in [`Face::ALL`] order.
+    pub fn cubemap() -> Vec<Projection> {
+        Face::ALL.into_iter().map(Projection::CubeFace).collect()
+    }
+
+    /// The point pixel `(px, py)` of a `width x height` image samples, at
+    /// `t = 0`. The sphere and the cube faces sample at pixel centres, so
+    /// faces meet without repeating a row.
+    pub fn point(self, px: u32, py: u32, width: u32, height: u32) -> PixelCoordinates {
+        let centre = |p: u32, size: u32| ((p as f32 + 0.5) / size as f32) * 2.0 - 1.0;
+        match self {
+            Projection::Plane(z) => {
+                let x = (px as f32 / (width - 1) as f32) * 2.0 - 1.0;
+                let y = (py as f32 / (height - 1) as f32) * 2.0 - 1.0;
+                PixelCoordinates { x, y, z, t: 0.0 }
+            }
+            Projection::Equirectangular => {
+                let longitude = centre(px, width) * PI;
+                let latitude = centre(py, height) * (PI / 2.0);
+                let (sin_lat, cos_lat) = (sinf(latitude), cosf(latitude));
+                PixelCoordinates { x: cos_lat * sinf(longitude), y: sin_lat, z: cos_lat * cosf(longitude), t: 0.0 }
+            }
+            Projection::CubeFace(face) => {
+                let (x, y, z) = face.point(centre(px, width), centre(py, height));
+                let length = sqrtf(x * x + y * y + z * z);
+                PixelCoordinates { x: x / length, y: y / length, z: z / length, t: 0.0 }
+            }
+        }
+    }
 }
 
 pub struct Colour {
     pub r: f32,
     pub g: f32,
     pub b: f32,
+    /// From `-1` for transparent to `1` for opaque, the same scale as the
+    /// colour; only kept in images rendered with an alpha channel.
+    pub a: f32,
+}
+
+/// The channels a render writes: the colour alone, or the colour and
+/// [`Colour::a`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channels {
+    Rgb,
+    Rgba,
+}
+
+impl Channels {
+    /// The channels of an image of `root`: RGBA for a root with an alpha
+    /// channel, RGB otherwise.
+    pub fn of(root: &Node) -> Self {
+        if root.has_alpha() { Channels::Rgba } else { Channels::Rgb }
+    }
 }
 
 const TILE_SIZE: u32 = 32;
 
-/// Render `width x height` pixels in parallel tiles by evaluating `function` at
-/// each pixel's `[-1, 1]` coordinate. Disables FTZ/DAZ on every worker thread so
-/// subnormal floats are handled IEEE-correctly, keeping CPU backends bit-exact.
+/// Render `width x height` RGB pixels in parallel tiles by evaluating
+/// `function` at each pixel's `[-1, 1]` coordinate. Disables FTZ/DAZ on every
+/// worker thread so subnormal floats are handled IEEE-correctly, keeping CPU
+/// backends bit-exact.
 pub fn render_tiled<F>(function: &F, width: u32, height: u32) -> PixelBuffer
+where
+    F: Sync + Fn(PixelCoordinates) -> Colour,
+{
+    render_tiled_projected(function, Projection::Plane(0.0), Channels::Rgb, width, height)
+}
+
+/// [`render_tiled`], evaluating `function` at the points `projection` gives
+/// each pixel and writing `channels`.
+pub fn render_tiled_projected<F>(
+    function: &F,
+    projection: Projection,
+    channels: Channels,
+    width: u32,
+    height: u32,
+) -> PixelBuffer
+where
+    F: Sync + Fn(PixelCoordinates) -> Colour,
+{
+    render_frame(function, projection, 0.0, channels, width, height)
+}
+
+/// The times of `count` animation frames, running from `-1` to `1`. A
+/// `periodic` animation eases from `-1` up to `1` and back along a cosine
+/// instead, stopping a frame short of where it started, so that looping it
+/// shows no seam.
+pub fn frame_times(count: u32, periodic: bool) -> Vec<f32> {
+    match (count, periodic) {
+        (_, true) => (0..count).map(|k| -cosf(TAU * k as f32 / count as f32)).collect(),
+        (1, false) => vec![0.0],
+        (_, false) => (0..count).map(|k| (k as f32 / (count - 1) as f32) * 2.0 - 1.0).collect(),
+    }
+}
+
+/// The frames of an animation of the flat image: `function` rendered with
+/// [`render_tiled_projected`] once for each of `times`.
+pub fn render_frames<F>(function: &F, times: &[f32], channels: Channels, width: u32, height: u32) -> Vec<PixelBuffer>
+where
+    F: Sync + Fn(PixelCoordinates) -> Colour,
+{
+    times.iter().map(|&t| render_frame(function, Projection::Plane(0.0), t, channels, width, height)).collect()
+}
+
+/// [`render_tiled_projected`] at time `t`.
+fn render_frame<F>(
+    function: &F,
+    projection: Projection,
+    t: f32,
+    channels: Channels,
+    width: u32,
+    height: u32,
+) -> PixelBuffer
 where
     F: Sync + Fn(PixelCoordinates) -> Colour,
 {
@@ -29,10 +205,10 @@ where
         .flat_map(|ty| (0..tiles_x).map(move |tx| (tx * TILE_SIZE, ty * TILE_SIZE)))
         .collect();
 
-    // Each tile produces a vec of (global_x, global_y, r, g, b) tuples.
+    // Each tile produces a vec of (global_x, global_y, [r, g, b, a]) tuples.
     rayon::broadcast(|_| unsafe { disable_ftz() });
 
-    let tile_pixels: Vec<Vec<(u32, u32, u8, u8, u8)>> = tiles
+    let tile_pixels: Vec<Vec<(u32, u32, [u8; 4])>> = tiles
         .into_par_iter()
         .map(|(x_start, y_start)| {
             let x_end = (x_start + TILE_SIZE).min(width);
@@ -42,14 +218,10 @@ where
 
             for py in y_start..y_end {
                 for px in x_start..x_end {
-                    let x = (px as f32 / (width - 1) as f32) * 2.0 - 1.0;
-                    let y = (py as f32 / (height - 1) as f32) * 2.0 - 1.0;
-                    let Colour { r, g, b } = function(PixelCoordinates { x, y });
-
-                    let r = ((r + 1.0) * 127.5).clamp(0.0, 255.0) as u8;
-                    let g = ((g + 1.0) * 127.5).clamp(0.0, 255.0) as u8;
-                    let b = ((b + 1.0) * 127.5).clamp(0.0, 255.0) as u8;
-                    pixels.push((px, py, r, g, b));
+                    let Colour { r, g, b, a } = function(PixelCoordinates { t, ..projection.point(px, py, width, height) });
+
+                    let byte = |v: f32| ((v + 1.0) * 127.5).clamp(0.0, 255.0) as u8;
+                    pixels.push((px, py, [byte(r), byte(g), byte(b), byte(a)]));
                 }
             }
 
@@ -57,11 +229,85 @@ where
         })
         .collect();
 
-    let mut buf = PixelBuffer::new(width, height);
+    let mut buf = match channels {
+        Channels::Rgb => PixelBuffer::new(width, height),
+        Channels::Rgba => PixelBuffer::with_alpha(width, height),
+    };
     for tile in tile_pixels {
-        for (x, y, r, g, b) in tile {
-            buf.put_pixel(x, y, r, g, b);
+        for (x, y, [r, g, b, a]) in tile {
+            match channels {
+                Channels::Rgb => buf.put_pixel(x, y, r, g, b),
+                Channels::Rgba => buf.put_pixel_rgba(x, y, r, g, b, a),
+            }
         }
     }
     buf
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinates(projection: Projection, px: u32, py: u32, size: u32) -> (f32, f32, f32) {
+        let PixelCoordinates { x, y, z, .. } = projection.point(px, py, size, size);
+        (x, y, z)
+    }
+
+    #[test]
+    fn planes_keep_the_flat_grid() {
+        assert_eq!(coordinates(Projection::Plane(0.0), 0, 4, 5), (-1.0, 1.0, 0.0));
+        assert_eq!(coordinates(Projection::Plane(0.5), 2, 0, 5), (0.0, -1.0, 0.5));
+        let depths: Vec<f32> = Projection::slices(5)
+            .into_iter()
+            .map(|slice| coordinates(slice, 0, 0, 2).2)
+            .collect();
+        assert_eq!(depths, [-1.0, -0.5, 0.0, 0.5, 1.0]);
+        assert_eq!(Projection::slices(1), [Projection::Plane(0.0)]);
+    }
+
+    #[test]
+    fn sphere_and_cube_points_are_unit_directions() {
+        let size = 8;
+        for projection in Projection::cubemap().into_iter().chain([Projection::Equirectangular]) {
+            for (px, py) in [(0, 0), (3, 5), (7, 7)] {
+                let (x, y, z) = coordinates(projection, px, py, size);
+                assert!((x * x + y * y + z * z - 1.0).abs() < 1e-5, "{projection:?} at ({px}, {py})");
+            }
+        }
+        // Straight ahead is in the middle of the panorama and of the front face,
+        // up (-y) along the top edge of the panorama.
+        let (x, y, z) = coordinates(Projection::Equirectangular, 4, 4, 9);
+        assert!(x.abs() < 1e-6 && y.abs() < 1e-6 && (z - 1.0).abs() < 1e-6);
+        let (_, y, _) = coordinates(Projection::Equirectangular, 0, 0, 1000);
+        assert!(y < -0.9999);
+        let (x, y, z) = coordinates(Projection::CubeFace(Face::PositiveZ), 4, 4, 9);
+        assert_eq!((x, y, z), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn neighbouring_faces_meet_at_their_edges() {
+        // The right edge of the front face runs into the left edge of +x, and
+        // its top edge into the bottom edge of +y, the face above.
+        let size = 64;
+        for p in [0, 17, size - 1] {
+            let front = coordinates(Projection::CubeFace(Face::PositiveZ), size - 1, p, size);
+            let right = coordinates(Projection::CubeFace(Face::PositiveX), 0, p, size);
+            assert!((front.0 - right.0).abs() < 0.05 && (front.1 - right.1).abs() < 0.05 && (front.2 - right.2).abs() < 0.05);
+            let front = coordinates(Projection::CubeFace(Face::PositiveZ), p, 0, size);
+            let top = coordinates(Projection::CubeFace(Face::PositiveY), p, size - 1, size);
+            assert!((front.0 - top.0).abs() < 0.05 && (front.1 - top.1).abs() < 0.05 && (front.2 - top.2).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn periodic_frames_come_back_around() {
+        assert_eq!(frame_times(5, false), [-1.0, -0.5, 0.0, 0.5, 1.0]);
+        assert_eq!(frame_times(1, false), [0.0]);
+        let times = frame_times(4, true);
+        assert_eq!(times[0], -1.0);
+        assert!((times[2] - 1.0).abs() < 1e-6 && times[1].abs() < 1e-6 && times[3].abs() < 1e-6);
+        // The frame after the last would be the first again.
+        let times = frame_times(60, true);
+        assert!((times[1] - times[59]).abs() < 1e-6);
+    }
+}
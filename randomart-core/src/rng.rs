@@ -15,4 +15,8 @@ impl Rng_ {
     pub fn next_float(&mut self) -> f32 {
         self.rng.random::<f32>() // guaranteed in [0.0, 1.0)
     }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.rng.random::<u64>()
+    }
 }
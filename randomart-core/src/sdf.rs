@@ -0,0 +1,79 @@
+//! Signed distance fields for the shape nodes ([`Node::Circle`] and friends),
+//! in plain f32 arithmetic so every CPU backend gets the same bits. Distances
+//! are negative inside a shape.
+//!
+//! [`Node::Circle`]: crate::node::Node::Circle
+
+use crate::math::sqrtf;
+
+/// Distance from `(x, y)` to the circle of radius `r` around `(cx, cy)`.
+pub fn circle(x: f32, y: f32, cx: f32, cy: f32, r: f32) -> f32 {
+    let (dx, dy) = (x - cx, y - cy);
+    sqrtf(dx * dx + dy * dy) - r
+}
+
+/// Distance from `(x, y)` to the axis-aligned box around `(cx, cy)` reaching
+/// `|half_width|` and `|half_height|` from its centre.
+pub fn rectangle(x: f32, y: f32, cx: f32, cy: f32, half_width: f32, half_height: f32) -> f32 {
+    let dx = (x - cx).abs() - half_width.abs();
+    let dy = (y - cy).abs() - half_height.abs();
+    let (ox, oy) = (dx.max(0.0), dy.max(0.0));
+    sqrtf(ox * ox + oy * oy) + dx.max(dy).min(0.0)
+}
+
+/// Distance from `(x, y)` to the segment from `(ax, ay)` to `(bx, by)`. A
+/// segment whose squared length is `1e-6` or below counts as its first end,
+/// like the `Div` guard.
+pub fn segment(x: f32, y: f32, ax: f32, ay: f32, bx: f32, by: f32) -> f32 {
+    let (px, py) = (x - ax, y - ay);
+    let (dx, dy) = (bx - ax, by - ay);
+    let length2 = dx * dx + dy * dy;
+    let h = if length2 > 1e-6 { ((px * dx + py * dy) / length2).clamp(0.0, 1.0) } else { 0.0 };
+    let (ex, ey) = (px - dx * h, py - dy * h);
+    sqrtf(ex * ex + ey * ey)
+}
+
+/// The union of two shapes, `min(a, b)`, with the seam rounded off over a
+/// width of `|k|`. At `|k|` of `1e-6` or below it is exactly `Min`.
+pub fn smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    let k = k.abs();
+    if k <= 1e-6 {
+        return if a < b { a } else { b };
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+/// The intersection of two shapes, `max(a, b)`, rounded like [`smooth_union`].
+pub fn smooth_intersection(a: f32, b: f32, k: f32) -> f32 {
+    -smooth_union(-a, -b, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_are_negative_inside() {
+        assert_eq!(circle(0.0, 0.0, 0.0, 0.0, 0.5), -0.5);
+        assert_eq!(circle(1.0, 0.0, 0.0, 0.0, 0.5), 0.5);
+        assert_eq!(rectangle(0.0, 0.0, 0.0, 0.0, 0.5, -0.25), -0.25);
+        // Off a corner the distance is to the corner itself.
+        assert_eq!(rectangle(1.25, 1.5, 0.0, 0.0, 0.5, 0.5), 1.25);
+        assert_eq!(segment(0.5, 0.3, 0.0, 0.0, 1.0, 0.0), 0.3);
+        assert_eq!(segment(2.0, 0.0, 0.0, 0.0, 1.0, 0.0), 1.0);
+        // A zero-length segment is a point.
+        assert_eq!(segment(0.0, 0.5, 0.0, 0.0, 0.0, 0.0), 0.5);
+    }
+
+    #[test]
+    fn smooth_joins_round_only_the_seam() {
+        // Far apart, the smooth versions are plain min and max.
+        assert_eq!(smooth_union(-1.0, 1.0, 0.5), -1.0);
+        assert_eq!(smooth_intersection(-1.0, 1.0, 0.5), 1.0);
+        // On the seam they bulge by k / 4.
+        assert_eq!(smooth_union(0.0, 0.0, 0.5), -0.125);
+        assert_eq!(smooth_intersection(0.0, 0.0, 0.5), 0.125);
+        assert_eq!(smooth_union(0.25, 0.5, 0.0), 0.25);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::node::Node;
 use std::collections::BTreeMap;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Dependency {
     X,
     Y,
@@ -21,6 +21,8 @@ struct TreeStatsInner {
     leaf_depths: Vec<usize>,
     x_only_subtree_op_counts: Vec<usize>,
     y_only_subtree_op_counts: Vec<usize>,
+    /// What the `Let`s enclosing the node being visited depend on, innermost last.
+    bindings: Vec<(u32, Dependency)>,
 }
 
 impl TreeStatsInner {
@@ -44,11 +46,24 @@ impl TreeStatsInner {
                     stats.leaf_depths.push(depth);
                     return (Dependency::Y, 0);
                 }
-                Number(_) => {
+                // Fixed across any one image, like a number.
+                Number(_) | T => {
                     stats.leaf_nodes += 1;
                     stats.leaf_depths.push(depth);
                     return (Dependency::NO, 0);
                 }
+                // Z follows both coordinates under the sphere and cubemap projections.
+                R | Theta | Z => {
+                    stats.leaf_nodes += 1;
+                    stats.leaf_depths.push(depth);
+                    return (Dependency::XY, 0);
+                }
+                Var(id) => {
+                    stats.leaf_nodes += 1;
+                    stats.leaf_depths.push(depth);
+                    let bound = stats.bindings.iter().rev().find(|(bound, _)| bound == id);
+                    return (bound.expect("Var outside its Let").1, 0);
+                }
 
                 Add(a, b) => {
                     *stats.op_counts.entry("Add").or_default() += 1;
@@ -74,6 +89,25 @@ impl TreeStatsInner {
                     child_deps.extend([d1, d2]);
                     child_op_count += o1 + o2;
                 }
+                Min(a, b) | Max(a, b) | Mod(a, b) | Atan2(a, b) | Pow(a, b) | Step(a, b) | Noise(a, b, _)
+                | Texture(_, a, b) | Scale(a, b) | Xor(a, b) | And(a, b) => {
+                    *stats.op_counts.entry(node.name()).or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d1, o1) = helper(a, depth + 1, stats);
+                    let (d2, o2) = helper(b, depth + 1, stats);
+                    child_deps.extend([d1, d2]);
+                    child_op_count += o1 + o2;
+                }
+                IfPositive(a, b, c) | Smoothstep(a, b, c) | Warp(a, b, c) | SmoothUnion(a, b, c)
+                | SmoothIntersect(a, b, c) => {
+                    *stats.op_counts.entry(node.name()).or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d1, o1) = helper(a, depth + 1, stats);
+                    let (d2, o2) = helper(b, depth + 1, stats);
+                    let (d3, o3) = helper(c, depth + 1, stats);
+                    child_deps.extend([d1, d2, d3]);
+                    child_op_count += o1 + o2 + o3;
+                }
 
                 Sin(a) => {
                     *stats.op_counts.entry("Sin").or_default() += 1;
@@ -103,6 +137,61 @@ impl TreeStatsInner {
                     child_deps.push(d);
                     child_op_count += o;
                 }
+                Abs(a) | Tan(a) | Log(a) | Quantize(a, _) => {
+                    *stats.op_counts.entry(node.name()).or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d, o) = helper(a, depth + 1, stats);
+                    child_deps.push(d);
+                    child_op_count += o;
+                }
+                Rotate(a, b) => {
+                    *stats.op_counts.entry("Rotate").or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d1, o1) = helper(a, depth + 1, stats);
+                    let (d2, o2) = helper(b, depth + 1, stats);
+                    // Turning the point mixes x into y and back.
+                    let d2 = if d2 == Dependency::NO { d2 } else { Dependency::XY };
+                    child_deps.extend([d1, d2]);
+                    child_op_count += o1 + o2;
+                }
+                Circle(a, b, c) => {
+                    *stats.op_counts.entry("Circle").or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d1, o1) = helper(a, depth + 1, stats);
+                    let (d2, o2) = helper(b, depth + 1, stats);
+                    let (d3, o3) = helper(c, depth + 1, stats);
+                    // A distance reads the point whatever the shape's parameters read.
+                    child_deps.extend([d1, d2, d3, Dependency::XY]);
+                    child_op_count += o1 + o2 + o3;
+                }
+                BoxSdf(a, b, c, d) | Line(a, b, c, d) => {
+                    *stats.op_counts.entry(node.name()).or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d1, o1) = helper(a, depth + 1, stats);
+                    let (d2, o2) = helper(b, depth + 1, stats);
+                    let (d3, o3) = helper(c, depth + 1, stats);
+                    let (d4, o4) = helper(d, depth + 1, stats);
+                    child_deps.extend([d1, d2, d3, d4, Dependency::XY]);
+                    child_op_count += o1 + o2 + o3 + o4;
+                }
+                Iterate(a, _) | Escape(a, _) => {
+                    *stats.op_counts.entry(node.name()).or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d, o) = helper(a, depth + 1, stats);
+                    // The loop starts from x whatever the body reads.
+                    child_deps.extend([d, Dependency::X]);
+                    child_op_count += o;
+                }
+                Let(id, value, body) => {
+                    *stats.op_counts.entry("Let").or_default() += 1;
+                    stats.total_ops += 1;
+                    let (d1, o1) = helper(value, depth + 1, stats);
+                    stats.bindings.push((*id, d1));
+                    let (d2, o2) = helper(body, depth + 1, stats);
+                    stats.bindings.pop();
+                    child_deps.extend([d1, d2]);
+                    child_op_count += o1 + o2;
+                }
                 MixUnbounded(a, b, c, d) => {
                     *stats.op_counts.entry("MixUnbounded").or_default() += 1;
                     stats.total_ops += 1;
@@ -114,7 +203,7 @@ impl TreeStatsInner {
                     child_op_count += o1 + o2 + o3 + o4;
                 }
 
-                Rule(_) | Random | Triple(_, _, _) => unreachable!(),
+                Rule(_) | Random | Triple(_, _, _) | Mono(_) | Quad(..) => unreachable!(),
             };
 
             let unified = unify_deps(&child_deps);
@@ -219,31 +308,45 @@ impl TreeStatsInner {
 }
 
 pub struct TreeStats {
-    r: TreeStatsInner,
-    g: TreeStatsInner,
-    b: TreeStatsInner,
+    channels: Vec<(&'static str, TreeStatsInner)>,
 }
 
 impl TreeStats {
-    pub fn from_triple(node: &Node) -> Self {
-        let (r, g, b) = match &*node {
-            Node::Triple(r, g, b) => (r, g, b),
-            _ => panic!("Expected Triple node at top level"),
+    /// Statistics for each channel of a `Triple`, `Mono` or `Quad` root.
+    pub fn from_root(node: &Node) -> Self {
+        let channels = match node {
+            Node::Triple(r, g, b) => {
+                let (r, g): (TreeStatsInner, TreeStatsInner) = rayon::join(
+                    || TreeStatsInner::from_node(r),
+                    || TreeStatsInner::from_node(g),
+                );
+                let b = TreeStatsInner::from_node(b);
+                vec![("r", r), ("g", g), ("b", b)]
+            }
+            Node::Mono(v) => vec![("mono", TreeStatsInner::from_node(v))],
+            Node::Quad(r, g, b, a) => {
+                vec![("r", r), ("g", g), ("b", b), ("a", a)]
+                    .into_iter()
+                    .map(|(name, channel)| (name, TreeStatsInner::from_node(channel)))
+                    .collect()
+            }
+            _ => panic!("Expected Triple, Mono or Quad node at top level"),
         };
-        let (r, g): (TreeStatsInner, TreeStatsInner) = rayon::join(
-            || TreeStatsInner::from_node(r),
-            || TreeStatsInner::from_node(g),
-        );
-        let b = TreeStatsInner::from_node(b);
-        Self { r, g, b }
+        Self { channels }
+    }
+
+    #[deprecated(note = "renamed to `from_root`, which also takes `Mono` and `Quad` roots")]
+    pub fn from_triple(node: &Node) -> Self {
+        Self::from_root(node)
     }
 
     pub fn report(&self) {
-        println!("r channel:");
-        self.r.report();
-        println!("\ng channel:");
-        self.g.report();
-        println!("\nb channel:");
-        self.b.report();
+        for (i, (name, stats)) in self.channels.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            println!("{name} channel:");
+            stats.report();
+        }
     }
 }
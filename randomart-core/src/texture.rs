@@ -0,0 +1,182 @@
+//! Input images for [`Node::Texture`], looked up by the slot name the node
+//! carries. A texture covers the same `[-1, 1]` square as the rendered image,
+//! and a node samples the channel of the `Triple` component it is in, or the
+//! mean of the three under a `Mono` and in a `Quad`'s alpha.
+//!
+//! [`Node::Texture`]: crate::node::Node::Texture
+
+use crate::node::Node;
+use crate::pixel_buffer::PixelBuffer;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// How a [`Texture`] is read between its pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// The value of the closest pixel, for hard-edged blocks.
+    Nearest,
+    /// A blend of the four closest pixels.
+    #[default]
+    Bilinear,
+}
+
+/// The names [`Filter`] parses from.
+pub const FILTERS: &[&str] = &["nearest", "bilinear"];
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Filter::Nearest),
+            "bilinear" => Ok(Filter::Bilinear),
+            _ => Err(format!("unknown filter `{s}`, expected one of {}", FILTERS.join(", "))),
+        }
+    }
+}
+
+/// The channel [`Texture::sample`] reads as the mean of red, green and blue,
+/// which is what a `Texture` under a `Mono` root or in a `Quad`'s alpha sees.
+pub const MEAN: usize = 3;
+
+/// An image to sample, stretched over `[-1, 1]` in both directions with its
+/// edge pixels repeated beyond.
+pub struct Texture {
+    pixels: PixelBuffer,
+    filter: Filter,
+}
+
+impl Texture {
+    /// Panics if `pixels` is empty.
+    pub fn new(pixels: PixelBuffer, filter: Filter) -> Self {
+        assert!(pixels.width > 0 && pixels.height > 0, "a texture needs at least one pixel");
+        Self { pixels, filter }
+    }
+
+    /// Channel `channel` (0 for red, 1 for green, 2 for blue, 3 for their
+    /// mean) at `(x, y)`, in `[-1, 1]`. A byte is read as the middle of the
+    /// values that render to it, so a texture sampled at the pixel coordinates
+    /// of an image of its own size renders back to itself. NaN coordinates read
+    /// the first row or column.
+    pub fn sample(&self, channel: usize, x: f32, y: f32) -> f32 {
+        let (width, height) = (self.pixels.width, self.pixels.height);
+        let u = along(x, width);
+        let v = along(y, height);
+        let byte = |px: u32, py: u32| {
+            let idx = (py as usize * width as usize + px as usize) * self.pixels.channels as usize;
+            match channel {
+                MEAN => self.pixels.data[idx..idx + 3].iter().map(|&b| b as f32).sum::<f32>() / 3.0,
+                _ => self.pixels.data[idx + channel] as f32,
+            }
+        };
+        let value = match self.filter {
+            Filter::Nearest => byte(u.round() as u32, v.round() as u32),
+            Filter::Bilinear => {
+                let (x0, y0) = (u.floor(), v.floor());
+                let (fx, fy) = (u - x0, v - y0);
+                let (x0, y0) = (x0 as u32, y0 as u32);
+                let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                let top = byte(x0, y0) + (byte(x1, y0) - byte(x0, y0)) * fx;
+                let bottom = byte(x0, y1) + (byte(x1, y1) - byte(x0, y1)) * fx;
+                top + (bottom - top) * fy
+            }
+        };
+        (value + 0.5) / 127.5 - 1.0
+    }
+}
+
+/// Where `t` in `[-1, 1]` falls across `size` pixels, as a pixel position held
+/// inside the image.
+fn along(t: f32, size: u32) -> f32 {
+    let last = (size - 1) as f32;
+    ((t + 1.0) * 0.5 * last).max(0.0).min(last)
+}
+
+/// The textures a render can sample, by slot name.
+#[derive(Default)]
+pub struct Textures {
+    slots: BTreeMap<String, Texture>,
+}
+
+impl Textures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put `texture` in `slot`, replacing what was there.
+    pub fn insert(&mut self, slot: &str, texture: Texture) {
+        self.slots.insert(slot.to_string(), texture);
+    }
+
+    pub fn get(&self, slot: &str) -> Option<&Texture> {
+        self.slots.get(slot)
+    }
+
+    /// The first slot `tree` samples that has no texture, if any.
+    pub fn missing<'a>(&self, tree: &'a Node) -> Option<&'a str> {
+        if let Node::Texture(slot, ..) = tree {
+            if !self.slots.contains_key(slot) {
+                return Some(slot);
+            }
+        }
+        tree.children().into_iter().find_map(|child| self.missing(child))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2x2 image whose red channel runs 0, 100 along the top and 200, 255
+    /// along the bottom.
+    fn square(filter: Filter) -> Texture {
+        let mut pixels = PixelBuffer::new(2, 2);
+        pixels.put_pixel(0, 0, 0, 1, 2);
+        pixels.put_pixel(1, 0, 100, 1, 2);
+        pixels.put_pixel(0, 1, 200, 1, 2);
+        pixels.put_pixel(1, 1, 255, 1, 2);
+        Texture::new(pixels, filter)
+    }
+
+    /// What a byte reads as.
+    fn read(byte: f32) -> f32 {
+        (byte + 0.5) / 127.5 - 1.0
+    }
+
+    #[test]
+    fn corners_read_their_pixels() {
+        for filter in [Filter::Nearest, Filter::Bilinear] {
+            let texture = square(filter);
+            assert_eq!(texture.sample(0, -1.0, -1.0), read(0.0));
+            assert_eq!(texture.sample(0, 1.0, -1.0), read(100.0));
+            assert_eq!(texture.sample(0, -1.0, 1.0), read(200.0));
+            assert_eq!(texture.sample(2, 1.0, 1.0), read(2.0));
+            assert_eq!(texture.sample(MEAN, -1.0, -1.0), read(1.0));
+            // Beyond the edges and NaN stay on the image.
+            assert_eq!(texture.sample(0, 5.0, -7.0), read(100.0));
+            assert_eq!(texture.sample(0, f32::NAN, f32::NAN), read(0.0));
+        }
+    }
+
+    #[test]
+    fn filters_differ_between_pixels() {
+        assert_eq!(square(Filter::Nearest).sample(0, -0.2, -1.0), read(0.0));
+        assert_eq!(square(Filter::Bilinear).sample(0, 0.0, -1.0), read(50.0));
+        assert_eq!(square(Filter::Bilinear).sample(0, 0.0, 0.0), read(138.75));
+    }
+
+    #[test]
+    fn finds_slots_without_textures() {
+        let mut textures = Textures::new();
+        textures.insert("photo", square(Filter::Nearest));
+        let tree = Node::Add(
+            Box::new(Node::Texture("photo".into(), Box::new(Node::X), Box::new(Node::Y))),
+            Box::new(Node::Sin(Box::new(Node::Texture("logo".into(), Box::new(Node::Y), Box::new(Node::X))))),
+        );
+        assert_eq!(textures.missing(&tree), Some("logo"));
+        textures.insert("logo", square(Filter::Bilinear));
+        assert_eq!(textures.missing(&tree), None);
+        assert_eq!("nearest".parse(), Ok(Filter::Nearest));
+        assert!("cubic".parse::<Filter>().is_err());
+    }
+}
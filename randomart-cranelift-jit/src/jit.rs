@@ -1,8 +1,9 @@
 use cranelift::prelude::*;
 use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{Module, Linkage};
+use cranelift_module::{DataDescription, Module, Linkage};
 use randomart_core::node::Node;
-use randomart_core::math;
+use randomart_core::{math, noise, sdf};
+use randomart_core::texture::{Texture, Textures};
 
 macro_rules! define_and_register_math_fns {
     ($builder:ident, [$(($name:ident, $ret:ty, [$($arg:ident : $typ:ty),*], $body:block)),* $(,)?]) => {
@@ -36,59 +37,120 @@ macro_rules! call_imported_func {
     }};
 }
 
+/// What a node is emitted inside: the point's depth and the time, which no
+/// node moves, the values of the enclosing `Let`s by id, innermost last, the
+/// number of loop variables declared so far, and the textures and channel a
+/// `Texture` samples.
+struct Scope<'a> {
+    z: Value,
+    t: Value,
+    lets: Vec<(u32, Value)>,
+    variables: usize,
+    textures: &'a Textures,
+    channel: usize,
+}
+
+/// Emit `node` evaluated at `(x, y)` inside `scope`.
 fn codegen_node(
     builder: &mut FunctionBuilder,
     module: &mut JITModule,
     node: &Node,
     x: Value,
     y: Value,
+    scope: &mut Scope,
 ) -> Value {
     use cranelift::prelude::*;
 
     match node {
         Node::X => x,
         Node::Y => y,
+        Node::Z => scope.z,
+        Node::T => scope.t,
+
+        Node::R => {
+            let xx = builder.ins().fmul(x, x);
+            let yy = builder.ins().fmul(y, y);
+            let sum = builder.ins().fadd(xx, yy);
+            builder.ins().sqrt(sum)
+        }
+
+        Node::Theta => {
+            call_imported_func!(builder, module, "my_atan2", [y, x], [types::F32, types::F32], types::F32)
+        }
         Node::Number(val) => builder.ins().f32const(Ieee32::with_float(*val)),
 
         Node::Add(a, b) => {
-            let lhs = codegen_node(builder, module, a, x, y);
-            let rhs = codegen_node(builder, module, b, x, y);
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
             let sum = builder.ins().fadd(lhs, rhs);
             let two = builder.ins().f32const(Ieee32::with_float(2.0));
             builder.ins().fdiv(sum, two)
         }
 
         Node::Mult(a, b) => {
-            let lhs = codegen_node(builder, module, a, x, y);
-            let rhs = codegen_node(builder, module, b, x, y);
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
             builder.ins().fmul(lhs, rhs)
         }
 
         Node::Sin(inner) => {
-            let arg = codegen_node(builder, module, inner, x, y);
+            let arg = codegen_node(builder, module, inner, x, y, scope);
             call_imported_func!(builder, module, "my_sin", [arg], [types::F32], types::F32)
         }
 
         Node::Cos(inner) => {
-            let arg = codegen_node(builder, module, inner, x, y);
+            let arg = codegen_node(builder, module, inner, x, y, scope);
             call_imported_func!(builder, module, "my_cos", [arg], [types::F32], types::F32)
         }
 
         Node::Sqrt(inner) => {
-            let arg = codegen_node(builder, module, inner, x, y);
+            let arg = codegen_node(builder, module, inner, x, y, scope);
             let zero = builder.ins().f32const(Ieee32::with_float(0.0));
             let safe = builder.ins().fmax(arg, zero);
             builder.ins().sqrt(safe)
         }
 
         Node::Exp(inner) => {
-            let arg = codegen_node(builder, module, inner, x, y);
+            let arg = codegen_node(builder, module, inner, x, y, scope);
             call_imported_func!(builder, module, "my_exp", [arg], [types::F32], types::F32)
         }
 
+        Node::Abs(inner) => {
+            let arg = codegen_node(builder, module, inner, x, y, scope);
+            builder.ins().fabs(arg)
+        }
+
+        Node::Tan(inner) => {
+            let arg = codegen_node(builder, module, inner, x, y, scope);
+            call_imported_func!(builder, module, "my_tan", [arg], [types::F32], types::F32)
+        }
+
+        Node::Log(inner) => {
+            let arg = codegen_node(builder, module, inner, x, y, scope);
+            call_imported_func!(builder, module, "my_log", [arg], [types::F32], types::F32)
+        }
+
+        Node::Mod(a, b) => {
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
+            call_imported_func!(builder, module, "my_mod", [lhs, rhs], [types::F32, types::F32], types::F32)
+        }
+
+        Node::Atan2(a, b) => {
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
+            call_imported_func!(builder, module, "my_atan2", [lhs, rhs], [types::F32, types::F32], types::F32)
+        }
+
+        Node::Pow(a, b) => {
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
+            call_imported_func!(builder, module, "my_pow", [lhs, rhs], [types::F32, types::F32], types::F32)
+        }
+
         Node::Div(a, b) => {
-            let lhs = codegen_node(builder, module, a, x, y);
-            let rhs = codegen_node(builder, module, b, x, y);
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
             let threshold = builder.ins().f32const(Ieee32::with_float(1e-6));
             let zero = builder.ins().f32const(Ieee32::with_float(0.0));
             let abs_rhs = builder.ins().fabs(rhs);
@@ -97,11 +159,142 @@ fn codegen_node(
             builder.ins().select(cond, quot, zero)
         }
 
+        Node::Min(a, b) => {
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
+            let cond = builder.ins().fcmp(FloatCC::LessThan, lhs, rhs);
+            builder.ins().select(cond, lhs, rhs)
+        }
+
+        Node::Max(a, b) => {
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
+            let cond = builder.ins().fcmp(FloatCC::GreaterThan, lhs, rhs);
+            builder.ins().select(cond, lhs, rhs)
+        }
+
+        Node::Step(edge, v) => {
+            let edge = codegen_node(builder, module, edge, x, y, scope);
+            let v = codegen_node(builder, module, v, x, y, scope);
+            let below = builder.ins().f32const(Ieee32::with_float(-1.0));
+            let above = builder.ins().f32const(Ieee32::with_float(1.0));
+            let cond = builder.ins().fcmp(FloatCC::LessThan, v, edge);
+            builder.ins().select(cond, below, above)
+        }
+
+        Node::Noise(a, b, perm) => {
+            let nx = codegen_node(builder, module, a, x, y, scope);
+            let ny = codegen_node(builder, module, b, x, y, scope);
+            // The table goes in the module's own data so it outlives `node`.
+            let data = module.declare_anonymous_data(false, false).unwrap();
+            let mut description = DataDescription::new();
+            description.define(Box::new(*perm.table()));
+            module.define_data(data, &description).unwrap();
+            let global = module.declare_data_in_func(data, builder.func);
+            let ptr_ty = module.target_config().pointer_type();
+            let table = builder.ins().global_value(ptr_ty, global);
+            call_imported_func!(builder, module, "my_noise", [nx, ny, table], [types::F32, types::F32, ptr_ty], types::F32)
+        }
+
+        Node::Texture(slot, a, b) => {
+            let sx = codegen_node(builder, module, a, x, y, scope);
+            let sy = codegen_node(builder, module, b, x, y, scope);
+            // The compiled function borrows `textures`, so the address stays good while it runs.
+            let texture: *const Texture = scope.textures.get(slot).expect("render checks every slot has a texture");
+            let ptr_ty = module.target_config().pointer_type();
+            let texture = builder.ins().iconst(ptr_ty, texture as i64);
+            let channel = builder.ins().iconst(types::I32, scope.channel as i64);
+            call_imported_func!(
+                builder,
+                module,
+                "my_texture",
+                [texture, channel, sx, sy],
+                [ptr_ty, types::I32, types::F32, types::F32],
+                types::F32
+            )
+        }
+
+        Node::Rotate(angle, body) => {
+            // Step for step the same as `math::rotate`.
+            let half_turns = codegen_node(builder, module, angle, x, y, scope);
+            let pi = builder.ins().f32const(Ieee32::with_float(std::f32::consts::PI));
+            let radians = builder.ins().fmul(half_turns, pi);
+            let c = call_imported_func!(builder, module, "my_cos", [radians], [types::F32], types::F32);
+            let s = call_imported_func!(builder, module, "my_sin", [radians], [types::F32], types::F32);
+            let xc = builder.ins().fmul(x, c);
+            let ys = builder.ins().fmul(y, s);
+            let xs = builder.ins().fmul(x, s);
+            let yc = builder.ins().fmul(y, c);
+            let wx = builder.ins().fsub(xc, ys);
+            let wy = builder.ins().fadd(xs, yc);
+            codegen_node(builder, module, body, wx, wy, scope)
+        }
+
+        Node::Scale(factor, body) => {
+            let factor = codegen_node(builder, module, factor, x, y, scope);
+            let threshold = builder.ins().f32const(Ieee32::with_float(1e-6));
+            let zero = builder.ins().f32const(Ieee32::with_float(0.0));
+            let abs_factor = builder.ins().fabs(factor);
+            let cond = builder.ins().fcmp(FloatCC::GreaterThan, abs_factor, threshold);
+            let qx = builder.ins().fdiv(x, factor);
+            let qy = builder.ins().fdiv(y, factor);
+            let wx = builder.ins().select(cond, qx, zero);
+            let wy = builder.ins().select(cond, qy, zero);
+            codegen_node(builder, module, body, wx, wy, scope)
+        }
+
+        Node::Warp(dx, dy, body) => {
+            let dx = codegen_node(builder, module, dx, x, y, scope);
+            let dy = codegen_node(builder, module, dy, x, y, scope);
+            let wx = builder.ins().fadd(x, dx);
+            let wy = builder.ins().fadd(y, dy);
+            codegen_node(builder, module, body, wx, wy, scope)
+        }
+
+        Node::IfPositive(cond, a, b) => {
+            let vc = codegen_node(builder, module, cond, x, y, scope);
+            let va = codegen_node(builder, module, a, x, y, scope);
+            let vb = codegen_node(builder, module, b, x, y, scope);
+            let zero = builder.ins().f32const(Ieee32::with_float(0.0));
+            let positive = builder.ins().fcmp(FloatCC::GreaterThan, vc, zero);
+            builder.ins().select(positive, va, vb)
+        }
+
+        Node::Smoothstep(e0, e1, v) => {
+            // Step for step the same as `math::smoothstep`.
+            let e0 = codegen_node(builder, module, e0, x, y, scope);
+            let e1 = codegen_node(builder, module, e1, x, y, scope);
+            let v = codegen_node(builder, module, v, x, y, scope);
+            let zero = builder.ins().f32const(Ieee32::with_float(0.0));
+            let one = builder.ins().f32const(Ieee32::with_float(1.0));
+            let two = builder.ins().f32const(Ieee32::with_float(2.0));
+            let three = builder.ins().f32const(Ieee32::with_float(3.0));
+            let threshold = builder.ins().f32const(Ieee32::with_float(1e-6));
+
+            let width = builder.ins().fsub(e1, e0);
+            let abs_width = builder.ins().fabs(width);
+            let wide = builder.ins().fcmp(FloatCC::GreaterThan, abs_width, threshold);
+            let offset = builder.ins().fsub(v, e0);
+            let quot = builder.ins().fdiv(offset, width);
+            let t = builder.ins().select(wide, quot, zero);
+            let below = builder.ins().fcmp(FloatCC::LessThan, t, zero);
+            let t = builder.ins().select(below, zero, t);
+            let above = builder.ins().fcmp(FloatCC::GreaterThan, t, one);
+            let t = builder.ins().select(above, one, t);
+
+            let tt = builder.ins().fmul(t, t);
+            let two_t = builder.ins().fmul(two, t);
+            let rest = builder.ins().fsub(three, two_t);
+            let eased = builder.ins().fmul(tt, rest);
+            let doubled = builder.ins().fmul(two, eased);
+            builder.ins().fsub(doubled, one)
+        }
+
         Node::MixUnbounded(a, b, c, d) => {
-            let va = codegen_node(builder, module, a, x, y);
-            let vb = codegen_node(builder, module, b, x, y);
-            let vc = codegen_node(builder, module, c, x, y);
-            let vd = codegen_node(builder, module, d, x, y);
+            let va = codegen_node(builder, module, a, x, y, scope);
+            let vb = codegen_node(builder, module, b, x, y, scope);
+            let vc = codegen_node(builder, module, c, x, y, scope);
+            let vd = codegen_node(builder, module, d, x, y, scope);
             let eps = builder.ins().f32const(Ieee32::with_float(1e-6));
             let rac = builder.ins().fmul(va, vc);
             let rbd = builder.ins().fmul(vb, vd);
@@ -111,8 +304,120 @@ fn codegen_node(
             builder.ins().fdiv(num, denom)
         }
 
-        Node::Triple(_, _, _) => {
-            panic!("Triple node should be handled at the top level, not in scalar codegen")
+        Node::Circle(cx, cy, r) => {
+            let cx = codegen_node(builder, module, cx, x, y, scope);
+            let cy = codegen_node(builder, module, cy, x, y, scope);
+            let r = codegen_node(builder, module, r, x, y, scope);
+            call_imported_func!(
+                builder,
+                module,
+                "my_circle",
+                [x, y, cx, cy, r],
+                [types::F32, types::F32, types::F32, types::F32, types::F32],
+                types::F32
+            )
+        }
+
+        Node::BoxSdf(cx, cy, w, h) => {
+            let cx = codegen_node(builder, module, cx, x, y, scope);
+            let cy = codegen_node(builder, module, cy, x, y, scope);
+            let w = codegen_node(builder, module, w, x, y, scope);
+            let h = codegen_node(builder, module, h, x, y, scope);
+            call_imported_func!(
+                builder,
+                module,
+                "my_box",
+                [x, y, cx, cy, w, h],
+                [types::F32, types::F32, types::F32, types::F32, types::F32, types::F32],
+                types::F32
+            )
+        }
+
+        Node::Line(ax, ay, bx, by) => {
+            let ax = codegen_node(builder, module, ax, x, y, scope);
+            let ay = codegen_node(builder, module, ay, x, y, scope);
+            let bx = codegen_node(builder, module, bx, x, y, scope);
+            let by = codegen_node(builder, module, by, x, y, scope);
+            call_imported_func!(
+                builder,
+                module,
+                "my_segment",
+                [x, y, ax, ay, bx, by],
+                [types::F32, types::F32, types::F32, types::F32, types::F32, types::F32],
+                types::F32
+            )
+        }
+
+        Node::SmoothUnion(a, b, k) => {
+            let a = codegen_node(builder, module, a, x, y, scope);
+            let b = codegen_node(builder, module, b, x, y, scope);
+            let k = codegen_node(builder, module, k, x, y, scope);
+            call_imported_func!(builder, module, "my_smooth_union", [a, b, k], [types::F32, types::F32, types::F32], types::F32)
+        }
+
+        Node::SmoothIntersect(a, b, k) => {
+            let a = codegen_node(builder, module, a, x, y, scope);
+            let b = codegen_node(builder, module, b, x, y, scope);
+            let k = codegen_node(builder, module, k, x, y, scope);
+            call_imported_func!(builder, module, "my_smooth_intersection", [a, b, k], [types::F32, types::F32, types::F32], types::F32)
+        }
+
+        Node::Iterate(..) => {
+            let (v, _) = codegen_loop(builder, module, node, x, y, scope);
+            v
+        }
+
+        Node::Escape(_, n) => {
+            if *n == 0 {
+                return builder.ins().f32const(Ieee32::with_float(math::escape_time(0, 0)));
+            }
+            // Step for step the same as `math::escape_time`.
+            let (_, steps) = codegen_loop(builder, module, node, x, y, scope);
+            let steps = builder.ins().fcvt_from_uint(types::F32, steps);
+            let two = builder.ins().f32const(Ieee32::with_float(2.0));
+            let limit = builder.ins().f32const(Ieee32::with_float(*n as f32));
+            let one = builder.ins().f32const(Ieee32::with_float(1.0));
+            let doubled = builder.ins().fmul(two, steps);
+            let fraction = builder.ins().fdiv(doubled, limit);
+            builder.ins().fsub(fraction, one)
+        }
+
+        Node::Quantize(inner, levels) => {
+            if *levels < 2 {
+                return builder.ins().f32const(Ieee32::with_float(math::quantize(0.0, *levels)));
+            }
+            let v = codegen_node(builder, module, inner, x, y, scope);
+            let level = codegen_to_level(builder, v, *levels);
+            codegen_from_level(builder, level, *levels)
+        }
+
+        Node::Xor(a, b) | Node::And(a, b) => {
+            let lhs = codegen_node(builder, module, a, x, y, scope);
+            let rhs = codegen_node(builder, module, b, x, y, scope);
+            let lhs = codegen_to_level(builder, lhs, math::BIT_LEVELS);
+            let rhs = codegen_to_level(builder, rhs, math::BIT_LEVELS);
+            let bits = match node {
+                Node::Xor(..) => builder.ins().bxor(lhs, rhs),
+                _ => builder.ins().band(lhs, rhs),
+            };
+            codegen_from_level(builder, bits, math::BIT_LEVELS)
+        }
+
+        Node::Var(id) => {
+            let (_, value) = scope.lets.iter().rev().find(|(bound, _)| bound == id).expect("Var outside its Let");
+            *value
+        }
+
+        Node::Let(id, value, body) => {
+            let value = codegen_node(builder, module, value, x, y, scope);
+            scope.lets.push((*id, value));
+            let result = codegen_node(builder, module, body, x, y, scope);
+            scope.lets.pop();
+            result
+        }
+
+        Node::Triple(_, _, _) | Node::Mono(_) | Node::Quad(..) => {
+            panic!("{} node should be handled at the top level, not in scalar codegen", node.name())
         }
 
         Node::Random => {
@@ -125,7 +430,95 @@ fn codegen_node(
     }
 }
 
-fn build_jit_function(ast: &Node) -> Box<dyn Fn(f32, f32) -> f32 + Sync + Send> {
+/// Emit the loop of an `Iterate` or `Escape` node: evaluate the body at
+/// `(v, y)` and feed the result back as `v`, starting from `x`, for up to its
+/// number of passes, stopping early for `Escape` once `|v|` passes
+/// `math::ESCAPE_RADIUS`. Returns the final `v` and the number of passes made.
+fn codegen_loop(
+    builder: &mut FunctionBuilder,
+    module: &mut JITModule,
+    node: &Node,
+    x: Value,
+    y: Value,
+    scope: &mut Scope,
+) -> (Value, Value) {
+    let (body, passes, escape) = match node {
+        Node::Iterate(body, n) => (body, *n, false),
+        Node::Escape(body, n) => (body, *n, true),
+        _ => unreachable!("codegen_loop is only called for loops"),
+    };
+    let header = builder.create_block();
+    let step = builder.create_block();
+    let exit = builder.create_block();
+
+    let coord = Variable::new(scope.variables);
+    let count = Variable::new(scope.variables + 1);
+    scope.variables += 2;
+    builder.declare_var(coord, types::F32);
+    builder.declare_var(count, types::I32);
+    builder.def_var(coord, x);
+    let zero = builder.ins().iconst(types::I32, 0);
+    builder.def_var(count, zero);
+    builder.ins().jump(header, &[]);
+
+    builder.switch_to_block(header);
+    let v = builder.use_var(coord);
+    let k = builder.use_var(count);
+    let mut go = builder.ins().icmp_imm(IntCC::UnsignedLessThan, k, i64::from(passes));
+    if escape {
+        let magnitude = builder.ins().fabs(v);
+        let radius = builder.ins().f32const(Ieee32::with_float(math::ESCAPE_RADIUS));
+        let inside = builder.ins().fcmp(FloatCC::LessThanOrEqual, magnitude, radius);
+        go = builder.ins().band(go, inside);
+    }
+    builder.ins().brif(go, step, &[], exit, &[]);
+
+    builder.switch_to_block(step);
+    builder.seal_block(step);
+    let next = codegen_node(builder, module, body, v, y, scope);
+    builder.def_var(coord, next);
+    let next_k = builder.ins().iadd_imm(k, 1);
+    builder.def_var(count, next_k);
+    builder.ins().jump(header, &[]);
+    builder.seal_block(header);
+
+    builder.switch_to_block(exit);
+    builder.seal_block(exit);
+    (builder.use_var(coord), builder.use_var(count))
+}
+
+/// Emit `math::to_level` of `v` step for step, giving an `I32`.
+fn codegen_to_level(builder: &mut FunctionBuilder, v: Value, levels: u32) -> Value {
+    let one = builder.ins().f32const(Ieee32::with_float(1.0));
+    let half = builder.ins().f32const(Ieee32::with_float(0.5));
+    let count = builder.ins().f32const(Ieee32::with_float(levels as f32));
+    let shifted = builder.ins().fadd(v, one);
+    let halved = builder.ins().fmul(shifted, half);
+    let scaled = builder.ins().fmul(halved, count);
+    let floored = builder.ins().floor(scaled);
+    // Saturates and sends NaN to zero, as Rust's `as i32` does.
+    let level = builder.ins().fcvt_to_sint_sat(types::I32, floored);
+    let lowest = builder.ins().iconst(types::I32, 0);
+    let highest = builder.ins().iconst(types::I32, i64::from((levels - 1) as i32));
+    let level = builder.ins().smax(level, lowest);
+    builder.ins().smin(level, highest)
+}
+
+/// Emit `math::from_level` of the `I32` `level` step for step.
+fn codegen_from_level(builder: &mut FunctionBuilder, level: Value, levels: u32) -> Value {
+    let level = builder.ins().fcvt_from_sint(types::F32, level);
+    let two = builder.ins().f32const(Ieee32::with_float(2.0));
+    let spacing = builder.ins().f32const(Ieee32::with_float((levels - 1) as f32));
+    let one = builder.ins().f32const(Ieee32::with_float(1.0));
+    let doubled = builder.ins().fmul(two, level);
+    let fraction = builder.ins().fdiv(doubled, spacing);
+    builder.ins().fsub(fraction, one)
+}
+
+/// Compile `ast` as channel `channel` of a `Triple`, or the `MEAN` channel of a
+/// `Mono` or a `Quad`'s alpha. The function samples `textures` in place, so it
+/// may not outlive them.
+pub(crate) fn build_jit_function<'a>(ast: &Node, textures: &'a Textures, channel: usize) -> Box<dyn Fn(f32, f32, f32, f32) -> f32 + Sync + Send + 'a> {
     let mut builder = JITBuilder::new(cranelift_module::default_libcall_names())
         .expect("Failed to create JITBuilder");
 
@@ -133,6 +526,20 @@ fn build_jit_function(ast: &Node) -> Box<dyn Fn(f32, f32) -> f32 + Sync + Send>
         (my_sin, f32, [x: f32], { math::sinf(x) }),
         (my_cos, f32, [x: f32], { math::cosf(x) }),
         (my_exp, f32, [x: f32], { math::expf(x) }),
+        (my_tan, f32, [x: f32], { math::tanf(x) }),
+        (my_log, f32, [x: f32], { math::guarded_log(x) }),
+        (my_mod, f32, [a: f32, b: f32], { math::guarded_mod(a, b) }),
+        (my_atan2, f32, [y: f32, x: f32], { math::atan2f(y, x) }),
+        (my_pow, f32, [a: f32, b: f32], { math::guarded_pow(a, b) }),
+        (my_noise, f32, [x: f32, y: f32, table: *const [u8; 256]], { noise::noise(x, y, unsafe { &*table }) }),
+        (my_texture, f32, [texture: *const Texture, channel: u32, x: f32, y: f32], {
+            unsafe { &*texture }.sample(channel as usize, x, y)
+        }),
+        (my_circle, f32, [x: f32, y: f32, cx: f32, cy: f32, r: f32], { sdf::circle(x, y, cx, cy, r) }),
+        (my_box, f32, [x: f32, y: f32, cx: f32, cy: f32, w: f32, h: f32], { sdf::rectangle(x, y, cx, cy, w, h) }),
+        (my_segment, f32, [x: f32, y: f32, ax: f32, ay: f32, bx: f32, by: f32], { sdf::segment(x, y, ax, ay, bx, by) }),
+        (my_smooth_union, f32, [a: f32, b: f32, k: f32], { sdf::smooth_union(a, b, k) }),
+        (my_smooth_intersection, f32, [a: f32, b: f32, k: f32], { sdf::smooth_intersection(a, b, k) }),
     ]);
 
     let mut module = JITModule::new(builder);
@@ -140,6 +547,8 @@ fn build_jit_function(ast: &Node) -> Box<dyn Fn(f32, f32) -> f32 + Sync + Send>
     let mut sig = module.make_signature();
     sig.params.push(AbiParam::new(types::F32));
     sig.params.push(AbiParam::new(types::F32));
+    sig.params.push(AbiParam::new(types::F32));
+    sig.params.push(AbiParam::new(types::F32));
     sig.returns.push(AbiParam::new(types::F32));
 
     let func_id = module
@@ -159,7 +568,10 @@ fn build_jit_function(ast: &Node) -> Box<dyn Fn(f32, f32) -> f32 + Sync + Send>
 
     let x = fb.block_params(block)[0];
     let y = fb.block_params(block)[1];
-    let result = codegen_node(&mut fb, &mut module, ast, x, y);
+    let z = fb.block_params(block)[2];
+    let t = fb.block_params(block)[3];
+    let mut scope = Scope { z, t, lets: Vec::new(), variables: 0, textures, channel };
+    let result = codegen_node(&mut fb, &mut module, ast, x, y, &mut scope);
     fb.ins().return_(&[result]);
     fb.finalize();
 
@@ -168,28 +580,28 @@ fn build_jit_function(ast: &Node) -> Box<dyn Fn(f32, f32) -> f32 + Sync + Send>
     let _ = module.finalize_definitions();
 
     let code = module.get_finalized_function(func_id);
-    let fn_ptr = unsafe { std::mem::transmute::<_, fn(f32, f32) -> f32>(code) };
-    Box::new(fn_ptr) as Box<dyn Fn(f32, f32) -> f32 + Sync + Send>
+    let fn_ptr = unsafe { std::mem::transmute::<_, fn(f32, f32, f32, f32) -> f32>(code) };
+    Box::new(fn_ptr) as Box<dyn Fn(f32, f32, f32, f32) -> f32 + Sync + Send + 'a>
 }
 
-pub(crate) fn build_jit_function_triple(node: &Node)
+pub(crate) fn build_jit_function_triple<'a>(node: &Node, textures: &'a Textures)
 -> (
-    Box<dyn Fn(f32, f32) -> f32 + Sync + Send>,
-    Box<dyn Fn(f32, f32) -> f32 + Sync + Send>,
-    Box<dyn Fn(f32, f32) -> f32 + Sync + Send>,
+    Box<dyn Fn(f32, f32, f32, f32) -> f32 + Sync + Send + 'a>,
+    Box<dyn Fn(f32, f32, f32, f32) -> f32 + Sync + Send + 'a>,
+    Box<dyn Fn(f32, f32, f32, f32) -> f32 + Sync + Send + 'a>,
 )
 {
     let (r, g, b) = match &*node {
-        Node::Triple(r, g, b) => (r, g, b),
-        _ => panic!("Expected Triple node at top level"),
+        Node::Triple(r, g, b) | Node::Quad(r, g, b, _) => (r, g, b),
+        _ => panic!("Expected Triple or Quad node at top level"),
     };
     let (r_jit_fn, g_jit_fn): (
-        Box<dyn Fn(f32, f32) -> f32 + Sync + Send>,
-        Box<dyn Fn(f32, f32) -> f32 + Sync + Send>
+        Box<dyn Fn(f32, f32, f32, f32) -> f32 + Sync + Send + 'a>,
+        Box<dyn Fn(f32, f32, f32, f32) -> f32 + Sync + Send + 'a>
     ) = rayon::join(
-        || build_jit_function(r),
-        || build_jit_function(g)
+        || build_jit_function(r, textures, 0),
+        || build_jit_function(g, textures, 1)
     );
-    let b_jit_fn = build_jit_function(b);
+    let b_jit_fn = build_jit_function(b, textures, 2);
     (r_jit_fn, g_jit_fn, b_jit_fn)
 }
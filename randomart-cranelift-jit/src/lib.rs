@@ -1,50 +1,102 @@
 mod jit;
 
-use crate::jit::build_jit_function_triple;
+use crate::jit::{build_jit_function, build_jit_function_triple};
 use randomart_core::{
-    grammar::generate_tree_parallel,
+    formula::Formula,
+    grammar::{generate_tree_parallel, Grammar},
+    colormap::Colormap,
     node::Node,
-    pixel_buffer::{GenerateOutput, ReadOutput},
-    render::{render_tiled, Colour, PixelCoordinates},
+    pixel_buffer::{GenerateOutput, PixelBuffer, ReadOutput},
+    render::{render_frames as core_render_frames, render_tiled_projected, Channels, Colour, PixelCoordinates, Projection},
+    texture::{Textures, MEAN},
 };
 use anyhow::{bail, Context, Result};
 use xxhash_rust::xxh3::xxh3_64;
 
+pub fn render(node: &Node, textures: &Textures, width: u32, height: u32) -> Result<PixelBuffer> {
+    let mut images = render_projected(node, textures, &Colormap::default(), &[Projection::Plane(0.0)], width, height)?;
+    Ok(images.remove(0))
+}
+
+/// One image of `node` per entry of `projections`, compiling it once. A `Mono`
+/// root is coloured by `colormap`, and a `Quad` root renders RGBA images.
+pub fn render_projected(
+    node: &Node,
+    textures: &Textures,
+    colormap: &Colormap,
+    projections: &[Projection],
+    width: u32,
+    height: u32,
+) -> Result<Vec<PixelBuffer>> {
+    let rgb_fn = compile_root(node, textures, colormap)?;
+    Ok(projections.iter().map(|&projection| render_tiled_projected(&rgb_fn, projection, Channels::of(node), width, height)).collect())
+}
+
+/// One frame of the flat image of `node` per entry of `times`, compiling it
+/// once.
+pub fn render_frames(
+    node: &Node,
+    textures: &Textures,
+    colormap: &Colormap,
+    times: &[f32],
+    width: u32,
+    height: u32,
+) -> Result<Vec<PixelBuffer>> {
+    let rgb_fn = compile_root(node, textures, colormap)?;
+    Ok(core_render_frames(&rgb_fn, times, Channels::of(node), width, height))
+}
+
+type RootFn<'a> = Box<dyn Fn(PixelCoordinates) -> Colour + Sync + 'a>;
+
+fn compile_root<'a>(node: &Node, textures: &'a Textures, colormap: &'a Colormap) -> Result<RootFn<'a>> {
+    if !matches!(node, Node::Triple(_, _, _) | Node::Mono(_) | Node::Quad(..)) {
+        bail!("top-level node must be a Triple, Mono or Quad");
+    }
+    if let Some(slot) = textures.missing(node) {
+        bail!("no texture given for slot `{slot}`");
+    }
+
+    if let Node::Mono(v) = node {
+        let v_jit_fn = build_jit_function(v, textures, MEAN);
+        return Ok(Box::new(move |coord: PixelCoordinates| {
+            colormap.colour(v_jit_fn(coord.x, coord.y, coord.z, coord.t))
+        }));
+    }
+    if let Node::Quad(_, _, _, a) = node {
+        let (r_jit_fn, g_jit_fn, b_jit_fn) = build_jit_function_triple(node, textures);
+        let a_jit_fn = build_jit_function(a, textures, MEAN);
+        return Ok(Box::new(move |coord: PixelCoordinates| Colour {
+            r: r_jit_fn(coord.x, coord.y, coord.z, coord.t),
+            g: g_jit_fn(coord.x, coord.y, coord.z, coord.t),
+            b: b_jit_fn(coord.x, coord.y, coord.z, coord.t),
+            a: a_jit_fn(coord.x, coord.y, coord.z, coord.t),
+        }));
+    }
+    let (r_jit_fn, g_jit_fn, b_jit_fn) = build_jit_function_triple(node, textures);
+    Ok(Box::new(move |coord: PixelCoordinates| Colour {
+        r: r_jit_fn(coord.x, coord.y, coord.z, coord.t),
+        g: g_jit_fn(coord.x, coord.y, coord.z, coord.t),
+        b: b_jit_fn(coord.x, coord.y, coord.z, coord.t),
+        a: 1.0,
+    }))
+}
+
 pub fn generate(string: &str, depth: u32, width: u32, height: u32) -> Result<GenerateOutput> {
     let seed: u64 = xxh3_64(string.as_bytes());
-    let mut node = generate_tree_parallel(seed, depth)
+    let grammar = Grammar::default(seed);
+    let mut node = generate_tree_parallel(&grammar, seed, depth)
         .context("tree generation failed")?;
-    node.simplify_triple();
+    node.simplify_root();
 
-    let json = serde_json::to_string_pretty(&*node)
+    let pixels = render(&node, &Textures::new(), width, height)?;
+    let json = Formula::new(*node, &grammar).to_json()
         .context("failed to serialize node tree")?;
-
-    let (r_jit_fn, g_jit_fn, b_jit_fn) = build_jit_function_triple(&node);
-    let rgb_fn = |coord: PixelCoordinates| Colour {
-        r: r_jit_fn(coord.x, coord.y),
-        g: g_jit_fn(coord.x, coord.y),
-        b: b_jit_fn(coord.x, coord.y),
-    };
-
-    let pixels = render_tiled(&rgb_fn, width, height);
     Ok(GenerateOutput { pixels, json })
 }
 
 pub fn read_json(json: &str, width: u32, height: u32) -> Result<ReadOutput> {
-    let node: Node = serde_json::from_str(json)
+    let formula = Formula::from_json(json)
         .context("failed to deserialize node tree from JSON")?;
-
-    if !matches!(node, Node::Triple(_, _, _)) {
-        bail!("top-level JSON node must be a Triple");
-    }
-
-    let (r_jit_fn, g_jit_fn, b_jit_fn) = build_jit_function_triple(&node);
-    let rgb_fn = |coord: PixelCoordinates| Colour {
-        r: r_jit_fn(coord.x, coord.y),
-        g: g_jit_fn(coord.x, coord.y),
-        b: b_jit_fn(coord.x, coord.y),
-    };
-
-    let pixels = render_tiled(&rgb_fn, width, height);
+    let pixels = render(&formula.tree, &Textures::new(), width, height)?;
     Ok(ReadOutput { pixels })
 }
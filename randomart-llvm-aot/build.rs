@@ -1,76 +1,276 @@
-use randomart_core::{grammar::generate_tree_parallel, node::Node};
+use randomart_core::{grammar::{generate_tree_parallel, Grammar}, node::Node};
 use xxhash_rust::xxh3::xxh3_64;
 use std::fmt::Write;
 
-fn emit_node(out: &mut String, node: &Node) {
+/// Emit `node` as a Rust expression of the coordinates named `x` and `y`, and
+/// of the depth `z` and time `t`, which no node moves.
+fn emit_node(out: &mut String, node: &Node, x: &str, y: &str) {
     match node {
-        Node::X => out.push_str("x"),
-        Node::Y => out.push_str("y"),
+        Node::X => out.push_str(x),
+        Node::Y => out.push_str(y),
+        Node::Z => out.push('z'),
+        Node::T => out.push('t'),
+        Node::R => write!(out, "randomart_core::math::sqrtf({x} * {x} + {y} * {y})").unwrap(),
+        Node::Theta => write!(out, "randomart_core::math::atan2f({y}, {x})").unwrap(),
         Node::Number(v) => write!(out, "({}_f32)", v).unwrap(),
 
         Node::Add(a, b) => {
             out.push('(');
-            emit_node(out, a);
+            emit_node(out, a, x, y);
             out.push_str(" + ");
-            emit_node(out, b);
+            emit_node(out, b, x, y);
             out.push_str(") / 2.0_f32");
         }
         Node::Mult(a, b) => {
             out.push('(');
-            emit_node(out, a);
+            emit_node(out, a, x, y);
             out.push_str(" * ");
-            emit_node(out, b);
+            emit_node(out, b, x, y);
             out.push(')');
         }
         Node::Div(a, b) => {
             out.push_str("{ let _d = ");
-            emit_node(out, b);
+            emit_node(out, b, x, y);
             out.push_str("; if _d.abs() > 1e-6_f32 { (");
-            emit_node(out, a);
+            emit_node(out, a, x, y);
             out.push_str(") / _d } else { 0.0_f32 } }");
         }
+        Node::Min(a, b) => {
+            out.push_str("{ let _a = ");
+            emit_node(out, a, x, y);
+            out.push_str("; let _b = ");
+            emit_node(out, b, x, y);
+            out.push_str("; if _a < _b { _a } else { _b } }");
+        }
+        Node::Max(a, b) => {
+            out.push_str("{ let _a = ");
+            emit_node(out, a, x, y);
+            out.push_str("; let _b = ");
+            emit_node(out, b, x, y);
+            out.push_str("; if _a > _b { _a } else { _b } }");
+        }
+        Node::Mod(a, b) => {
+            out.push_str("randomart_core::math::guarded_mod(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            out.push(')');
+        }
+        Node::Atan2(a, b) => {
+            out.push_str("randomart_core::math::atan2f(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            out.push(')');
+        }
+        Node::Pow(a, b) => {
+            out.push_str("randomart_core::math::guarded_pow(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            out.push(')');
+        }
+        Node::Xor(a, b) => {
+            out.push_str("randomart_core::math::bit_xor(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            out.push(')');
+        }
+        Node::And(a, b) => {
+            out.push_str("randomart_core::math::bit_and(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            out.push(')');
+        }
+        Node::Step(edge, v) => {
+            out.push_str("{ let _e = ");
+            emit_node(out, edge, x, y);
+            out.push_str("; if (");
+            emit_node(out, v, x, y);
+            out.push_str(") < _e { -1.0_f32 } else { 1.0_f32 } }");
+        }
+        Node::Noise(a, b, perm) => {
+            out.push_str("randomart_core::noise::noise(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            write!(out, ", &{:?})", perm.table()).unwrap();
+        }
+        // Transforms bind the new point as `_wx`/`_wy`; the tuple is built
+        // before it shadows any outer binding of the same names.
+        Node::Rotate(angle, body) => {
+            out.push_str("{ let (_wx, _wy) = randomart_core::math::rotate(");
+            emit_node(out, angle, x, y);
+            write!(out, ", {x}, {y}); ").unwrap();
+            emit_node(out, body, "_wx", "_wy");
+            out.push_str(" }");
+        }
+        Node::Scale(factor, body) => {
+            out.push_str("{ let (_wx, _wy) = randomart_core::math::scale(");
+            emit_node(out, factor, x, y);
+            write!(out, ", {x}, {y}); ").unwrap();
+            emit_node(out, body, "_wx", "_wy");
+            out.push_str(" }");
+        }
+        Node::Warp(dx, dy, body) => {
+            write!(out, "{{ let (_wx, _wy) = ({x} + (").unwrap();
+            emit_node(out, dx, x, y);
+            write!(out, "), {y} + (").unwrap();
+            emit_node(out, dy, x, y);
+            out.push_str(")); ");
+            emit_node(out, body, "_wx", "_wy");
+            out.push_str(" }");
+        }
+        Node::IfPositive(cond, a, b) => {
+            out.push_str("{ if (");
+            emit_node(out, cond, x, y);
+            out.push_str(") > 0.0_f32 { ");
+            emit_node(out, a, x, y);
+            out.push_str(" } else { ");
+            emit_node(out, b, x, y);
+            out.push_str(" } }");
+        }
+        Node::Smoothstep(e0, e1, v) => {
+            out.push_str("randomart_core::math::smoothstep(");
+            emit_node(out, e0, x, y);
+            out.push_str(", ");
+            emit_node(out, e1, x, y);
+            out.push_str(", ");
+            emit_node(out, v, x, y);
+            out.push(')');
+        }
         Node::Sin(inner) => {
             out.push_str("randomart_core::math::sinf(");
-            emit_node(out, inner);
+            emit_node(out, inner, x, y);
             out.push(')');
         }
         Node::Cos(inner) => {
             out.push_str("randomart_core::math::cosf(");
-            emit_node(out, inner);
+            emit_node(out, inner, x, y);
             out.push(')');
         }
         Node::Exp(inner) => {
             out.push_str("randomart_core::math::expf(");
-            emit_node(out, inner);
+            emit_node(out, inner, x, y);
             out.push(')');
         }
         Node::Sqrt(inner) => {
             out.push_str("randomart_core::math::sqrtf((");
-            emit_node(out, inner);
+            emit_node(out, inner, x, y);
             out.push_str(").max(0.0_f32))");
         }
+        Node::Abs(inner) => {
+            out.push('(');
+            emit_node(out, inner, x, y);
+            out.push_str(").abs()");
+        }
+        Node::Tan(inner) => {
+            out.push_str("randomart_core::math::tanf(");
+            emit_node(out, inner, x, y);
+            out.push(')');
+        }
+        Node::Log(inner) => {
+            out.push_str("randomart_core::math::guarded_log(");
+            emit_node(out, inner, x, y);
+            out.push(')');
+        }
+        Node::Quantize(inner, levels) => {
+            out.push_str("randomart_core::math::quantize(");
+            emit_node(out, inner, x, y);
+            write!(out, ", {levels}_u32)").unwrap();
+        }
         Node::MixUnbounded(a, b, c, d) => {
             out.push_str("{ let _a = ");
-            emit_node(out, a);
+            emit_node(out, a, x, y);
             out.push_str("; let _b = ");
-            emit_node(out, b);
+            emit_node(out, b, x, y);
             out.push_str("; let _c = ");
-            emit_node(out, c);
+            emit_node(out, c, x, y);
             out.push_str("; let _d = ");
-            emit_node(out, d);
+            emit_node(out, d, x, y);
             out.push_str("; (_a * _c + _b * _d) / (_a + _b + 1e-6_f32) }");
         }
 
-        Node::Triple(_, _, _) => panic!("Triple should not appear in scalar emit"),
+        Node::Circle(cx, cy, r) => emit_sdf_call(out, "circle", &[cx, cy, r], x, y),
+        Node::BoxSdf(cx, cy, w, h) => emit_sdf_call(out, "rectangle", &[cx, cy, w, h], x, y),
+        Node::Line(ax, ay, bx, by) => emit_sdf_call(out, "segment", &[ax, ay, bx, by], x, y),
+        Node::SmoothUnion(a, b, k) => {
+            out.push_str("randomart_core::sdf::smooth_union(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            out.push_str(", ");
+            emit_node(out, k, x, y);
+            out.push(')');
+        }
+        Node::SmoothIntersect(a, b, k) => {
+            out.push_str("randomart_core::sdf::smooth_intersection(");
+            emit_node(out, a, x, y);
+            out.push_str(", ");
+            emit_node(out, b, x, y);
+            out.push_str(", ");
+            emit_node(out, k, x, y);
+            out.push(')');
+        }
+
+        // Real loops; a nested loop's `_it` shadows this one's only inside it.
+        Node::Iterate(body, n) => {
+            write!(out, "{{ let mut _it = {x}; for _ in 0..{n}_u32 {{ _it = ").unwrap();
+            emit_node(out, body, "_it", y);
+            out.push_str("; } _it }");
+        }
+        Node::Escape(body, n) => {
+            write!(
+                out,
+                "{{ let mut _it = {x}; let mut _k = 0_u32; \
+                 while _k < {n}_u32 && _it.abs() <= randomart_core::math::ESCAPE_RADIUS {{ _it = "
+            )
+            .unwrap();
+            emit_node(out, body, "_it", y);
+            write!(out, "; _k += 1; }} randomart_core::math::escape_time(_k, {n}_u32) }}").unwrap();
+        }
+
+        // Rust's own block scoping gives a `Var` the innermost `Let` of its id.
+        Node::Var(id) => write!(out, "_v{id}").unwrap(),
+        Node::Let(id, value, body) => {
+            write!(out, "{{ let _v{id} = ").unwrap();
+            emit_node(out, value, x, y);
+            out.push_str("; ");
+            emit_node(out, body, x, y);
+            out.push_str(" }");
+        }
+
+        Node::Triple(_, _, _) | Node::Mono(_) | Node::Quad(..) => panic!("{} should not appear in scalar emit", node.name()),
+        Node::Texture(..) => panic!("a baked formula has no textures to sample"),
         Node::Random => panic!("Random must be resolved before emit"),
         Node::Rule(_) => panic!("Rule must be expanded before emit"),
     }
 }
 
+/// Emit a call to the `randomart_core::sdf` distance `function` from the point
+/// to the shape described by `params`.
+fn emit_sdf_call(out: &mut String, function: &str, params: &[&Node], x: &str, y: &str) {
+    write!(out, "randomart_core::sdf::{function}({x}, {y}").unwrap();
+    for param in params {
+        out.push_str(", ");
+        emit_node(out, param, x, y);
+    }
+    out.push(')');
+}
+
+/// Whether `terminal` appears anywhere in `node`.
+fn uses(node: &Node, terminal: &Node) -> bool {
+    node == terminal || node.children().into_iter().any(|child| uses(child, terminal))
+}
+
 fn emit_channel_fn(name: &str, node: &Node) -> String {
     let mut body = String::new();
-    emit_node(&mut body, node);
-    format!("#[inline(always)]\npub fn {name}(x: f32, y: f32) -> f32 {{\n    {body}\n}}\n")
+    emit_node(&mut body, node, "x", "y");
+    let z = if uses(node, &Node::Z) { "z" } else { "_z" };
+    let t = if uses(node, &Node::T) { "t" } else { "_t" };
+    format!("#[inline(always)]\npub fn {name}(x: f32, y: f32, {z}: f32, {t}: f32) -> f32 {{\n    {body}\n}}\n")
 }
 
 fn main() {
@@ -84,9 +284,9 @@ fn main() {
     println!("cargo:rerun-if-env-changed=RANDOMART_DEPTH");
 
     let seed = xxh3_64(seed_str.as_bytes());
-    let mut node = generate_tree_parallel(seed, depth)
+    let mut node = generate_tree_parallel(&Grammar::default(seed), seed, depth)
         .expect("failed to generate tree");
-    node.simplify_triple();
+    node.simplify_root();
 
     let (r, g, b) = match &*node {
         Node::Triple(r, g, b) => (r.as_ref(), g.as_ref(), b.as_ref()),
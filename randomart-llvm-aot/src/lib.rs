@@ -11,7 +11,8 @@ pub fn baked_depth() -> u32 {
 }
 
 use randomart_core::{
-    grammar::generate_tree_parallel,
+    formula::Formula,
+    grammar::{generate_tree_parallel, Grammar},
     pixel_buffer::{GenerateOutput, PixelBuffer, ReadOutput},
     render::{render_tiled, Colour, PixelCoordinates},
 };
@@ -20,9 +21,10 @@ use anyhow::{Context, Result};
 fn render(width: u32, height: u32) -> PixelBuffer {
     render_tiled(
         &|coord: PixelCoordinates| Colour {
-            r: r(coord.x, coord.y),
-            g: g(coord.x, coord.y),
-            b: b(coord.x, coord.y),
+            r: r(coord.x, coord.y, coord.z, coord.t),
+            g: g(coord.x, coord.y, coord.z, coord.t),
+            b: b(coord.x, coord.y, coord.z, coord.t),
+            a: 1.0,
         },
         width,
         height,
@@ -38,10 +40,11 @@ pub fn generate(_string: &str, _depth: u32, width: u32, height: u32) -> Result<G
         .and_then(|v| v.parse().ok())
         .unwrap_or(8);
     let seed = xxh3_64(seed_str.as_bytes());
-    let mut node = generate_tree_parallel(seed, depth_str)
+    let grammar = Grammar::default(seed);
+    let mut node = generate_tree_parallel(&grammar, seed, depth_str)
         .context("tree generation failed")?;
-    node.simplify_triple();
-    let json = serde_json::to_string_pretty(&*node)
+    node.simplify_root();
+    let json = Formula::new(*node, &grammar).to_json()
         .context("failed to serialize node tree")?;
     let pixels = render(width, height);
     Ok(GenerateOutput { pixels, json })
@@ -1,4 +1,5 @@
 use randomart_core::pixel_buffer::PixelBuffer;
+use randomart_core::render::Channels;
 use anyhow::{anyhow, Context, Result};
 use std::ptr::NonNull;
 use objc2::rc::Retained;
@@ -30,8 +31,9 @@ fn ns_error_msg(err: &Retained<NSError>) -> String {
 }
 
 /// JIT-compile MSL `source`, dispatch the `art_gen` kernel over a `width x height`
-/// rgba32Float texture, read back the pixels, and return a RGB PixelBuffer.
-pub fn run_gpu_kernel(source: &str, width: u32, height: u32) -> Result<PixelBuffer> {
+/// rgba32Float texture, read back the pixels, and return a PixelBuffer with
+/// `channels`.
+pub fn run_gpu_kernel(source: &str, width: u32, height: u32, channels: Channels) -> Result<PixelBuffer> {
     let device = MTLCreateSystemDefaultDevice().context("no Metal device available")?;
 
     let queue: Retained<ProtocolObject<dyn MTLCommandQueue>> = device
@@ -117,14 +119,22 @@ pub fn run_gpu_kernel(source: &str, width: u32, height: u32) -> Result<PixelBuff
     // stays in bounds and fully initializes the buffer.
     unsafe { texture.getBytes_bytesPerRow_fromRegion_mipmapLevel(ptr, bytes_per_row, region, 0) };
 
-    // Convert RGBA32F → RGB8.
+    // Convert RGBA32F → RGB8 or RGBA8.
     // The Metal kernel already maps values into [0, 1] before writing.
-    let mut buf = PixelBuffer::new(width, height);
+    let mut buf = match channels {
+        Channels::Rgb => PixelBuffer::new(width, height),
+        Channels::Rgba => PixelBuffer::with_alpha(width, height),
+    };
     for (i, chunk) in float_pixels.chunks_exact(4).enumerate() {
         let x = (i % width as usize) as u32;
         let y = (i / width as usize) as u32;
         let to_u8 = |v: f32| if (v * 255.0).is_finite() { ((v * 255.0) as u32).min(255) as u8 } else { 0 };
-        buf.put_pixel(x, y, to_u8(chunk[0]), to_u8(chunk[1]), to_u8(chunk[2]));
+        match channels {
+            Channels::Rgb => buf.put_pixel(x, y, to_u8(chunk[0]), to_u8(chunk[1]), to_u8(chunk[2])),
+            Channels::Rgba => {
+                buf.put_pixel_rgba(x, y, to_u8(chunk[0]), to_u8(chunk[1]), to_u8(chunk[2]), to_u8(chunk[3]))
+            }
+        }
     }
     Ok(buf)
 }
@@ -2,48 +2,99 @@ mod metal_codegen;
 pub mod gpu;
 
 use randomart_core::{
-    grammar::generate_tree_parallel,
+    formula::Formula,
+    colormap::Colormap,
+    grammar::{generate_tree_parallel, Grammar},
     node::Node,
     pixel_buffer::{PixelBuffer, GenerateOutput, ReadOutput},
+    render::{Channels, Projection},
+    texture::Textures,
 };
 use crate::{
-    metal_codegen::emit_metal_from_triple,
+    metal_codegen::{emit_metal_from_mono, emit_metal_from_quad, emit_metal_from_triple},
     gpu::run_gpu_kernel,
 };
 use anyhow::{Context, Result};
 use xxhash_rust::xxh3::xxh3_64;
 
-fn render_to_buffer(r: &Node, g: &Node, b: &Node, width: u32, height: u32) -> Result<PixelBuffer> {
-    let metal_src = emit_metal_from_triple(r, g, b);
-    run_gpu_kernel(&metal_src, width, height)
+pub fn render(node: &Node, textures: &Textures, width: u32, height: u32) -> Result<PixelBuffer> {
+    let mut images = render_projected(node, textures, &Colormap::default(), &[Projection::Plane(0.0)], width, height)?;
+    Ok(images.remove(0))
+}
+
+/// One image of `node` per entry of `projections`. Each projection is a kernel
+/// of its own, so each is compiled separately. Textures are not uploaded to the
+/// GPU yet, so a tree that samples one is an error whatever `_textures` holds.
+/// A `Mono` root is coloured by `colormap`, and a `Quad` root renders RGBA
+/// images.
+pub fn render_projected(
+    node: &Node,
+    _textures: &Textures,
+    colormap: &Colormap,
+    projections: &[Projection],
+    width: u32,
+    height: u32,
+) -> Result<Vec<PixelBuffer>> {
+    check_root(node)?;
+    projections
+        .iter()
+        .map(|&projection| run_gpu_kernel(&emit_kernel(node, colormap, projection, 0.0), width, height, Channels::of(node)))
+        .collect()
+}
+
+/// One frame of the flat image of `node` per entry of `times`, each a kernel
+/// of its own like the projections of [`render_projected`].
+pub fn render_frames(
+    node: &Node,
+    _textures: &Textures,
+    colormap: &Colormap,
+    times: &[f32],
+    width: u32,
+    height: u32,
+) -> Result<Vec<PixelBuffer>> {
+    check_root(node)?;
+    times
+        .iter()
+        .map(|&t| run_gpu_kernel(&emit_kernel(node, colormap, Projection::Plane(0.0), t), width, height, Channels::of(node)))
+        .collect()
+}
+
+fn check_root(node: &Node) -> Result<()> {
+    if !matches!(node, Node::Triple(..) | Node::Mono(_) | Node::Quad(..)) {
+        anyhow::bail!("top-level node must be a Triple, Mono or Quad");
+    }
+    if let Some(slot) = Textures::new().missing(node) {
+        anyhow::bail!("the Metal backend cannot sample textures (slot `{slot}`)");
+    }
+    Ok(())
+}
+
+/// The kernel for a root [`check_root`] accepted.
+fn emit_kernel(node: &Node, colormap: &Colormap, projection: Projection, t: f32) -> String {
+    match node {
+        Node::Triple(r, g, b) => emit_metal_from_triple(r, g, b, projection, t),
+        Node::Mono(v) => emit_metal_from_mono(v, colormap, projection, t),
+        Node::Quad(r, g, b, a) => emit_metal_from_quad(r, g, b, a, projection, t),
+        _ => unreachable!("checked by check_root"),
+    }
 }
 
 pub fn generate(string: &str, depth: u32, width: u32, height: u32) -> Result<GenerateOutput> {
     let seed: u64 = xxh3_64(string.as_bytes());
-    let mut node = generate_tree_parallel(seed, depth)
+    let grammar = Grammar::default(seed);
+    let mut node = generate_tree_parallel(&grammar, seed, depth)
         .context("tree generation failed")?;
-    node.simplify_triple();
+    node.simplify_root();
 
-    let json = serde_json::to_string_pretty(&*node)
+    let pixels = render(&node, &Textures::new(), width, height)?;
+    let json = Formula::new(*node, &grammar).to_json()
         .context("failed to serialize node tree")?;
-
-    let Node::Triple(r, g, b) = *node else {
-        // Invariant: generate_tree_parallel always produces a Triple root.
-        unreachable!("generated tree root is not a Triple");
-    };
-
-    let pixels = render_to_buffer(&r, &g, &b, width, height)?;
     Ok(GenerateOutput { pixels, json })
 }
 
 pub fn read_json(json: &str, width: u32, height: u32) -> Result<ReadOutput> {
-    let node: Node = serde_json::from_str(json)
+    let formula = Formula::from_json(json)
         .context("failed to deserialize node tree from JSON")?;
-
-    let Node::Triple(r, g, b) = node else {
-        anyhow::bail!("top-level JSON node must be a Triple");
-    };
-
-    let pixels = render_to_buffer(&r, &g, &b, width, height)?;
+    let pixels = render(&formula.tree, &Textures::new(), width, height)?;
     Ok(ReadOutput { pixels })
 }
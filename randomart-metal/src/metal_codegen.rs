@@ -1,14 +1,21 @@
+use randomart_core::colormap::Colormap;
 use randomart_core::node::Node;
+use randomart_core::render::{Face, Projection};
 use std::fmt::Write;
 
 struct CodegenCtx {
     lines: Vec<String>,
     counter: usize,
+    /// Program-scope declarations, named after `prefix` to stay unique.
+    tables: Vec<String>,
+    prefix: &'static str,
+    /// Variables holding the values of the enclosing `Let`s by id, innermost last.
+    scope: Vec<(u32, String)>,
 }
 
 impl CodegenCtx {
-    pub fn new() -> Self {
-        Self { lines: Vec::new(), counter: 0 }
+    pub fn new(prefix: &'static str) -> Self {
+        Self { lines: Vec::new(), counter: 0, tables: Vec::new(), prefix, scope: Vec::new() }
     }
 
     fn next_tmp(&mut self) -> String {
@@ -17,14 +24,41 @@ impl CodegenCtx {
         name
     }
 
+    /// Emit a call to the MSL distance `function` from the point `(x, y)` to
+    /// the shape described by `params`, returning the variable holding it.
+    fn sdf_call(&mut self, function: &str, params: &[&Node], x: &str, y: &str) -> String {
+        let params: Vec<String> = params.iter().map(|param| self.gen(param, x, y)).collect();
+        let tmp = self.next_tmp();
+        self.emit(format!("float {tmp} = {function}({x}, {y}, {});", params.join(", ")));
+        tmp
+    }
+
     fn emit(&mut self, line: String) {
         self.lines.push(line);
     }
 
-    pub fn gen(&mut self, node: &Node) -> String {
+    /// Emit `node` evaluated at the point held in the variables `x` and `y`,
+    /// returning the variable that holds the result.
+    pub fn gen(&mut self, node: &Node, x: &str, y: &str) -> String {
         match node {
-            Node::X => "x".to_string(),
-            Node::Y => "y".to_string(),
+            Node::X => x.to_string(),
+            Node::Y => y.to_string(),
+            // Every eval function takes the depth as `z` and the time as `t`,
+            // and no node moves them.
+            Node::Z => "z".to_string(),
+            Node::T => "t".to_string(),
+
+            Node::R => {
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = sqrt({x} * {x} + {y} * {y});"));
+                tmp
+            }
+
+            Node::Theta => {
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = atan2({y}, {x});"));
+                tmp
+            }
 
             Node::Number(n) => {
                 let tmp = self.next_tmp();
@@ -33,62 +67,261 @@ impl CodegenCtx {
             }
 
             Node::Sin(inner) => {
-                let arg = self.gen(inner);
+                let arg = self.gen(inner, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {} = sin({});", tmp, arg));
                 tmp
             }
 
             Node::Cos(inner) => {
-                let arg = self.gen(inner);
+                let arg = self.gen(inner, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {} = cos({});", tmp, arg));
                 tmp
             }
 
             Node::Sqrt(inner) => {
-                let arg = self.gen(inner);
+                let arg = self.gen(inner, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {} = sqrt(fmax({}, 0.0));", tmp, arg));
                 tmp
             }
 
             Node::Exp(inner) => {
-                let arg = self.gen(inner);
+                let arg = self.gen(inner, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {} = exp({});", tmp, arg));
                 tmp
             }
 
+            Node::Abs(inner) => {
+                let arg = self.gen(inner, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = fabs({arg});"));
+                tmp
+            }
+
+            Node::Tan(inner) => {
+                let arg = self.gen(inner, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = tan({arg});"));
+                tmp
+            }
+
+            Node::Log(inner) => {
+                let arg = self.gen(inner, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = logu({arg});"));
+                tmp
+            }
+
+            Node::Quantize(inner, levels) => {
+                let arg = self.gen(inner, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = quantizeu({arg}, {levels});"));
+                tmp
+            }
+
+            Node::Xor(a, b) => {
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = bit_xor({left}, {right});"));
+                tmp
+            }
+
+            Node::And(a, b) => {
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = bit_and({left}, {right});"));
+                tmp
+            }
+
+            Node::Mod(a, b) => {
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = modu({left}, {right});"));
+                tmp
+            }
+
+            Node::Atan2(a, b) => {
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = atan2({left}, {right});"));
+                tmp
+            }
+
+            Node::Pow(a, b) => {
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = powu({left}, {right});"));
+                tmp
+            }
+
             Node::Add(a, b) => {
-                let left = self.gen(a);
-                let right = self.gen(b);
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {} = ({} + {}) * 0.5;", tmp, left, right));
                 tmp
             }
 
             Node::Mult(a, b) => {
-                let left = self.gen(a);
-                let right = self.gen(b);
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {} = {} * {};", tmp, left, right));
                 tmp
             }
 
             Node::Div(a, b) => {
-                let left = self.gen(a);
-                let right = self.gen(b);
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {tmp} = fabs({right}) > 1e-6 ? ({left} / {right}) : 0.0;"));
                 tmp
             }
 
+            Node::Min(a, b) => {
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = {left} < {right} ? {left} : {right};"));
+                tmp
+            }
+
+            Node::Max(a, b) => {
+                let left = self.gen(a, x, y);
+                let right = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = {left} > {right} ? {left} : {right};"));
+                tmp
+            }
+
+            Node::Step(edge, v) => {
+                let edge = self.gen(edge, x, y);
+                let v = self.gen(v, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = {v} < {edge} ? -1.0 : 1.0;"));
+                tmp
+            }
+
+            Node::Noise(a, b, perm) => {
+                let nx = self.gen(a, x, y);
+                let ny = self.gen(b, x, y);
+                let table = format!("{}_perm{}", self.prefix, self.tables.len());
+                let entries: Vec<String> = perm.table().iter().map(u8::to_string).collect();
+                self.tables.push(format!("constant uchar {}[256] = {{{}}};", table, entries.join(", ")));
+                let tmp = self.next_tmp();
+                self.emit(format!("float {} = noise2({}, {}, {});", tmp, nx, ny, table));
+                tmp
+            }
+
+            Node::Rotate(angle, body) => {
+                let angle = self.gen(angle, x, y);
+                let (wx, wy) = (self.next_tmp(), self.next_tmp());
+                self.emit(format!("float2 {wx}_r = rotateu({angle}, {x}, {y});"));
+                self.emit(format!("float {wx} = {wx}_r.x;"));
+                self.emit(format!("float {wy} = {wx}_r.y;"));
+                self.gen(body, &wx, &wy)
+            }
+
+            Node::Scale(factor, body) => {
+                let factor = self.gen(factor, x, y);
+                let (wx, wy) = (self.next_tmp(), self.next_tmp());
+                self.emit(format!("float {wx} = fabs({factor}) > 1e-6 ? {x} / {factor} : 0.0;"));
+                self.emit(format!("float {wy} = fabs({factor}) > 1e-6 ? {y} / {factor} : 0.0;"));
+                self.gen(body, &wx, &wy)
+            }
+
+            Node::Iterate(body, n) => {
+                let v = self.next_tmp();
+                self.emit(format!("float {v} = {x};"));
+                self.emit(format!("for (uint {v}_i = 0; {v}_i < {n}; {v}_i++) {{"));
+                let next = self.gen(body, &v, y);
+                self.emit(format!("{v} = {next};"));
+                self.emit("}".to_string());
+                v
+            }
+
+            Node::Escape(body, n) => {
+                let (v, steps, tmp) = (self.next_tmp(), self.next_tmp(), self.next_tmp());
+                if *n == 0 {
+                    self.emit(format!("float {tmp} = -1.0;"));
+                    return tmp;
+                }
+                self.emit(format!("float {v} = {x};"));
+                self.emit(format!("uint {steps} = 0;"));
+                self.emit(format!("while ({steps} < {n} && fabs({v}) <= 2.0) {{"));
+                let next = self.gen(body, &v, y);
+                self.emit(format!("{v} = {next};"));
+                self.emit(format!("{steps}++;"));
+                self.emit("}".to_string());
+                self.emit(format!("float {tmp} = 2.0 * float({steps}) / {n}.0 - 1.0;"));
+                tmp
+            }
+
+            Node::Var(id) => {
+                let (_, value) = self.scope.iter().rev().find(|(bound, _)| bound == id).expect("Var outside its Let");
+                value.clone()
+            }
+
+            Node::Let(id, value, body) => {
+                let value = self.gen(value, x, y);
+                self.scope.push((*id, value));
+                let result = self.gen(body, x, y);
+                self.scope.pop();
+                result
+            }
+
+            Node::Warp(dx, dy, body) => {
+                let dx = self.gen(dx, x, y);
+                let dy = self.gen(dy, x, y);
+                let (wx, wy) = (self.next_tmp(), self.next_tmp());
+                self.emit(format!("float {wx} = {x} + {dx};"));
+                self.emit(format!("float {wy} = {y} + {dy};"));
+                self.gen(body, &wx, &wy)
+            }
+
+            Node::IfPositive(cond, a, b) => {
+                let cond = self.gen(cond, x, y);
+                let a = self.gen(a, x, y);
+                let b = self.gen(b, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = {cond} > 0.0 ? {a} : {b};"));
+                tmp
+            }
+
+            Node::Circle(cx, cy, r) => self.sdf_call("circle_sdf", &[cx, cy, r], x, y),
+            Node::BoxSdf(cx, cy, w, h) => self.sdf_call("box_sdf", &[cx, cy, w, h], x, y),
+            Node::Line(ax, ay, bx, by) => self.sdf_call("segment_sdf", &[ax, ay, bx, by], x, y),
+
+            Node::SmoothUnion(a, b, k) | Node::SmoothIntersect(a, b, k) => {
+                let function = if matches!(node, Node::SmoothUnion(..)) { "smooth_union" } else { "smooth_intersection" };
+                let (a, b, k) = (self.gen(a, x, y), self.gen(b, x, y), self.gen(k, x, y));
+                let tmp = self.next_tmp();
+                self.emit(format!("float {tmp} = {function}({a}, {b}, {k});"));
+                tmp
+            }
+
+            Node::Smoothstep(e0, e1, v) => {
+                let e0 = self.gen(e0, x, y);
+                let e1 = self.gen(e1, x, y);
+                let v = self.gen(v, x, y);
+                let tmp = self.next_tmp();
+                self.emit(format!("float {} = smoothstepu({}, {}, {});", tmp, e0, e1, v));
+                tmp
+            }
+
             Node::MixUnbounded(a, b, c, d) => {
-                let a = self.gen(a);
-                let b = self.gen(b);
-                let c = self.gen(c);
-                let d = self.gen(d);
+                let a = self.gen(a, x, y);
+                let b = self.gen(b, x, y);
+                let c = self.gen(c, x, y);
+                let d = self.gen(d, x, y);
                 let tmp = self.next_tmp();
                 self.emit(format!("float {} = mixu({}, {}, {}, {});", tmp, a, b, c, d));
                 tmp
@@ -99,7 +332,11 @@ impl CodegenCtx {
     }
 
     pub fn eval_function(&self, name: &str, result_var: &str) -> String {
-        let mut out = format!("float {}(float x, float y) {{\n", name);
+        let mut out = String::new();
+        for table in &self.tables {
+            writeln!(out, "{}", table).unwrap();
+        }
+        writeln!(out, "float {}(float x, float y, float z, float t) {{", name).unwrap();
         for line in &self.lines {
             writeln!(out, "    {}", line).unwrap();
         }
@@ -109,46 +346,265 @@ impl CodegenCtx {
     }
 }
 
-pub(crate) fn emit_metal_from_triple(r: &Node, g: &Node, b: &Node) -> String {
-    let mut out = String::new();
-    out += r#"
+/// MSL statements setting `x`, `y` and `z` to the point `projection` gives the
+/// pixel at `gid` of the texture `out`, as `Projection::point` does.
+fn emit_point(projection: Projection) -> String {
+    let size = "float2(out.get_width(), out.get_height())";
+    match projection {
+        Projection::Plane(z) => format!(
+            "    float2 uv = float2(gid) / {size};\n    float x = uv.x * 2.0 - 1.0;\n    float y = uv.y * 2.0 - 1.0;\n    float z = {z:.6};\n"
+        ),
+        Projection::Equirectangular => format!(
+            "    float2 uv = (float2(gid) + 0.5) / {size} * 2.0 - 1.0;\n    float longitude = uv.x * M_PI_F;\n    float latitude = uv.y * M_PI_F * 0.5;\n    float x = cos(latitude) * sin(longitude);\n    float y = sin(latitude);\n    float z = cos(latitude) * cos(longitude);\n"
+        ),
+        Projection::CubeFace(face) => {
+            let point = match face {
+                Face::PositiveX => "1.0, t, -s",
+                Face::NegativeX => "-1.0, t, s",
+                Face::PositiveY => "s, -1.0, t",
+                Face::NegativeY => "s, 1.0, -t",
+                Face::PositiveZ => "s, t, 1.0",
+                Face::NegativeZ => "-s, t, -1.0",
+            };
+            format!(
+                "    float2 uv = (float2(gid) + 0.5) / {size} * 2.0 - 1.0;\n    float s = uv.x;\n    float t = uv.y;\n    float3 p = normalize(float3({point}));\n    float x = p.x;\n    float y = p.y;\n    float z = p.z;\n"
+            )
+        }
+    }
+}
+
+/// The helpers every kernel's eval functions call.
+const PRELUDE: &str = r#"
 #include <metal_stdlib>
 using namespace metal;
 
 inline float mixu(float a, float b, float c, float d) {
     return (a * c + b * d) / (a + b + 1e-6);
 }
+
+inline float logu(float x) {
+    return log(fabs(x) > 1e-6 ? fabs(x) : 1e-6);
+}
+
+inline float powu(float base, float exponent) {
+    return fabs(base) > 1e-6 ? pow(fabs(base), exponent) : 0.0;
+}
+
+inline float modu(float a, float b) {
+    return fabs(b) > 1e-6 ? fmod(a, b) : 0.0;
+}
+
+inline float noise_grad(uchar hash, float x, float y) {
+    switch (hash & 7) {
+        case 0: return x + y;
+        case 1: return -x + y;
+        case 2: return x - y;
+        case 3: return -x - y;
+        case 4: return x;
+        case 5: return -x;
+        case 6: return y;
+        default: return -y;
+    }
+}
+
+inline float noise_fade(float t) {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+// Perlin noise as in randomart_core::noise.
+inline float noise2(float x, float y, constant uchar *perm) {
+    x *= 4.0;
+    y *= 4.0;
+    float x0 = floor(x);
+    float y0 = floor(y);
+    float fx = x - x0;
+    float fy = y - y0;
+    int xi = int(x0) & 255;
+    int yi = int(y0) & 255;
+    float n00 = noise_grad(perm[(perm[xi] + yi) & 255], fx, fy);
+    float n10 = noise_grad(perm[(perm[(xi + 1) & 255] + yi) & 255], fx - 1.0, fy);
+    float n01 = noise_grad(perm[(perm[xi] + yi + 1) & 255], fx, fy - 1.0);
+    float n11 = noise_grad(perm[(perm[(xi + 1) & 255] + yi + 1) & 255], fx - 1.0, fy - 1.0);
+    float u = noise_fade(fx);
+    float v = noise_fade(fy);
+    float nx0 = n00 + u * (n10 - n00);
+    float nx1 = n01 + u * (n11 - n01);
+    return nx0 + v * (nx1 - nx0);
+}
+
+inline float2 rotateu(float half_turns, float x, float y) {
+    float angle = half_turns * M_PI_F;
+    float c = cos(angle);
+    float s = sin(angle);
+    return float2(x * c - y * s, x * s + y * c);
+}
+
+inline float circle_sdf(float x, float y, float cx, float cy, float r) {
+    return length(float2(x - cx, y - cy)) - r;
+}
+
+inline float box_sdf(float x, float y, float cx, float cy, float w, float h) {
+    float2 d = abs(float2(x - cx, y - cy)) - abs(float2(w, h));
+    return length(max(d, 0.0)) + min(max(d.x, d.y), 0.0);
+}
+
+inline float segment_sdf(float x, float y, float ax, float ay, float bx, float by) {
+    float2 p = float2(x - ax, y - ay);
+    float2 d = float2(bx - ax, by - ay);
+    float length2 = dot(d, d);
+    float h = length2 > 1e-6 ? clamp(dot(p, d) / length2, 0.0, 1.0) : 0.0;
+    return length(p - d * h);
+}
+
+inline float smooth_union(float a, float b, float k) {
+    k = fabs(k);
+    if (k <= 1e-6) {
+        return a < b ? a : b;
+    }
+    float h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+    return b + (a - b) * h - k * h * (1.0 - h);
+}
+
+inline float smooth_intersection(float a, float b, float k) {
+    return -smooth_union(-a, -b, k);
+}
+
+inline float smoothstepu(float e0, float e1, float x) {
+    float width = e1 - e0;
+    float t = fabs(width) > 1e-6 ? (x - e0) / width : 0.0;
+    t = t < 0.0 ? 0.0 : (t > 1.0 ? 1.0 : t);
+    return 2.0 * (t * t * (3.0 - 2.0 * t)) - 1.0;
+}
+
+// fmax and fmin drop a NaN, which puts it on the lowest level like the CPU does.
+inline int to_level(float v, uint levels) {
+    return int(fmin(fmax(floor((v + 1.0) * 0.5 * float(levels)), 0.0), float(levels - 1)));
+}
+
+inline float from_level(int level, uint levels) {
+    return 2.0 * float(level) / float(levels - 1) - 1.0;
+}
+
+inline float quantizeu(float v, uint levels) {
+    return levels < 2 ? -1.0 : from_level(to_level(v, levels), levels);
+}
+
+inline float bit_xor(float a, float b) {
+    return from_level(to_level(a, 256) ^ to_level(b, 256), 256);
+}
+
+inline float bit_and(float a, float b) {
+    return from_level(to_level(a, 256) & to_level(b, 256), 256);
+}
 "#;
 
-    let mut ctx_r = CodegenCtx::new();
-    let r_final = ctx_r.gen(r);
+/// The kernel's header and the statements setting `x`, `y`, `z` and `t`, up
+/// to where the eval functions are called.
+fn emit_kernel_start(projection: Projection, t: f32) -> String {
+    let mut out = String::from(
+        r#"
+kernel void art_gen(texture2d<float, access::write> out [[texture(0)]],
+                    uint2 gid [[thread_position_in_grid]]) {
+"#,
+    );
+    out += &emit_point(projection);
+    writeln!(out, "    float t = {t:.6};").unwrap();
+    out
+}
+
+/// A kernel rendering the image `projection` gives at time `t`.
+pub(crate) fn emit_metal_from_triple(r: &Node, g: &Node, b: &Node, projection: Projection, t: f32) -> String {
+    emit_channels(r, g, b, None, projection, t)
+}
+
+/// A kernel like [`emit_metal_from_triple`]'s that also writes the `Quad`
+/// channel `a` as the alpha.
+pub(crate) fn emit_metal_from_quad(r: &Node, g: &Node, b: &Node, a: &Node, projection: Projection, t: f32) -> String {
+    emit_channels(r, g, b, Some(a), projection, t)
+}
+
+fn emit_channels(r: &Node, g: &Node, b: &Node, a: Option<&Node>, projection: Projection, t: f32) -> String {
+    let mut out = String::from(PRELUDE);
+
+    let mut ctx_r = CodegenCtx::new("r");
+    let r_final = ctx_r.gen(r, "x", "y");
     out += &ctx_r.eval_function("eval_r", &r_final);
     out += "\n";
 
-    let mut ctx_g = CodegenCtx::new();
-    let g_final = ctx_g.gen(g);
+    let mut ctx_g = CodegenCtx::new("g");
+    let g_final = ctx_g.gen(g, "x", "y");
     out += &ctx_g.eval_function("eval_g", &g_final);
     out += "\n";
 
-    let mut ctx_b = CodegenCtx::new();
-    let b_final = ctx_b.gen(b);
+    let mut ctx_b = CodegenCtx::new("b");
+    let b_final = ctx_b.gen(b, "x", "y");
     out += &ctx_b.eval_function("eval_b", &b_final);
     out += "\n";
 
+    if let Some(a) = a {
+        let mut ctx_a = CodegenCtx::new("a");
+        let a_final = ctx_a.gen(a, "x", "y");
+        out += &ctx_a.eval_function("eval_a", &a_final);
+        out += "\n";
+    }
+
+    out += &emit_kernel_start(projection, t);
     out += r#"
-kernel void art_gen(texture2d<float, access::write> out [[texture(0)]],
-                    uint2 gid [[thread_position_in_grid]]) {
-    float2 uv = float2(gid) / float2(out.get_width(), out.get_height());
-    float x = uv.x * 2.0 - 1.0;
-    float y = uv.y * 2.0 - 1.0;
+    float r = eval_r(x, y, z, t);
+    float g = eval_g(x, y, z, t);
+    float b = eval_b(x, y, z, t);
+"#;
+    out += match a {
+        Some(_) => "    float a = (eval_a(x, y, z, t) + 1.0) * 0.5;\n",
+        None => "    float a = 1.0;\n",
+    };
+    out += r#"
+    out.write(float4((r + 1.0) * 0.5, (g + 1.0) * 0.5, (b + 1.0) * 0.5, a), gid);
+}
+"#;
 
-    float r = eval_r(x, y);
-    float g = eval_g(x, y);
-    float b = eval_b(x, y);
+    out
+}
+
+/// A kernel rendering the `Mono` channel `v` through `colormap`, blending the
+/// stops in bytes as [`Colormap::colour`] does.
+pub(crate) fn emit_metal_from_mono(v: &Node, colormap: &Colormap, projection: Projection, t: f32) -> String {
+    let mut out = String::from(PRELUDE);
+
+    let stops = colormap.stops();
+    writeln!(out, "constant float3 colormap_stops[{}] = {{", stops.len()).unwrap();
+    for [r, g, b] in stops {
+        writeln!(out, "    float3({r}.0, {g}.0, {b}.0),").unwrap();
+    }
+    out += "};\n";
+    writeln!(
+        out,
+        r#"
+// fmax and fmin drop a NaN, which puts it on the first stop like the CPU does.
+inline float3 colormap(float v) {{
+    float u = fmin(fmax((v + 1.0) * 0.5 * {last}.0, 0.0), {last}.0);
+    int i = min(int(u), {last} - 1);
+    float f = u - float(i);
+    float3 bytes = colormap_stops[i] + (colormap_stops[i + 1] - colormap_stops[i]) * f;
+    return (bytes + 0.5) / 127.5 - 1.0;
+}}
+"#,
+        last = stops.len() - 1
+    )
+    .unwrap();
 
-    out.write(float4((r + 1.0) * 0.5, (g + 1.0) * 0.5, (b + 1.0) * 0.5, 1.0), gid);
+    let mut ctx_v = CodegenCtx::new("v");
+    let v_final = ctx_v.gen(v, "x", "y");
+    out += &ctx_v.eval_function("eval_v", &v_final);
+    out += "\n";
+
+    out += &emit_kernel_start(projection, t);
+    out += r#"
+    float3 c = colormap(eval_v(x, y, z, t));
+
+    out.write(float4((c + 1.0) * 0.5, 1.0), gid);
 }
 "#;
 
     out
-}
\ No newline at end of file
+}
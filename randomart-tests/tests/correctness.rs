@@ -35,3 +35,29 @@ fn metal_matches_closure_tree() {
     let closure = randomart_closure_tree::generate("test", 8, 64, 64).unwrap();
     assert_eq!(metal.pixels, closure.pixels);
 }
+
+/// A tree grown from a grammar that leans on the polar terminals.
+fn polar_json(seed: u64, depth: u32) -> String {
+    use randomart_core::{formula::Formula, grammar::{generate_tree_parallel, Grammar}};
+
+    let grammar = Grammar::parse(
+        "E ::= Triple(C, C, C)
+         C ::= A | Add(C, C) | Mult(C, C) | Sin(C) [2] | Cos(C) [2] | Sqrt(C) | Div(C, C)
+         A ::= R [2] | Theta [2] | X | Y | Random",
+        seed,
+    )
+    .unwrap();
+    let mut node = generate_tree_parallel(&grammar, seed, depth).unwrap();
+    node.simplify_root();
+    Formula::new(*node, &grammar).to_json().unwrap()
+}
+
+#[test]
+fn jit_matches_closure_tree_with_polar_terminals() {
+    for seed in 0..8 {
+        let json = polar_json(seed, 10);
+        let jit = randomart_cranelift_jit::read_json(&json, 64, 64).unwrap();
+        let closure = randomart_closure_tree::read_json(&json, 64, 64).unwrap();
+        assert_eq!(jit.pixels, closure.pixels, "seed {seed}");
+    }
+}
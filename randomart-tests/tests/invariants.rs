@@ -5,6 +5,7 @@
 //! The trees are constant per pixel, so every pixel has the same value; we assert
 //! on pixel (0,0).
 
+use randomart_core::colormap::Colormap;
 use randomart_core::node::Node;
 
 fn num(v: f32) -> Box<Node> {
@@ -104,3 +105,628 @@ fn backends_agree_on_guarded_trees() {
         );
     }
 }
+
+#[test]
+fn math_guards_stay_finite() {
+    // Log(0) holds its argument at 1e-6; Pow(0, -1) and Mod(1, 0) are guarded to 0.
+    let json = triple_json(
+        Node::Mult(Node::Log(num(0.0)).into(), num(0.05)),
+        Node::Pow(num(0.0), num(-1.0)),
+        Node::Mod(num(1.0), num(0.0)),
+    );
+    let (r, g, b) = first_pixel(&json);
+    assert_eq!(r, expected_u8(randomart_core::math::logf(1e-6) * 0.05));
+    assert_eq!(g, expected_u8(0.0));
+    assert_eq!(b, expected_u8(0.0));
+}
+
+/// Pixel `(x, y)` of a 3x3 render, whose columns and rows sit at -1, 0 and 1.
+fn pixel_3x3(json: &str, x: usize, y: usize) -> (u8, u8, u8) {
+    let out = randomart_closure_tree::read_json(json, 3, 3).unwrap();
+    let i = (y * 3 + x) * 3;
+    let d = &out.pixels.data;
+    (d[i], d[i + 1], d[i + 2])
+}
+
+#[test]
+fn polar_terminals_at_known_pixels() {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    // R is the distance from the centre; Theta is atan2(y, x), scaled into range in `b`.
+    let json = triple_json(Node::R, Node::Theta, Node::Mult(Node::Theta.into(), num(0.25)));
+    // The origin: atan2(0, 0) = 0.
+    assert_eq!(pixel_3x3(&json, 1, 1), (expected_u8(0.0), expected_u8(0.0), expected_u8(0.0)));
+    assert_eq!(pixel_3x3(&json, 2, 1), (expected_u8(1.0), expected_u8(0.0), expected_u8(0.0)));
+    assert_eq!(pixel_3x3(&json, 1, 2).2, expected_u8(FRAC_PI_2 * 0.25));
+    assert_eq!(pixel_3x3(&json, 0, 1).2, expected_u8(PI * 0.25));
+    assert_eq!(pixel_3x3(&json, 1, 0).2, expected_u8(-FRAC_PI_2 * 0.25));
+}
+
+/// Polar terminals vary per pixel, so the backends have to agree everywhere.
+#[test]
+fn backends_agree_on_polar_trees() {
+    let trees = [
+        triple_json(Node::R, Node::Theta, Node::Sin(Node::Mult(Node::Theta.into(), num(5.0)).into())),
+        triple_json(
+            Node::Cos(Node::Add(Node::Mult(Node::R.into(), num(20.0)).into(), Node::Theta.into()).into()),
+            Node::Div(Node::Theta.into(), Node::R.into()),
+            Node::Sqrt(Node::Mult(Node::R.into(), Node::X.into()).into()),
+        ),
+    ];
+
+    for (i, json) in trees.iter().enumerate() {
+        let closure = randomart_closure_tree::read_json(json, 64, 64).unwrap();
+        let jit = randomart_cranelift_jit::read_json(json, 64, 64).unwrap();
+        assert_eq!(closure.pixels, jit.pixels, "closure and cranelift disagree on polar tree #{i}: {json}");
+    }
+}
+
+/// Threshold nodes compare rather than compute, so NaN and signed zeros have to
+/// take the same side of every comparison on each backend.
+#[test]
+fn backends_agree_on_threshold_trees() {
+    // inf * 0 evaluates to NaN at render time.
+    let nan = || Node::Mult(Node::Exp(num(100.0)).into(), num(0.0));
+    let trees = [
+        triple_json(
+            Node::IfPositive(Node::Sin(Node::Mult(Node::X.into(), num(9.0)).into()).into(), Node::Y.into(), num(-0.5)),
+            Node::Step(Node::X.into(), Node::Y.into()),
+            Node::Smoothstep(num(-0.5), num(0.5), Node::Add(Node::X.into(), Node::Y.into()).into()),
+        ),
+        triple_json(
+            Node::Min(Node::X.into(), Node::Y.into()),
+            Node::Max(Node::Mult(Node::X.into(), num(-0.0)).into(), num(0.0)),
+            Node::Smoothstep(Node::X.into(), Node::X.into(), Node::Y.into()),
+        ),
+        triple_json(
+            Node::Min(nan().into(), Node::X.into()),
+            Node::Step(nan().into(), Node::Y.into()),
+            Node::IfPositive(nan().into(), num(1.0), Node::Max(Node::Y.into(), nan().into()).into()),
+        ),
+    ];
+
+    for (i, json) in trees.iter().enumerate() {
+        let closure = randomart_closure_tree::read_json(json, 32, 32).unwrap();
+        let jit = randomart_cranelift_jit::read_json(json, 32, 32).unwrap();
+        assert_eq!(closure.pixels, jit.pixels, "closure and cranelift disagree on threshold tree #{i}: {json}");
+    }
+}
+
+#[test]
+fn backends_agree_on_extended_math() {
+    let trees = [
+        triple_json(
+            Node::Tan(Node::Mult(Node::X.into(), num(3.0)).into()),
+            Node::Atan2(Node::Y.into(), Node::X.into()),
+            Node::Log(Node::Mult(Node::X.into(), Node::Y.into()).into()),
+        ),
+        triple_json(
+            Node::Pow(Node::X.into(), Node::Mult(Node::Y.into(), num(4.0)).into()),
+            Node::Mod(Node::Mult(Node::X.into(), num(7.0)).into(), Node::Y.into()),
+            Node::Abs(Node::Sin(Node::Mult(Node::R.into(), num(10.0)).into()).into()),
+        ),
+    ];
+
+    for (i, json) in trees.iter().enumerate() {
+        let closure = randomart_closure_tree::read_json(json, 32, 32).unwrap();
+        let jit = randomart_cranelift_jit::read_json(json, 32, 32).unwrap();
+        assert_eq!(closure.pixels, jit.pixels, "closure and cranelift disagree on math tree #{i}: {json}");
+    }
+}
+
+#[test]
+fn backends_agree_on_noise() {
+    use randomart_core::noise::Permutation;
+
+    let noise = |x: Node, y: Node, seed| Node::Noise(x.into(), y.into(), Permutation::from_seed(seed));
+    let json = triple_json(
+        noise(Node::X, Node::Y, 1),
+        noise(Node::Mult(Node::R.into(), num(3.0)), Node::Theta, 2),
+        Node::Sin(noise(noise(Node::Y, Node::X, 3), Node::Exp(Node::X.into()), 4).into()),
+    );
+
+    let closure = randomart_closure_tree::read_json(&json, 64, 64).unwrap();
+    let jit = randomart_cranelift_jit::read_json(&json, 64, 64).unwrap();
+    assert_eq!(closure.pixels, jit.pixels);
+    // The pattern is not flat.
+    assert!(closure.pixels.data.iter().any(|&v| v != closure.pixels.data[0]));
+}
+
+#[test]
+fn transforms_move_the_body_at_known_pixels() {
+    // A half turn (angle 1) sends (1, 0) to (-1, 0); a quarter turn sends it to (0, 1).
+    let json = triple_json(
+        Node::Rotate(num(1.0), Node::X.into()),
+        Node::Rotate(num(0.5), Node::Y.into()),
+        Node::Warp(num(-0.5), num(0.0), Node::X.into()),
+    );
+    let (r, g, b) = pixel_3x3(&json, 2, 1);
+    assert_eq!(r, expected_u8(-1.0));
+    assert_eq!(g, expected_u8(1.0));
+    assert_eq!(b, expected_u8(0.5));
+
+    // Scale divides the coordinates and is guarded like Div: X is 0 at the origin,
+    // and Add averages it with 0.5.
+    let json = triple_json(
+        Node::Scale(num(2.0), Node::X.into()),
+        Node::Scale(num(0.0), Node::Add(Node::X.into(), num(0.5)).into()),
+        Node::Y,
+    );
+    let (r, g, _) = pixel_3x3(&json, 2, 1);
+    assert_eq!(r, expected_u8(0.5));
+    assert_eq!(g, expected_u8(0.25));
+}
+
+/// Transformed coordinates have to reach the body's terminals on every backend,
+/// including through nested transforms.
+#[test]
+fn backends_agree_on_transforms() {
+    let json = triple_json(
+        Node::Rotate(Node::R.into(), Node::Sin(Node::Mult(Node::X.into(), num(7.0)).into()).into()),
+        Node::Warp(
+            Node::Sin(Node::Mult(Node::Y.into(), num(5.0)).into()).into(),
+            Node::Cos(Node::Mult(Node::X.into(), num(3.0)).into()).into(),
+            Node::Scale(Node::Add(Node::X.into(), num(0.5)).into(), Node::Theta.into()).into(),
+        ),
+        Node::Add(
+            Node::Scale(num(0.3), Node::Rotate(Node::Y.into(), Node::Mult(Node::X.into(), Node::Y.into()).into()).into())
+                .into(),
+            Node::Y.into(),
+        ),
+    );
+
+    let closure = randomart_closure_tree::read_json(&json, 64, 64).unwrap();
+    let jit = randomart_cranelift_jit::read_json(&json, 64, 64).unwrap();
+    assert_eq!(closure.pixels, jit.pixels);
+}
+
+#[test]
+fn let_binds_its_value_where_it_is_evaluated() {
+    let var = || Box::new(Node::Var(0));
+    // The bound X is read before the rotation, so at x = 1 it stays 1 while the
+    // rotated X is -1, and they average to 0.
+    let json = triple_json(
+        Node::Let(0, Node::X.into(), Node::Rotate(num(1.0), Node::Add(var(), Node::X.into()).into()).into()),
+        Node::Let(0, num(0.5), Node::Mult(var(), var()).into()),
+        Node::Y,
+    );
+    let (r, g, _) = pixel_3x3(&json, 2, 1);
+    assert_eq!(r, expected_u8(0.0));
+    assert_eq!(g, expected_u8(0.25));
+
+    // A Var outside any Let that binds it is rejected when reading.
+    let json = triple_json(Node::Var(0), Node::X, Node::Let(1, Node::X.into(), Node::Var(1).into()));
+    assert!(randomart_closure_tree::read_json(&json, 3, 3).is_err());
+}
+
+/// Bindings have to resolve to the same values on every backend, including
+/// shadowed ids and bindings used from inside transforms.
+#[test]
+fn backends_agree_on_let_trees() {
+    let var = |id| Box::new(Node::Var(id));
+    let json = triple_json(
+        Node::Let(
+            0,
+            Node::Sin(Node::Mult(Node::X.into(), num(4.0)).into()).into(),
+            Node::Warp(var(0), Node::Mult(var(0), var(0)).into(), Node::Cos(Node::Add(Node::Y.into(), var(0)).into()).into())
+                .into(),
+        ),
+        Node::Let(
+            0,
+            Node::Theta.into(),
+            Node::Let(
+                1,
+                Node::Exp(var(0)).into(),
+                Node::Add(Node::Let(0, Node::R.into(), Node::Mult(var(0), var(1)).into()).into(), var(0)).into(),
+            )
+            .into(),
+        ),
+        Node::Let(2, Node::Mult(Node::X.into(), Node::Y.into()).into(), Node::Step(var(2), Node::X.into()).into()),
+    );
+
+    let closure = randomart_closure_tree::read_json(&json, 64, 64).unwrap();
+    let jit = randomart_cranelift_jit::read_json(&json, 64, 64).unwrap();
+    assert_eq!(closure.pixels, jit.pixels);
+}
+
+#[test]
+fn loops_feed_their_result_back_as_x() {
+    // Halving x three times from x = 1 gives 1/8. Tripling x escapes after one
+    // pass from x = 1, out of 4, and never from x = 0.
+    let json = triple_json(
+        Node::Iterate(Node::Mult(Node::X.into(), num(0.5)).into(), 3),
+        Node::Escape(Node::Mult(Node::X.into(), num(3.0)).into(), 4),
+        Node::Escape(Node::Mult(Node::X.into(), num(3.0)).into(), 4),
+    );
+    let (r, g, _) = pixel_3x3(&json, 2, 1);
+    assert_eq!(r, expected_u8(0.125));
+    assert_eq!(g, expected_u8(-0.5));
+    assert_eq!(pixel_3x3(&json, 1, 1).2, expected_u8(1.0));
+
+    // More passes than an untrusted formula may ask for is rejected.
+    let json = triple_json(Node::Iterate(Node::X.into(), 1_000_000), Node::X, Node::Y);
+    assert!(randomart_closure_tree::read_json(&json, 3, 3).is_err());
+}
+
+/// The JIT's loops have to run the same passes as the closure-tree's, with
+/// bindings and transforms inside and around them.
+#[test]
+fn backends_agree_on_loops() {
+    let square = |a: Node| Node::Mult(a.clone().into(), a.into());
+    let json = triple_json(
+        // z -> z^2 + c along x, escape-time style, with the row as c.
+        Node::Escape(Node::Add(square(Node::X).into(), Node::Mult(Node::Y.into(), num(1.8)).into()).into(), 24),
+        Node::Let(
+            0,
+            Node::Sin(Node::Mult(Node::Y.into(), num(3.0)).into()).into(),
+            Node::Iterate(
+                Node::Cos(Node::Add(Node::Mult(Node::X.into(), num(2.5)).into(), Node::Var(0).into()).into()).into(),
+                7,
+            )
+            .into(),
+        ),
+        Node::Iterate(
+            Node::Rotate(
+                num(0.1),
+                Node::Escape(Node::Let(1, Node::X.into(), Node::Mult(Node::Var(1).into(), num(2.0)).into()).into(), 5)
+                    .into(),
+            )
+            .into(),
+            3,
+        ),
+    );
+
+    let closure = randomart_closure_tree::read_json(&json, 64, 64).unwrap();
+    let jit = randomart_cranelift_jit::read_json(&json, 64, 64).unwrap();
+    assert_eq!(closure.pixels, jit.pixels);
+    assert!(closure.pixels.data.iter().any(|&v| v != closure.pixels.data[0]));
+}
+
+#[test]
+fn shapes_measure_signed_distance_from_the_pixel() {
+    // A circle of radius 0.5 at the centre: the centre pixel is 0.5 inside it,
+    // the right-hand edge 0.5 outside. A unit segment along x from the origin
+    // is 1 away from the top-centre pixel.
+    let json = triple_json(
+        Node::Circle(num(0.0), num(0.0), num(0.5)),
+        Node::BoxSdf(num(0.0), num(0.0), num(0.5), num(0.25)),
+        Node::Line(num(0.0), num(0.0), num(1.0), num(0.0)),
+    );
+    assert_eq!(pixel_3x3(&json, 1, 1), (expected_u8(-0.5), expected_u8(-0.25), expected_u8(0.0)));
+    assert_eq!(pixel_3x3(&json, 2, 1).0, expected_u8(0.5));
+    assert_eq!(pixel_3x3(&json, 1, 0).2, expected_u8(1.0));
+}
+
+/// Shapes whose parameters vary per pixel, joined smoothly and moved by
+/// transforms, have to come out the same on every backend.
+#[test]
+fn backends_agree_on_shapes() {
+    let circle = Node::Circle(num(0.3), Node::Sin(Node::Y.into()).into(), num(0.4));
+    let rect = Node::BoxSdf(num(-0.2), num(0.1), Node::Mult(Node::X.into(), num(0.5)).into(), num(0.3));
+    let line = Node::Line(num(-0.8), num(-0.8), Node::Cos(Node::X.into()).into(), num(0.7));
+    let json = triple_json(
+        Node::SmoothUnion(circle.clone().into(), rect.clone().into(), num(0.2)),
+        Node::Step(num(0.05), Node::SmoothIntersect(line.clone().into(), circle.into(), Node::Y.into()).into()),
+        Node::Rotate(Node::R.into(), Node::SmoothUnion(rect.into(), line.into(), num(0.0)).into()),
+    );
+
+    let closure = randomart_closure_tree::read_json(&json, 64, 64).unwrap();
+    let jit = randomart_cranelift_jit::read_json(&json, 64, 64).unwrap();
+    assert_eq!(closure.pixels, jit.pixels);
+}
+
+#[test]
+fn bitwise_nodes_snap_to_a_grid() {
+    // At x = 1, y = 0 the bytes are 255 and 128; at the centre both are 128.
+    let json = triple_json(
+        Node::Quantize(Node::X.into(), 3),
+        Node::Xor(Node::X.into(), Node::Y.into()),
+        Node::And(Node::X.into(), Node::Y.into()),
+    );
+    let byte = |b: f32| 2.0 * b / 255.0 - 1.0;
+    assert_eq!(pixel_3x3(&json, 2, 1), (expected_u8(1.0), expected_u8(byte(127.0)), expected_u8(byte(128.0))));
+    assert_eq!(pixel_3x3(&json, 1, 1), (expected_u8(0.0), expected_u8(-1.0), expected_u8(byte(128.0))));
+}
+
+/// The JIT snaps to levels with integer instructions of its own, so it has to
+/// floor, saturate and clamp exactly as `math::to_level` does, NaN included.
+#[test]
+fn backends_agree_on_bitwise_nodes() {
+    // Infinity times zero is NaN over the right-hand side.
+    let nan_right = Node::Mult(Node::Exp(Node::Mult(Node::X.into(), num(100.0)).into()).into(), num(0.0));
+    let json = triple_json(
+        Node::Xor(Node::Sin(Node::Mult(Node::X.into(), num(3.0)).into()).into(), Node::Y.into()),
+        Node::And(Node::Quantize(Node::R.into(), 5).into(), Node::Xor(nan_right.into(), Node::Y.into()).into()),
+        Node::Let(
+            0,
+            Node::Mult(Node::X.into(), num(4.0)).into(),
+            Node::Quantize(Node::Xor(Node::Var(0).into(), Node::Cos(Node::Y.into()).into()).into(), 7).into(),
+        ),
+    );
+
+    let closure = randomart_closure_tree::read_json(&json, 64, 64).unwrap();
+    let jit = randomart_cranelift_jit::read_json(&json, 64, 64).unwrap();
+    assert_eq!(closure.pixels, jit.pixels);
+    assert!(closure.pixels.data.iter().any(|&v| v != closure.pixels.data[0]));
+}
+
+/// A 5x4 image with every byte different from its neighbours.
+fn patterned_image() -> randomart_core::pixel_buffer::PixelBuffer {
+    let mut pixels = randomart_core::pixel_buffer::PixelBuffer::new(5, 4);
+    for (i, byte) in pixels.data.iter_mut().enumerate() {
+        *byte = (i * 37 % 256) as u8;
+    }
+    pixels
+}
+
+fn photo(filter: randomart_core::texture::Filter) -> randomart_core::texture::Textures {
+    let mut textures = randomart_core::texture::Textures::new();
+    textures.insert("photo", randomart_core::texture::Texture::new(patterned_image(), filter));
+    textures
+}
+
+fn texture(a: Node, b: Node) -> Node {
+    Node::Texture("photo".into(), a.into(), b.into())
+}
+
+#[test]
+fn texture_renders_back_to_its_image() {
+    use randomart_core::texture::Filter;
+    let tree = Node::Triple(
+        texture(Node::X, Node::Y).into(),
+        texture(Node::X, Node::Y).into(),
+        texture(Node::X, Node::Y).into(),
+    );
+    for filter in [Filter::Nearest, Filter::Bilinear] {
+        let textures = photo(filter);
+        assert_eq!(randomart_closure_tree::render(&tree, &textures, 5, 4).unwrap(), patterned_image());
+        assert_eq!(randomart_cranelift_jit::render(&tree, &textures, 5, 4).unwrap(), patterned_image());
+    }
+
+    // A slot nothing was given for is an error, not a blank image.
+    let json = triple_json(texture(Node::X, Node::Y), Node::X, Node::Y);
+    assert!(randomart_closure_tree::read_json(&json, 5, 4).is_err());
+    assert!(randomart_cranelift_jit::read_json(&json, 5, 4).is_err());
+}
+
+/// The JIT calls back into `Texture::sample` through a pointer it bakes in;
+/// moved, rotated and bound coordinates have to reach it as they do the
+/// closure-tree's.
+#[test]
+fn backends_agree_on_textures() {
+    use randomart_core::texture::Filter;
+    let tree = Node::Triple(
+        Node::Warp(
+            Node::Sin(Node::Mult(Node::Y.into(), num(4.0)).into()).into(),
+            num(0.1),
+            texture(Node::X, Node::Y).into(),
+        )
+        .into(),
+        Node::Let(
+            0,
+            Node::Mult(Node::R.into(), num(3.0)).into(),
+            Node::Add(texture(Node::Var(0), Node::Theta).into(), Node::Var(0).into()).into(),
+        )
+        .into(),
+        Node::Rotate(num(0.3), Node::Iterate(texture(Node::X, Node::Y).into(), 3).into()).into(),
+    );
+
+    for filter in [Filter::Nearest, Filter::Bilinear] {
+        let textures = photo(filter);
+        let closure = randomart_closure_tree::render(&tree, &textures, 64, 64).unwrap();
+        let jit = randomart_cranelift_jit::render(&tree, &textures, 64, 64).unwrap();
+        assert_eq!(closure, jit);
+        assert!(closure.data.iter().any(|&v| v != closure.data[0]));
+    }
+}
+
+#[test]
+fn depth_is_zero_on_the_flat_image_and_runs_through_slices() {
+    use randomart_core::render::Projection;
+    let tree = Node::Triple(Node::Z.into(), Node::X.into(), Node::Rotate(num(0.5), Node::Z.into()).into());
+    let textures = randomart_core::texture::Textures::new();
+
+    let flat = randomart_closure_tree::render(&tree, &textures, 4, 4).unwrap();
+    assert!(flat.data.chunks(3).all(|pixel| pixel[0] == expected_u8(0.0) && pixel[2] == expected_u8(0.0)));
+
+    let slices = Projection::slices(3);
+    for images in [
+        randomart_closure_tree::render_projected(&tree, &textures, &Colormap::default(), &slices, 4, 4).unwrap(),
+        randomart_cranelift_jit::render_projected(&tree, &textures, &Colormap::default(), &slices, 4, 4).unwrap(),
+    ] {
+        let depths: Vec<u8> = images.iter().map(|image| image.data[0]).collect();
+        assert_eq!(depths, [expected_u8(-1.0), expected_u8(0.0), expected_u8(1.0)]);
+        assert_eq!(images[1], flat);
+    }
+}
+
+/// The sphere and cube faces map pixels in core, so the backends see the same
+/// points there as on the flat image and have to agree bit for bit.
+#[test]
+fn backends_agree_on_projections() {
+    use randomart_core::{noise::Permutation, render::Projection};
+    let tree = Node::Triple(
+        Node::Sin(Node::Mult(Node::Add(Node::X.into(), Node::Z.into()).into(), num(7.0)).into()).into(),
+        Node::Let(
+            0,
+            Node::Atan2(Node::Z.into(), Node::R.into()).into(),
+            Node::Rotate(Node::Var(0).into(), Node::Iterate(Node::Mult(Node::Z.into(), Node::Y.into()).into(), 2).into()).into(),
+        )
+        .into(),
+        Node::Noise(Node::Mult(Node::Y.into(), num(3.0)).into(), Node::Mult(Node::Z.into(), num(3.0)).into(), Permutation::from_seed(7)).into(),
+    );
+    let textures = randomart_core::texture::Textures::new();
+
+    let mut projections = Projection::cubemap();
+    projections.push(Projection::Equirectangular);
+    projections.extend(Projection::slices(2));
+    let closure = randomart_closure_tree::render_projected(&tree, &textures, &Colormap::default(), &projections, 48, 32).unwrap();
+    let jit = randomart_cranelift_jit::render_projected(&tree, &textures, &Colormap::default(), &projections, 48, 32).unwrap();
+    assert_eq!(closure.len(), projections.len());
+    assert_eq!(closure, jit);
+    assert!(closure.windows(2).all(|pair| pair[0] != pair[1]));
+}
+
+#[test]
+fn time_is_zero_in_a_still_and_runs_through_frames() {
+    use randomart_core::render::frame_times;
+    let tree = Node::Triple(Node::T.into(), Node::Mult(Node::X.into(), Node::T.into()).into(), Node::Z.into());
+    let textures = randomart_core::texture::Textures::new();
+
+    let still = randomart_closure_tree::render(&tree, &textures, 4, 4).unwrap();
+    let times = frame_times(3, false);
+    for frames in [
+        randomart_closure_tree::render_frames(&tree, &textures, &Colormap::default(), &times, 4, 4).unwrap(),
+        randomart_cranelift_jit::render_frames(&tree, &textures, &Colormap::default(), &times, 4, 4).unwrap(),
+    ] {
+        let reds: Vec<u8> = frames.iter().map(|frame| frame.data[0]).collect();
+        assert_eq!(reds, [expected_u8(-1.0), expected_u8(0.0), expected_u8(1.0)]);
+        assert_eq!(frames[1], still);
+    }
+}
+
+#[test]
+fn backends_agree_on_frames() {
+    use randomart_core::render::frame_times;
+    let tree = Node::Triple(
+        Node::Sin(Node::Mult(Node::Add(Node::R.into(), Node::T.into()).into(), num(9.0)).into()).into(),
+        Node::Rotate(Node::T.into(), Node::Iterate(Node::Cos(Node::Mult(Node::X.into(), Node::T.into()).into()).into(), 3).into()).into(),
+        Node::Let(0, Node::T.into(), Node::Warp(Node::Var(0).into(), num(0.0), Node::Quantize(Node::X.into(), 5).into()).into()).into(),
+    );
+    let textures = randomart_core::texture::Textures::new();
+
+    let times = frame_times(6, true);
+    let closure = randomart_closure_tree::render_frames(&tree, &textures, &Colormap::default(), &times, 40, 40).unwrap();
+    let jit = randomart_cranelift_jit::render_frames(&tree, &textures, &Colormap::default(), &times, 40, 40).unwrap();
+    assert_eq!(closure, jit);
+    assert!(closure.windows(2).all(|pair| pair[0] != pair[1]));
+}
+
+/// Morphs mix the two trees' shared shape with blends of what differs; the
+/// in-between formulas are ordinary trees and render alike everywhere.
+#[test]
+fn morphs_start_and_end_on_their_formulas() {
+    use randomart_core::morph::morph;
+    let from = Node::Triple(
+        Node::Sin(Node::Mult(Node::X.into(), num(3.0)).into()).into(),
+        Node::Let(0, Node::R.into(), Node::Cos(Node::Var(0).into()).into()).into(),
+        Node::Y.into(),
+    );
+    let to = Node::Triple(
+        Node::Sin(Node::Mult(Node::X.into(), num(-5.0)).into()).into(),
+        Node::Let(0, Node::Theta.into(), Node::Mult(Node::Var(0).into(), Node::Y.into()).into()).into(),
+        Node::Iterate(Node::Sin(Node::X.into()).into(), 4).into(),
+    );
+    let textures = randomart_core::texture::Textures::new();
+    let render = |tree: &Node| randomart_closure_tree::render(tree, &textures, 32, 32).unwrap();
+
+    assert_eq!(render(&morph(&from, &to, 0.0).unwrap()), render(&from));
+    assert_eq!(render(&morph(&from, &to, 1.0).unwrap()), render(&to));
+    let middle = morph(&from, &to, 0.5).unwrap();
+    assert_eq!(render(&middle), randomart_cranelift_jit::render(&middle, &textures, 32, 32).unwrap());
+    assert_ne!(render(&middle), render(&from));
+    assert_ne!(render(&middle), render(&to));
+}
+
+/// Roots of different kinds cross-fade as images, so a `Mono` end keeps its
+/// colormap and a missing alpha counts as opaque.
+#[test]
+fn morphs_between_root_kinds_fade_the_rendered_images() {
+    use randomart_core::morph::morph_frame;
+    let mono = Node::Mono(Node::Sin(Node::Mult(Node::R.into(), num(7.0)).into()).into());
+    let triple = Node::Triple(Node::X.into(), Node::Y.into(), num(-0.5));
+    let quad = Node::Quad(Node::Y.into(), Node::X.into(), num(0.5), Node::X.into());
+    let textures = randomart_core::texture::Textures::new();
+    let colormap: Colormap = "viridis".parse().unwrap();
+    let render = |tree: &Node| {
+        randomart_closure_tree::render_frames(tree, &textures, &colormap, &[0.0], 16, 16).map(|mut frames| frames.remove(0))
+    };
+
+    assert_eq!(morph_frame(&mono, &triple, 0.0, render).unwrap(), render(&mono).unwrap());
+    assert_eq!(morph_frame(&mono, &triple, 1.0, render).unwrap(), render(&triple).unwrap());
+    let middle = morph_frame(&mono, &triple, 0.5, render).unwrap();
+    assert_ne!(middle, render(&mono).unwrap());
+    assert_ne!(middle, render(&triple).unwrap());
+
+    let start = morph_frame(&triple, &quad, 0.0, render).unwrap();
+    assert!(start.has_alpha());
+    let rgb: Vec<u8> = start.data.chunks(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    assert_eq!(rgb, render(&triple).unwrap().data);
+    assert!(start.data.chunks(4).all(|pixel| pixel[3] == 255));
+    assert_eq!(morph_frame(&triple, &quad, 1.0, render).unwrap(), render(&quad).unwrap());
+}
+
+/// A `Mono` channel is one value per pixel, so under the gray map every pixel
+/// comes out gray at that value's byte.
+#[test]
+fn mono_renders_gray_through_the_default_colormap() {
+    let tree = Node::Mono(Node::X.into());
+    let textures = randomart_core::texture::Textures::new();
+    for image in [
+        randomart_closure_tree::render(&tree, &textures, 4, 1).unwrap(),
+        randomart_cranelift_jit::render(&tree, &textures, 4, 1).unwrap(),
+    ] {
+        let pixels: Vec<&[u8]> = image.data.chunks(3).collect();
+        assert!(pixels.iter().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+        let grays: Vec<u8> = pixels.iter().map(|pixel| pixel[0]).collect();
+        assert_eq!(grays, [0, 85, 170, 255]);
+    }
+}
+
+/// The colormap is applied on the CPU after the channel is evaluated, so the
+/// backends agree on any stops, and a texture under a `Mono` reads the mean of
+/// its channels.
+#[test]
+fn backends_agree_on_colormaps() {
+    use randomart_core::texture::Filter;
+    let tree = Node::Mono(
+        Node::Add(
+            Node::Sin(Node::Mult(Node::R.into(), num(11.0)).into()).into(),
+            texture(Node::Y, Node::X).into(),
+        )
+        .into(),
+    );
+    let textures = photo(Filter::Bilinear);
+    let projections = [randomart_core::render::Projection::Plane(0.0)];
+
+    let mut rendered = Vec::new();
+    for colormap in ["viridis", "magma", "#ff0000,#000000,#00ffcc"] {
+        let colormap: Colormap = colormap.parse().unwrap();
+        let closure = randomart_closure_tree::render_projected(&tree, &textures, &colormap, &projections, 32, 32).unwrap();
+        let jit = randomart_cranelift_jit::render_projected(&tree, &textures, &colormap, &projections, 32, 32).unwrap();
+        assert_eq!(closure, jit);
+        rendered.push(closure);
+    }
+    assert!(rendered.windows(2).all(|pair| pair[0] != pair[1]));
+
+    let flat = Node::Mono(texture(Node::X, Node::Y).into());
+    let image = randomart_closure_tree::render(&flat, &photo(Filter::Nearest), 5, 4).unwrap();
+    for (pixel, source) in image.data.chunks(3).zip(patterned_image().data.chunks(3)) {
+        let mean = source.iter().map(|&b| b as u32).sum::<u32>() / 3;
+        assert!(pixel[0].abs_diff(mean as u8) <= 1, "{pixel:?} from {source:?}");
+    }
+}
+
+/// A `Quad`'s fourth channel becomes the alpha of an RGBA image, on the same
+/// scale as the colour; three-channel roots stay RGB.
+#[test]
+fn quad_renders_its_fourth_channel_as_alpha() {
+    let tree = Node::Quad(
+        Node::Sin(Node::Mult(Node::R.into(), num(5.0)).into()).into(),
+        Node::Y.into(),
+        num(0.25),
+        Node::X.into(),
+    );
+    let textures = randomart_core::texture::Textures::new();
+    let closure = randomart_closure_tree::render(&tree, &textures, 4, 2).unwrap();
+    let jit = randomart_cranelift_jit::render(&tree, &textures, 4, 2).unwrap();
+    assert_eq!(closure, jit);
+    assert!(closure.has_alpha());
+    assert_eq!(closure.data.len(), 4 * 2 * 4);
+    let alphas: Vec<u8> = closure.data.chunks(4).take(4).map(|pixel| pixel[3]).collect();
+    assert_eq!(alphas, [0, 85, 170, 255]);
+    assert!(closure.data.chunks(4).all(|pixel| pixel[2] == expected_u8(0.25)));
+
+    let opaque = Node::Triple(Node::X.into(), Node::Y.into(), num(0.25));
+    assert!(!randomart_closure_tree::render(&opaque, &textures, 4, 2).unwrap().has_alpha());
+}